@@ -0,0 +1,45 @@
+use crate::Vector3D;
+use crate::mesh::Aabb;
+
+// position and orientation the scene is viewed from, the default sits at the origin looking down -Z which is
+// exactly what the viewport did before there was a camera, so world and view space line up until it moves
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+	pub position: Vector3D,
+	pub forward: Vector3D, // unit length, kept perpendicular to up
+	pub up: Vector3D
+}
+
+impl Camera {
+	pub fn new() -> Camera {
+		Camera{ position: Vector3D::zero(), forward: Vector3D::XYZ(0.0, 0.0, -1.0), up: Vector3D::XYZ(0.0, 1.0, 0.0) }
+	}
+	
+	pub fn right(&self) -> Vector3D { self.forward.cross(self.up) }
+	
+	// turn towards target, up is rebuilt from the world Y axis (or Z when looking straight up or down)
+	pub fn look_at(&mut self, target: Vector3D) {
+		let forward = target.sub(self.position).normalize();
+		if forward.mag() == 0.0 { return; }
+		let world_up = if forward.Y.abs() > 0.999 { Vector3D::XYZ(0.0, 0.0, -forward.Y.signum()) }else { Vector3D::XYZ(0.0, 1.0, 0.0) };
+		self.forward = forward;
+		self.up = forward.cross(world_up).cross(forward).normalize();
+	}
+	
+	// world position to view space, X right, Y up and the camera looking down -Z
+	pub fn to_view(&self, point: Vector3D) -> Vector3D {
+		let d = point.sub(self.position);
+		Vector3D::XYZ(d.dot(self.right()), d.dot(self.up), -d.dot(self.forward))
+	}
+	
+	// move back along the current view direction until the bounding sphere of bounds fits inside the view,
+	// half_fov is the narrower of the horizontal and vertical half angles (Viewport::half_fov) and margin is extra space
+	// as a fraction of the radius
+	pub fn frame(&mut self, bounds: impl Into<Aabb>, margin: f32, half_fov: f32) {
+		let bounds = bounds.into();
+		let center = bounds.center();
+		let radius = bounds.radius().max(1e-3);
+		let distance = radius*(1.0 + margin)/half_fov.sin().max(1e-3);
+		self.position = center.sub(self.forward.mul(distance));
+	}
+}
@@ -0,0 +1,782 @@
+use crate::{ Color, Vector3D, Point2D };
+use crate::clamp;
+use crate::graphicsutils::Rng;
+
+use std::cmp::{ min, max };
+use std::fmt::Write;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DisplayMode {
+	HalfBlock, // 2 pixels per character cell using ▀ with separate foreground and background colors
+	Sixel, // full resolution bitmap, needs a terminal with sixel support (xterm -ti vt340, mlterm, foot)
+	Kitty, // raw rgb frames through the kitty graphics protocol, falls back to half blocks elsewhere
+	Iterm, // inline png images through iTerm2's imgcat escape sequence
+	QuarterBlock, // 2x2 pixels per character cell using the quadrant block characters, twice the horizontal resolution of HalfBlock
+	Braille, // 1 bit, 2x4 dots per character cell with no color escapes, for e-ink terminals and text art
+	Ascii, // 1 bit, 2x2 pixels per character cell approximated with plain ascii, for printing and terminals without unicode
+	Regis // experimental, only what draw_line drew (the wireframe pass) as ReGIS vectors for vt330/vt340 style terminals
+}
+
+// how colors are written for the character cell modes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorMode {
+	TrueColor,
+	Ansi256
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dithering {
+	None,
+	FloydSteinberg, // error diffusion, smoothest gradients but the pattern crawls when the image moves
+	Bayer // ordered 4x4 threshold map, stable between frames
+}
+
+// box filter resize, every output pixel averages the source area it covers (with partial pixels weighted by overlap)
+pub fn resample(pixels: &Vec<Vec<Color>>, width: usize, height: usize) -> Vec<Vec<Color>> {
+	let src_height = pixels.len();
+	let src_width = if src_height > 0 { pixels[0].len() }else { 0 };
+	if src_width == 0 || (src_width == width && src_height == height) { return pixels.clone(); }
+	let (scale_x, scale_y) = (src_width as f32 / width as f32, src_height as f32 / height as f32);
+	
+	// overlap of the source pixel range [i, i+1) with the footprint [start, end)
+	let overlap = |i: usize, start: f32, end: f32| (end.min(i as f32 + 1.0) - start.max(i as f32)).max(0.0);
+	let mut output = vec![vec![Color::black(); width]; height];
+	for h in 0..height {
+		let (y0, y1) = (h as f32 * scale_y, (h+1) as f32 * scale_y);
+		for w in 0..width {
+			let (x0, x1) = (w as f32 * scale_x, (w+1) as f32 * scale_x);
+			let (mut sum, mut total) = (Color::black(), 0.0);
+			for y in (y0 as usize)..min(y1.ceil() as usize, src_height) {
+				for x in (x0 as usize)..min(x1.ceil() as usize, src_width) {
+					let weight = overlap(y, y0, y1) * overlap(x, x0, x1);
+					sum = sum.add(pixels[y][x].mul(weight));
+					total += weight;
+			}}
+			if total > 0.0 { output[h][w] = sum.mul(1.0/total); }
+	}}
+	output
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Bloom {
+	pub threshold: f32, // brightness (hdr, before exposure) where pixels start to glow
+	pub knee: f32, // width of the soft ramp around the threshold, 0 gives a hard cutoff
+	pub intensity: f32,
+	pub radius: usize // in pixels
+}
+
+impl Bloom {
+	pub fn new(threshold: f32, knee: f32, intensity: f32, radius: usize) -> Bloom { Bloom{ threshold, knee, intensity, radius } }
+}
+
+// how much of a pixel goes into the bloom, quadratic ramp from threshold-knee to threshold+knee then linear
+fn bloom_weight(brightness: f32, threshold: f32, knee: f32) -> f32 {
+	if brightness <= 0.0 { return 0.0; }
+	let soft = clamp(0.0, 2.0*knee, brightness - threshold + knee);
+	let soft = soft*soft/(4.0*knee + 1e-5);
+	soft.max(brightness - threshold) / brightness
+}
+
+// box blur in one direction, run twice for the full 2d blur
+fn blur_pass(pixels: &Vec<Vec<Color>>, radius: usize, horizontal: bool) -> Vec<Vec<Color>> {
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+	let mut output = vec![vec![Color::black(); width]; height];
+	for h in 0..height {
+		for w in 0..width {
+			let (center, len) = if horizontal { (w, width) }else { (h, height) };
+			let (start, end) = (center.saturating_sub(radius), min(center + radius + 1, len));
+			let mut sum = Color::black();
+			for i in start..end {
+				sum = sum.add(if horizontal { pixels[h][i] }else { pixels[i][w] });
+			}
+			output[h][w] = sum.mul(1.0/(end - start) as f32);
+	}}
+	output
+}
+
+// runs on the hdr buffer before tone mapping so only things actually brighter than white glow
+pub fn apply_bloom(pixels: &Vec<Vec<Color>>, bloom: Bloom) -> Vec<Vec<Color>> {
+	let bright: Vec<Vec<Color>> = pixels.iter().map(|row| row.iter().map(|c| {
+		let brightness = c.RGB.0.max(c.RGB.1).max(c.RGB.2);
+		c.mul(bloom_weight(brightness, bloom.threshold, bloom.knee))
+	}).collect()).collect();
+	let blurred = blur_pass(&blur_pass(&bright, bloom.radius, true), bloom.radius, false);
+	pixels.iter().zip(blurred.iter()).map(|(row, glow)| row.iter().zip(glow.iter()).map(|(c, g)| c.add(g.mul(bloom.intensity))).collect()).collect()
+}
+
+// film style noise on the tone mapped image, strongest in the midtones like real grain and a new pattern every seed
+pub fn film_grain(pixels: &Vec<Vec<Color>>, amount: f32, seed: u64) -> Vec<Vec<Color>> {
+	let mut rng = Rng::new(seed);
+	pixels.iter().map(|row| row.iter().map(|c| {
+		let l = clamp(0.0, 1.0, luminance(*c));
+		let noise = (rng.float() - 0.5) * amount * 4.0*l*(1.0 - l);
+		Color::RGB(clamp(0.0, 1.0, c.RGB.0 + noise), clamp(0.0, 1.0, c.RGB.1 + noise), clamp(0.0, 1.0, c.RGB.2 + noise))
+	}).collect()).collect()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToneMapping {
+	Clamp, // anything above 1 saturates, same as before the pipeline went hdr
+	Reinhard,
+	Aces // Narkowicz's fit of the ACES filmic curve, keeps more contrast than reinhard
+}
+
+pub fn tone_map(pixels: &Vec<Vec<Color>>, operator: ToneMapping, exposure: f32) -> Vec<Vec<Color>> {
+	let curve = |c: f32| {
+		let x = (c*exposure).max(0.0);
+		match operator {
+			ToneMapping::Clamp => clamp(0.0, 1.0, x),
+			ToneMapping::Reinhard => x/(1.0 + x),
+			ToneMapping::Aces => clamp(0.0, 1.0, (x*(2.51*x + 0.03))/(x*(2.43*x + 0.59) + 0.14))
+	}};
+	pixels.iter().map(|row| row.iter().map(|c| Color::RGB(curve(c.RGB.0), curve(c.RGB.1), curve(c.RGB.2))).collect()).collect()
+}
+
+// final grade on the tone mapped image, contrast pivots around middle gray and white balance scales each channel
+pub fn color_grade(pixels: &Vec<Vec<Color>>, contrast: f32, white_balance: Color) -> Vec<Vec<Color>> {
+	let grade = |c: f32, gain: f32| clamp(0.0, 1.0, ((c*gain) - 0.5)*contrast + 0.5);
+	pixels.iter().map(|row| row.iter().map(|c| Color::RGB(
+		grade(c.RGB.0, white_balance.RGB.0),
+		grade(c.RGB.1, white_balance.RGB.1),
+		grade(c.RGB.2, white_balance.RGB.2)
+	)).collect()).collect()
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum ColorFilter {
+	None,
+	Grayscale,
+	Monochrome(Color) // luminance mapped onto a single tint, see PHOSPHOR_GREEN and PHOSPHOR_AMBER
+}
+
+pub const PHOSPHOR_GREEN: Color = Color{ RGB: (0.2, 1.0, 0.35) };
+pub const PHOSPHOR_AMBER: Color = Color{ RGB: (1.0, 0.7, 0.1) };
+
+pub fn luminance(color: Color) -> f32 {
+	0.2126*color.RGB.0 + 0.7152*color.RGB.1 + 0.0722*color.RGB.2
+}
+
+pub fn apply_filter(pixels: &Vec<Vec<Color>>, filter: ColorFilter) -> Vec<Vec<Color>> {
+	let tint = match filter {
+		ColorFilter::None => return pixels.clone(),
+		ColorFilter::Grayscale => Color::RGB(1.0, 1.0, 1.0),
+		ColorFilter::Monochrome(tint) => tint
+	};
+	pixels.iter().map(|row| row.iter().map(|c| tint.mul(luminance(*c))).collect()).collect()
+}
+
+// xterm's color cube isn't evenly spaced, these are the channel values of the 6 levels
+const CUBE_LEVELS: [f32; 6] = [0.0, 95.0/255.0, 135.0/255.0, 175.0/255.0, 215.0/255.0, 1.0];
+
+fn nearest_cube_level(c: f32) -> usize {
+	let mut best = 0;
+	for l in 1..6 { if (CUBE_LEVELS[l] - c).abs() < (CUBE_LEVELS[best] - c).abs() { best = l; }}
+	best
+}
+
+// 6x6x6 color cube starting at 16 plus the 24 step gray ramp at 232, whichever is closer
+pub fn ansi256_index(color: Color) -> usize {
+	let (R, G, B) = (clamp(0.0, 1.0, color.RGB.0), clamp(0.0, 1.0, color.RGB.1), clamp(0.0, 1.0, color.RGB.2));
+	let (r, g, b) = (nearest_cube_level(R), nearest_cube_level(G), nearest_cube_level(B));
+	let cube_error = (CUBE_LEVELS[r] - R).powi(2) + (CUBE_LEVELS[g] - G).powi(2) + (CUBE_LEVELS[b] - B).powi(2);
+	
+	let gray = clamp(0.0, 23.0, ((R + G + B)/3.0*255.0 - 8.0)/10.0).round() as usize;
+	let gray_value = (8.0 + 10.0*gray as f32)/255.0;
+	let gray_error = (gray_value - R).powi(2) + (gray_value - G).powi(2) + (gray_value - B).powi(2);
+	
+	if gray_error < cube_error { 232 + gray }else { 16 + 36*r + 6*g + b }
+}
+
+pub fn ansi256_color(index: usize) -> Color {
+	if index >= 232 {
+		let gray = (8.0 + 10.0*(index - 232) as f32)/255.0;
+		return Color::RGB(gray, gray, gray);
+	}
+	let i = index - 16;
+	Color::RGB(CUBE_LEVELS[i/36], CUBE_LEVELS[(i/6) % 6], CUBE_LEVELS[i % 6])
+}
+
+// writes character cells, only sending the color escapes that changed since the previous cell on the line
+struct CellWriter {
+	color_mode: ColorMode,
+	last_fg: Option<(usize, usize, usize)>,
+	last_bg: Option<(usize, usize, usize)>
+}
+
+impl CellWriter {
+	fn new(color_mode: ColorMode) -> CellWriter { CellWriter { color_mode, last_fg: None, last_bg: None } }
+	
+	fn push(&mut self, buf: &mut String, glyph: char, fg: Color, bg: Color) {
+		// in 256 color mode the palette index goes in the first slot
+		let code = |c: Color| match self.color_mode {
+			ColorMode::TrueColor => c.to_24bit(),
+			ColorMode::Ansi256 => (ansi256_index(c), 0, 0)
+		};
+		let (fg, bg) = (code(fg), code(bg));
+		if self.last_fg != Some(fg) {
+			let _ = match self.color_mode {
+				ColorMode::TrueColor => write!(buf, "\x1b[38;2;{};{};{}m", fg.0, fg.1, fg.2),
+				ColorMode::Ansi256 => write!(buf, "\x1b[38;5;{}m", fg.0)
+			};
+			self.last_fg = Some(fg);
+		}
+		if self.last_bg != Some(bg) {
+			let _ = match self.color_mode {
+				ColorMode::TrueColor => write!(buf, "\x1b[48;2;{};{};{}m", bg.0, bg.1, bg.2),
+				ColorMode::Ansi256 => write!(buf, "\x1b[48;5;{}m", bg.0)
+			};
+			self.last_bg = Some(bg);
+		}
+		buf.push(glyph);
+	}
+	
+	// reset before the newline so the background color doesn't bleed into the rest of the terminal line
+	fn end_line(&mut self, buf: &mut String) {
+		buf.push_str("\x1b[0m\n");
+		(self.last_fg, self.last_bg) = (None, None);
+	}
+}
+
+pub fn encode_half_blocks(buf: &mut String, pixels: &Vec<Vec<Color>>, color_mode: ColorMode) {
+	let mut cells = CellWriter::new(color_mode);
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+	for h in (0..height).step_by(2) {
+		for w in 0..width {
+			let bottom = if h+1 < height { pixels[h+1][w] }else { Color::black() }; // odd heights get a black last row
+			cells.push(buf, '▀', pixels[h][w], bottom);
+		}
+		cells.end_line(buf);
+	}
+}
+
+// indexed by which quadrants are drawn in the foreground color: 1 top left, 2 top right, 4 bottom left, 8 bottom right
+const QUADRANTS: [char; 16] = [' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█'];
+
+// each cell packs a 2x2 pixel block, split into the 2 color groups that best fit the 4 pixels
+pub fn encode_quarter_blocks(buf: &mut String, pixels: &Vec<Vec<Color>>, color_mode: ColorMode) {
+	let mut cells = CellWriter::new(color_mode);
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+	let get = |h: usize, w: usize| if h < height && w < width { pixels[h][w] }else { Color::black() };
+	let distance = |a: Color, b: Color| (a.RGB.0 - b.RGB.0).powi(2) + (a.RGB.1 - b.RGB.1).powi(2) + (a.RGB.2 - b.RGB.2).powi(2);
+	
+	for h in (0..height).step_by(2) {
+		for w in (0..width).step_by(2) {
+			let block = [get(h, w), get(h, w+1), get(h+1, w), get(h+1, w+1)];
+			// try every split of the block into foreground and background, each side drawn with its average color
+			let (mut best_mask, mut best_fg, mut best_bg, mut best_error) = (15, block[0], block[0], f32::MAX);
+			for mask in 1..16 {
+				let (mut fg, mut bg, mut fg_count, mut bg_count) = (Color::black(), Color::black(), 0.0, 0.0);
+				for q in 0..4 {
+					if mask & (1 << q) != 0 { fg = fg.add(block[q]); fg_count += 1.0; }else { bg = bg.add(block[q]); bg_count += 1.0; }
+				}
+				fg = fg.mul(1.0/fg_count);
+				bg = if bg_count > 0.0 { bg.mul(1.0/bg_count) }else { fg };
+				let error: f32 = (0..4).map(|q| distance(block[q], if mask & (1 << q) != 0 { fg }else { bg })).sum();
+				if error < best_error { (best_mask, best_fg, best_bg, best_error) = (mask, fg, bg, error); }
+			}
+			cells.push(buf, QUADRANTS[best_mask], best_fg, best_bg);
+		}
+		cells.end_line(buf);
+	}
+}
+
+// the 1 bit modes light a pixel when it's at least half as bright as white, or the other way round when ink is set
+// (glyphs mark the dark pixels, for black on white e-ink screens and paper)
+fn lit(color: Color, ink: bool) -> bool { (luminance(color) >= 0.5) != ink }
+
+// braille dot bits for the 2x4 pixels of a cell, indexed [row][column]
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+pub fn encode_braille(buf: &mut String, pixels: &Vec<Vec<Color>>, ink: bool) {
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+	for h in (0..height).step_by(4) {
+		for w in (0..width).step_by(2) {
+			let mut dots = 0;
+			for (row, bits) in BRAILLE_DOTS.iter().enumerate() {
+				for (column, bit) in bits.iter().enumerate() {
+					if h+row < height && w+column < width && lit(pixels[h+row][w+column], ink) { dots |= bit; }
+			}}
+			buf.push(char::from_u32(0x2800 + dots).unwrap()); // the blank pattern keeps empty cells the same width as the rest
+		}
+		buf.push('\n');
+	}
+}
+
+// closest ascii shapes to the quadrant characters, same indexing as QUADRANTS
+const ASCII_QUADRANTS: [char; 16] = [' ', '`', '\'', '"', ',', '[', '/', 'P', '.', '\\', ']', '7', '_', 'L', 'J', '#'];
+
+pub fn encode_ascii(buf: &mut String, pixels: &Vec<Vec<Color>>, ink: bool) {
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+	let get = |h: usize, w: usize| h < height && w < width && lit(pixels[h][w], ink);
+	for h in (0..height).step_by(2) {
+		for w in (0..width).step_by(2) {
+			let mask = get(h, w) as usize | (get(h, w+1) as usize) << 1 | (get(h+1, w) as usize) << 2 | (get(h+1, w+1) as usize) << 3;
+			buf.push(ASCII_QUADRANTS[mask]);
+		}
+		buf.push('\n');
+	}
+}
+
+// everything the rasterizer knows about the pixel behind a character cell
+pub struct CellFragment {
+	pub color: Color,
+	pub normal: Vector3D,
+	pub background: bool, // nothing was drawn here
+	pub silhouette: bool // a neighboring pixel is background or much further away in the depth buffer
+}
+
+// maps a fragment to the character drawn for it plus foreground and background colors
+pub trait CellShader {
+	fn shade(&self, fragment: &CellFragment) -> (char, Color, Color);
+}
+
+// density based ascii art, brighter fragments get denser characters
+pub struct AsciiShader;
+
+impl CellShader for AsciiShader {
+	fn shade(&self, fragment: &CellFragment) -> (char, Color, Color) {
+		let ramp = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+		let density = clamp(0.0, 1.0, luminance(fragment.color));
+		(ramp[(density*9.0).round() as usize], fragment.color, Color::black())
+	}
+}
+
+// flat fills outlined by line characters running along the silhouette, picked from the screen space normal
+pub struct EdgeShader {
+	pub line_color: Color
+}
+
+impl CellShader for EdgeShader {
+	fn shade(&self, fragment: &CellFragment) -> (char, Color, Color) {
+		if fragment.background || !fragment.silhouette { return (' ', fragment.color, fragment.color); }
+		let (nx, ny) = (fragment.normal.X, fragment.normal.Y);
+		// the edge runs perpendicular to the normal
+		let glyph = if nx.abs() > 2.0*ny.abs() { '|' }else if ny.abs() > 2.0*nx.abs() { '_' }else if nx*ny > 0.0 { '/' }else { '\\' };
+		(glyph, self.line_color, fragment.color)
+	}
+}
+
+pub fn encode_cells(buf: &mut String, pixels: &Vec<Vec<Color>>, depth: &Vec<Vec<f32>>, normals: &Vec<Vec<Vector3D>>, shader: &dyn CellShader, color_mode: ColorMode) {
+	let mut cells = CellWriter::new(color_mode);
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+	let background = |h: usize, w: usize| depth[h][w] >= 999.0; // the depth buffer's clear value
+	for h in 0..height {
+		for w in 0..width {
+			let mut silhouette = false;
+			if !background(h, w) {
+				for (nh, nw) in [(h.wrapping_sub(1), w), (h+1, w), (h, w.wrapping_sub(1)), (h, w+1)] {
+					if nh >= height || nw >= width { continue; }
+					if background(nh, nw) || (depth[nh][nw] - depth[h][w]).abs() > 0.1*depth[h][w].abs() { silhouette = true; }
+			}}
+			let fragment = CellFragment { color: pixels[h][w], normal: normals[h][w], background: background(h, w), silhouette };
+			let (glyph, fg, bg) = shader.shade(&fragment);
+			cells.push(buf, glyph, fg, bg);
+		}
+		cells.end_line(buf);
+	}
+}
+
+// ask the terminal for its primary device attributes, a 4 in the reply means it can draw sixels
+// stty puts the tty in non canonical mode with a read timeout so terminals that never answer don't hang startup
+fn query_sixel_support() -> bool {
+	use std::io::{ IsTerminal, Read, Write };
+	use std::process::{ Command, Stdio };
+	if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() { return false; }
+	
+	let saved = match Command::new("stty").arg("-g").stdin(Stdio::inherit()).output() {
+		Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+		_ => return false
+	};
+	let _ = Command::new("stty").args(["-icanon", "-echo", "min", "0", "time", "2"]).stdin(Stdio::inherit()).status();
+	print!("\x1b[c");
+	let _ = std::io::stdout().flush();
+	
+	let mut reply = Vec::new();
+	let mut byte = [0u8; 1];
+	while let Ok(1) = std::io::stdin().read(&mut byte) {
+		reply.push(byte[0]);
+		if byte[0] == b'c' || reply.len() > 64 { break; }
+	}
+	let _ = Command::new("stty").arg(&saved).stdin(Stdio::inherit()).status();
+	
+	let reply = String::from_utf8_lossy(&reply);
+	let attributes = reply.trim_start_matches("\x1b[?").trim_end_matches('c');
+	attributes.split(';').skip(1).any(|a| a == "4")
+}
+
+// picks the best output the terminal claims to handle, only runs once per process since probing writes to the tty
+pub fn detect_terminal() -> (DisplayMode, ColorMode) {
+	static DETECTED: std::sync::OnceLock<(DisplayMode, ColorMode)> = std::sync::OnceLock::new();
+	*DETECTED.get_or_init(|| {
+		let term = std::env::var("TERM").unwrap_or_default();
+		let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+		let program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+		
+		let color_mode = if colorterm == "truecolor" || colorterm == "24bit" || term.contains("direct") || kitty_supported() { ColorMode::TrueColor }
+		else { ColorMode::Ansi256 };
+		
+		let display_mode = if kitty_supported() { DisplayMode::Kitty }
+		else if program == "iTerm.app" { DisplayMode::Iterm }
+		else if term != "dumb" && query_sixel_support() { DisplayMode::Sixel }
+		else { DisplayMode::HalfBlock };
+		
+		(display_mode, color_mode)
+	})
+}
+
+// the closest color an output can actually show and roughly how far apart its levels are
+pub fn output_palette(display_mode: DisplayMode, color_mode: ColorMode) -> (fn(Color) -> Color, f32) {
+	match (display_mode, color_mode) {
+		(DisplayMode::Sixel, _) => (|c| sixel_color(sixel_register(c)), 0.2),
+		(DisplayMode::HalfBlock, ColorMode::Ansi256) | (DisplayMode::QuarterBlock, ColorMode::Ansi256) => (|c| ansi256_color(ansi256_index(c)), 0.2),
+		(DisplayMode::Braille, _) | (DisplayMode::Ascii, _) => (|c| if luminance(c) >= 0.5 { Color::RGB(1.0, 1.0, 1.0) }else { Color::black() }, 1.0),
+		_ => (|c| {
+			let (R, G, B) = c.to_24bit();
+			Color::RGB(min(R, 255) as f32/255.0, min(G, 255) as f32/255.0, min(B, 255) as f32/255.0)
+		}, 1.0/255.0)
+	}
+}
+
+// ordered dither thresholds, 0 to 15
+pub const BAYER_4X4: [[f32; 4]; 4] = [[0.0, 8.0, 2.0, 10.0], [12.0, 4.0, 14.0, 6.0], [3.0, 11.0, 1.0, 9.0], [15.0, 7.0, 13.0, 5.0]];
+
+pub fn dither(pixels: &Vec<Vec<Color>>, method: Dithering, palette: fn(Color) -> Color, step: f32) -> Vec<Vec<Color>> {
+	let mut output = pixels.clone();
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+	match method {
+		Dithering::None => (),
+		Dithering::Bayer => {
+			for h in 0..height {
+				for w in 0..width {
+					let offset = ((BAYER_4X4[h % 4][w % 4] + 0.5)/16.0 - 0.5)*step;
+					let (R, G, B) = pixels[h][w].RGB;
+					output[h][w] = palette(Color::RGB(R + offset, G + offset, B + offset));
+			}}
+		},
+		Dithering::FloydSteinberg => {
+			let spread = |output: &mut Vec<Vec<Color>>, h: usize, w: usize, error: (f32, f32, f32), fac: f32| {
+				if h >= height || w >= width { return; }
+				let (R, G, B) = output[h][w].RGB;
+				output[h][w] = Color::RGB(R + error.0*fac, G + error.1*fac, B + error.2*fac);
+			};
+			for h in 0..height {
+				for w in 0..width {
+					let (old, new) = (output[h][w], palette(output[h][w]));
+					output[h][w] = new;
+					let error = (old.RGB.0 - new.RGB.0, old.RGB.1 - new.RGB.1, old.RGB.2 - new.RGB.2);
+					spread(&mut output, h, w+1, error, 7.0/16.0);
+					if w > 0 { spread(&mut output, h+1, w-1, error, 3.0/16.0); }
+					spread(&mut output, h+1, w, error, 5.0/16.0);
+					spread(&mut output, h+1, w+1, error, 1.0/16.0);
+			}}
+		}
+	}
+	output
+}
+
+// colors are snapped to a 6x7x6 cube so the whole palette fits in the 256 registers most sixel terminals have
+fn sixel_register(color: Color) -> usize {
+	let r = (clamp(0.0, 1.0, color.RGB.0)*5.0).round() as usize;
+	let g = (clamp(0.0, 1.0, color.RGB.1)*6.0).round() as usize;
+	let b = (clamp(0.0, 1.0, color.RGB.2)*5.0).round() as usize;
+	r*42 + g*6 + b
+}
+
+fn sixel_color(register: usize) -> Color {
+	Color::RGB((register/42) as f32/5.0, ((register/6) % 7) as f32/6.0, (register % 6) as f32/5.0)
+}
+
+fn push_sixel_run(buf: &mut String, sixel: char, len: usize) {
+	if len > 3 { let _ = write!(buf, "!{len}{sixel}"); }else {
+		for _ in 0..len { buf.push(sixel); }
+	}
+}
+
+pub fn encode_sixel(buf: &mut String, pixels: &Vec<Vec<Color>>) {
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+
+	// DCS header, 1:1 pixel aspect ratio and raster size so the terminal can reserve space up front
+	let _ = write!(buf, "\x1bPq\"1;1;{width};{height}");
+	for r in 0..6 { for g in 0..7 { for b in 0..6 {
+		let _ = write!(buf, "#{};2;{};{};{}", r*42 + g*6 + b, r*20, g*100/6, b*20);
+	}}}
+	let registers: Vec<Vec<usize>> = pixels.iter().map(|row| row.iter().map(|c| sixel_register(*c)).collect()).collect();
+
+	// each sixel character covers a 1x6 column, bands are drawn once per color they contain
+	for band in (0..height).step_by(6) {
+		let band_end = min(band+6, height);
+		let mut used = [false; 252];
+		for h in band..band_end { for w in 0..width { used[registers[h][w]] = true; }}
+
+		for reg in 0..252 {
+			if !used[reg] { continue; }
+			let _ = write!(buf, "#{reg}");
+			let (mut run_char, mut run_len) = ('?', 0);
+			for w in 0..width {
+				let mut bits = 0;
+				for h in band..band_end { if registers[h][w] == reg { bits |= 1 << (h-band); }}
+				let sixel = (63 + bits) as u8 as char;
+				if sixel == run_char { run_len += 1; continue; }
+				push_sixel_run(buf, run_char, run_len);
+				(run_char, run_len) = (sixel, 1);
+			}
+			push_sixel_run(buf, run_char, run_len);
+			buf.push('$'); // return to the start of the band for the next color
+		}
+		buf.push('-');
+	}
+	buf.push_str("\x1b\\");
+}
+
+// the 8 colors every ReGIS terminal knows, by the letter that selects them
+fn regis_color(color: Color) -> char {
+	let (R, G, B) = (color.RGB.0 >= 0.5, color.RGB.1 >= 0.5, color.RGB.2 >= 0.5);
+	match (R, G, B) {
+		(false, false, false) => 'D',
+		(false, false, true) => 'B',
+		(false, true, false) => 'G',
+		(false, true, true) => 'C',
+		(true, false, false) => 'R',
+		(true, false, true) => 'M',
+		(true, true, false) => 'Y',
+		(true, true, true) => 'W'
+	}
+}
+
+// cut a line down to the part inside the screen (liang-barsky), None if none of it is
+fn clip_line(p1: Point2D, p2: Point2D, width: f32, height: f32) -> Option<(Point2D, Point2D)> {
+	let (dx, dy) = (p2.0 - p1.0, p2.1 - p1.1);
+	let (mut t0, mut t1) = (0.0f32, 1.0f32);
+	for (p, q) in [(-dx, p1.0), (dx, width - p1.0), (-dy, p1.1), (dy, height - p1.1)] {
+		if p == 0.0 {
+			if q < 0.0 { return None; }
+			continue;
+		}
+		let t = q/p;
+		if p < 0.0 { t0 = t0.max(t); }else { t1 = t1.min(t); }
+	}
+	if t0 > t1 { return None; }
+	Some(((p1.0 + t0*dx, p1.1 + t0*dy), (p1.0 + t1*dx, p1.1 + t1*dy)))
+}
+
+// screen addressing maps the logical coordinates onto the whole graphics area, so lines are sent in viewport pixels
+// and the terminal scales them. connected lines continue from the last point instead of moving there again
+pub fn encode_regis(buf: &mut String, lines: &[(Point2D, Point2D, Color)], width: usize, height: usize) {
+	let _ = write!(buf, "\x1bP0pS(A[0,0][{},{}])S(E)", width.saturating_sub(1), height.saturating_sub(1));
+	let (mut color, mut position) = (None, None);
+	for &(p1, p2, line_color) in lines.iter() {
+		if !(p1.0.is_finite() && p1.1.is_finite() && p2.0.is_finite() && p2.1.is_finite()) { continue; }
+		let Some((start, end)) = clip_line(p1, p2, width.saturating_sub(1) as f32, height.saturating_sub(1) as f32) else { continue; };
+		let (start, end) = ((start.0.round() as i64, start.1.round() as i64), (end.0.round() as i64, end.1.round() as i64));
+		let letter = regis_color(line_color);
+		if color != Some(letter) {
+			let _ = write!(buf, "W(I({letter}))");
+			color = Some(letter);
+		}
+		if position != Some(start) { let _ = write!(buf, "P[{},{}]", start.0, start.1); }
+		let _ = write!(buf, "V[{},{}]", end.0, end.1);
+		position = Some(end);
+	}
+	buf.push_str("\x1b\\");
+}
+
+pub fn base64(data: &[u8]) -> String {
+	let table = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+	let mut buf = String::with_capacity((data.len()+2)/3*4);
+	for chunk in data.chunks(3) {
+		let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+		let group = ((bytes[0] as usize) << 16) | ((bytes[1] as usize) << 8) | bytes[2] as usize;
+		for i in 0..4 {
+			if i > chunk.len() { buf.push('='); }else { buf.push(table[(group >> (18 - 6*i)) & 63] as char); }
+		}
+	}
+	buf
+}
+
+pub fn to_rgb_bytes(pixels: &Vec<Vec<Color>>) -> Vec<u8> {
+	let mut bytes = Vec::new();
+	for row in pixels.iter() {
+		for pixel in row.iter() {
+			let (R, G, B) = pixel.to_24bit();
+			bytes.extend_from_slice(&[min(R, 255) as u8, min(G, 255) as u8, min(B, 255) as u8]);
+	}}
+	bytes
+}
+
+// kitty doesn't answer a capability query without raw mode, so go by what the terminal advertises about itself
+pub fn kitty_supported() -> bool {
+	let term = std::env::var("TERM").unwrap_or_default();
+	std::env::var("KITTY_WINDOW_ID").is_ok() || term.contains("kitty") || term.contains("ghostty")
+}
+
+pub fn encode_kitty(buf: &mut String, pixels: &Vec<Vec<Color>>) {
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+	let payload = base64(&to_rgb_bytes(pixels));
+	
+	// payloads have to be split into chunks of at most 4096 bytes, m=1 means more chunks follow
+	let chunks: Vec<&[u8]> = payload.as_bytes().chunks(4096).collect();
+	for (i, chunk) in chunks.iter().enumerate() {
+		let more = if i+1 < chunks.len() { 1 }else { 0 };
+		let chunk = std::str::from_utf8(chunk).unwrap();
+		if i == 0 {
+			let _ = write!(buf, "\x1b_Ga=T,f=24,s={width},v={height},q=2,m={more};{chunk}\x1b\\");
+		}else {
+			let _ = write!(buf, "\x1b_Gm={more};{chunk}\x1b\\");
+	}}
+}
+
+fn crc32(data: &[u8]) -> u32 {
+	let mut crc = 0xffffffffu32;
+	for byte in data {
+		crc ^= *byte as u32;
+		for _ in 0..8 { crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320 }else { crc >> 1 }; }
+	}
+	!crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+	let (mut a, mut b) = (1u32, 0u32);
+	for byte in data {
+		a = (a + *byte as u32) % 65521;
+		b = (b + a) % 65521;
+	}
+	(b << 16) | a
+}
+
+fn push_png_chunk(png: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+	png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+	let mut body = kind.to_vec();
+	body.extend_from_slice(data);
+	png.extend_from_slice(&body);
+	png.extend_from_slice(&crc32(&body).to_be_bytes());
+}
+
+// binary ppm, the quickest format to write and what ffmpeg's image2 demuxer reads without any extra libraries
+pub fn encode_ppm(pixels: &Vec<Vec<Color>>) -> Vec<u8> {
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+	let mut ppm = format!("P6\n{width} {height}\n255\n").into_bytes();
+	ppm.extend_from_slice(&to_rgb_bytes(pixels));
+	ppm
+}
+
+// deflate is left uncompressed (stored blocks), frames are small and this keeps the encoder dependency free
+pub fn encode_png(pixels: &Vec<Vec<Color>>) -> Vec<u8> {
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+	let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+	let mut header = Vec::new();
+	header.extend_from_slice(&(width as u32).to_be_bytes());
+	header.extend_from_slice(&(height as u32).to_be_bytes());
+	header.extend_from_slice(&[8, 2, 0, 0, 0]); // 8 bit rgb, no interlacing
+	push_png_chunk(&mut png, b"IHDR", &header);
+
+	let mut scanlines = Vec::new();
+	for row in to_rgb_bytes(pixels).chunks(max(width*3, 1)) {
+		scanlines.push(0); // no filter
+		scanlines.extend_from_slice(row);
+	}
+	let mut zlib = vec![0x78, 0x01];
+	let blocks: Vec<&[u8]> = scanlines.chunks(65535).collect();
+	for (i, block) in blocks.iter().enumerate() {
+		zlib.push(if i+1 == blocks.len() { 1 }else { 0 });
+		zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+		zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+		zlib.extend_from_slice(block);
+	}
+	zlib.extend_from_slice(&adler32(&scanlines).to_be_bytes());
+	push_png_chunk(&mut png, b"IDAT", &zlib);
+	push_png_chunk(&mut png, b"IEND", &[]);
+	png
+}
+
+// variable width lzw as gif wants it, codes are packed lsb first and the table resets when it hits 4096 entries
+fn gif_lzw(indices: &[u8]) -> Vec<u8> {
+	const CLEAR: u16 = 256;
+	const END: u16 = 257;
+	let mut table: std::collections::HashMap<(u16, u8), u16> = std::collections::HashMap::new();
+	let (mut out, mut bits, mut bit_count) = (Vec::new(), 0u32, 0u32);
+	let (mut next_code, mut code_size) = (258u16, 9u32);
+	let mut emit = |code: u16, size: u32, out: &mut Vec<u8>| {
+		bits |= (code as u32) << bit_count;
+		bit_count += size;
+		while bit_count >= 8 {
+			out.push(bits as u8);
+			bits >>= 8;
+			bit_count -= 8;
+	}};
+	
+	emit(CLEAR, code_size, &mut out);
+	let mut prefix: Option<u16> = None;
+	for &index in indices {
+		let Some(current) = prefix else { prefix = Some(index as u16); continue; };
+		if let Some(&code) = table.get(&(current, index)) { prefix = Some(code); continue; }
+		emit(current, code_size, &mut out);
+		if next_code == 4096 {
+			emit(CLEAR, code_size, &mut out);
+			table.clear();
+			(next_code, code_size) = (258, 9);
+		}else {
+			table.insert((current, index), next_code);
+			if next_code == 1 << code_size && code_size < 12 { code_size += 1; }
+			next_code += 1;
+		}
+		prefix = Some(index as u16);
+	}
+	if let Some(current) = prefix { emit(current, code_size, &mut out); }
+	emit(END, code_size, &mut out);
+	if bit_count > 0 { out.push(bits as u8); }
+	out
+}
+
+// looping animated gif, every frame is dithered onto the xterm 256 color palette so they can all share one color table
+pub fn encode_gif(frames: &[Vec<Vec<Color>>], delay: u16) -> Vec<u8> {
+	let height = if frames.len() > 0 { frames[0].len() }else { 0 };
+	let width = if height > 0 { frames[0][0].len() }else { 0 };
+	let mut gif = b"GIF89a".to_vec();
+	gif.extend_from_slice(&(width as u16).to_le_bytes());
+	gif.extend_from_slice(&(height as u16).to_le_bytes());
+	gif.extend_from_slice(&[0xf7, 0, 0]); // global color table with 256 entries
+	for i in 0..256 {
+		let color = if i < 16 { Color::black() }else { ansi256_color(i) }; // the 16 system colors depend on the terminal, they're never picked
+		let (R, G, B) = color.to_24bit();
+		gif.extend_from_slice(&[R as u8, G as u8, B as u8]);
+	}
+	gif.extend_from_slice(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00"); // loop forever
+	
+	for frame in frames {
+		let frame = dither(frame, Dithering::FloydSteinberg, |c| ansi256_color(ansi256_index(c)), 0.2);
+		let indices: Vec<u8> = frame.iter().flat_map(|row| row.iter().map(|c| ansi256_index(*c) as u8)).collect();
+		gif.extend_from_slice(&[0x21, 0xf9, 0x04, 0x00]);
+		gif.extend_from_slice(&delay.to_le_bytes());
+		gif.extend_from_slice(&[0x00, 0x00]);
+		gif.extend_from_slice(&[0x2c, 0, 0, 0, 0]);
+		gif.extend_from_slice(&(width as u16).to_le_bytes());
+		gif.extend_from_slice(&(height as u16).to_le_bytes());
+		gif.extend_from_slice(&[0x00, 8]); // no local color table, 8 bit minimum code size
+		for block in gif_lzw(&indices).chunks(255) {
+			gif.push(block.len() as u8);
+			gif.extend_from_slice(block);
+		}
+		gif.push(0);
+	}
+	gif.push(0x3b);
+	gif
+}
+
+pub fn encode_iterm(buf: &mut String, pixels: &Vec<Vec<Color>>) {
+	let height = pixels.len();
+	let width = if height > 0 { pixels[0].len() }else { 0 };
+	let png = encode_png(pixels);
+	let _ = write!(buf, "\x1b]1337;File=inline=1;size={};width={width}px;height={height}px;preserveAspectRatio=1:{}\x07", png.len(), base64(&png));
+}
@@ -1,47 +1,18 @@
-// the functions listed here are no longer accessible through the last version's API, load_bitmap, load_material and load_object have been replaced
-// with newer versions in main.rs for easier debugging, I'm still working on optimizing write_bitmap and write_object
-// this code and the file export API will be fully replaced with a newer version when new implementations of these functions are availible
+// load_bitmap, load_material and load_object live in main.rs now, I'm still working on optimizing write_bitmap and write_object
+// (--export-obj)
+// the binary mesh format further down is what converted assets should be stored as (--convert), it loads without any
+// text parsing
 
-use crate::mesh::{ Mesh, Texture, Material };
+use crate::mesh::{ Mesh, Face, Aabb };
+use crate::graphicsutils::{ Texture, Material, Pbr, LightingMode, SpecularModel, Transparency };
+use crate::viewport::Viewport;
+use crate::scene::Scene;
+use crate::displayutils::{ resample, encode_gif, encode_png };
 use crate::{ Vector3D, Point2D, Triangle, Color };
+use crate::clamp;
 use std::fs::File;
 use std::io::{ Read, Write };
-
-use regex::Regex;
-
-pub fn load_bitmap(filename: String) -> std::io::Result<Texture> {
-	println!("importing image: {filename}.ppm");
-	let mut img = File::open(format!("{filename}.ppm"))?;
-	let mut content = String::new();
-	img.read_to_string(&mut content)?;
-
-	
-	let inline = content.replace("\n", " ");
-	let arr: Vec<&str> = inline.split(" ").filter(|c| !c.is_empty()).collect();
-	
-	print!("verifying format... ");
-	if arr[0] != "P3" {
-		println!("error: unrecongized format: {}", arr[0]);
-		return Ok(Texture::missing(1, 1, 1));
-	}
-	println!("done!");
-	let data: Vec<usize> = arr.iter().skip(1).map(|num| num.parse::<usize>().unwrap()).collect();
-	let (width, height) = (data[0], data[1]);
-	let (mut pix_row, mut pix_buf) = (Vec::new(), Vec::new());
-	
-	for h in 0..height {
-		for w in (0..width*3).step_by(3) {
-			let R = data[w+3 + width*h*3];
-			let G = data[w+4 + width*h*3];
-			let B = data[w+5 + width*h*3];
-			pix_row.push(Color::RGB((R as f32)/255.0, (G as f32)/255.0, (B as f32)/255.0));
-		}
-		pix_buf.push(pix_row.clone());
-		pix_row.clear();
-	}
-	println!("image imported successfully!\n");
-	Ok(Texture::new(width, height, pix_buf))
-}
+use std::collections::{ HashSet, HashMap };
 
 pub fn write_bitmap(filename: String, tex: Texture) -> std::io::Result<()> {
 	println!("exporting image: {filename}.ppm");
@@ -52,7 +23,7 @@ pub fn write_bitmap(filename: String, tex: Texture) -> std::io::Result<()> {
 	for h in 0..tex.height {
 		for w in 0..tex.width {
 			let pixel = tex.bitmap[h][w].RGB;
-			let string = format!("{} {} {} ", (pixel[0]*255.0) as usize, (pixel[1]*255.0) as usize, (pixel[2]*255.0) as usize);
+			let string = format!("{} {} {} ", (pixel.0*255.0) as usize, (pixel.1*255.0) as usize, (pixel.2*255.0) as usize);
 			color_data.push_str(&string);
 		}
 		color_data.push_str("\n");
@@ -64,232 +35,14 @@ pub fn write_bitmap(filename: String, tex: Texture) -> std::io::Result<()> {
 	Ok(())
 }
 
-fn load_material(filename: String) -> std::io::Result<(Material, Texture)> {
-	println!("importing material: {filename}");
-	let mut mtl = File::open(format!("{}", filename))?;
-	let mut mtl_data = String::new();
-	mtl.read_to_string(&mut mtl_data);
-
-	let attrib_patterns = vec![
-		("header", Regex::new("newmtl (?<result>[a-zA-Z0-9_-]+)\n").unwrap()),
-		("ambient", Regex::new("Ka (?<result>[0-9]+.[0-9]+ [0-9]+.[0-9]+ [0-9]+.[0-9]+)\n").unwrap()),
-		("diffuse", Regex::new("Kd (?<result>[0-9]+.[0-9]+ [0-9]+.[0-9]+ [0-9]+.[0-9]+)\n").unwrap()),
-		("specular", Regex::new("Ks (?<result>[0-9]+.[0-9]+ [0-9]+.[0-9]+ [0-9]+.[0-9]+)\n").unwrap()),
-		("highlights", Regex::new("Ns (?<result>[0-9]+.?[0-9]*)\n").unwrap()),
-		("opacity", Regex::new("d (?<result>[0-9]+.?[0-9]*)\n").unwrap()),
-		("texture", Regex::new("map_Kd (?<result>[a-zA-Z0-9_-]+).ppm").unwrap())
-	];
-	
-	let mut string_components = Vec::new();
-	let mut material = Material::missing();
-	let mut texture = Texture::missing(10, 10, 1);
-	
-	for attrib in attrib_patterns.iter() {
-		print!("reading material component {}... ", attrib.0);
-		if let Some(capture) = attrib.1.captures(&mtl_data) {
-			let component = capture["result"].to_string();
-			println!("{component}");
-			string_components.push((attrib.0, component));
-		}else {
-			println!("component missing, setting to default");
-	}}
-	
-	for component in string_components {
-		match component.0 {
-			"ambient" => {
-				let RGB: Vec<&str> = component.1.split(" ").collect();
-				material.ambient = Color::RGB(RGB[0].parse::<f32>().unwrap(), RGB[1].parse::<f32>().unwrap(), RGB[2].parse::<f32>().unwrap());
-			},
-			"diffuse" => {
-				let RGB: Vec<&str> = component.1.split(" ").collect();
-				material.diffuse = Color::RGB(RGB[0].parse::<f32>().unwrap(), RGB[1].parse::<f32>().unwrap(), RGB[2].parse::<f32>().unwrap());
-			},
-			"specular" => {
-				let RGB: Vec<&str> = component.1.split(" ").collect();
-				material.specular = Color::RGB(RGB[0].parse::<f32>().unwrap(), RGB[1].parse::<f32>().unwrap(), RGB[2].parse::<f32>().unwrap());
-			},
-			"highlights" => { material.highlights = component.1.parse::<f32>().unwrap(); },
-			"opacity" => { material.opacity = component.1.parse::<f32>().unwrap(); },
-			"texture" => { texture = load_bitmap(component.1)?; },
-			"header" => (),
-			other => {
-				println!("error: unrecognized component: {other}");
-				return Ok((Material::missing(), Texture::missing(10, 10, 1)));
-			}
-	}}
-	println!("material imported successfully!");
-	Ok((material, texture))
-}
-
-
-pub fn load_object(filename: String) -> std::io::Result<Mesh> {
-	println!("importing object: {filename}.obj");
-	let mut obj = File::open(format!("{filename}.obj"))?;
-	let mut obj_data = String::new();
-	obj.read_to_string(&mut obj_data)?;
-	
-	let match_mtl_filename = Regex::new("mtllib (?<mtlfile>[a-zA-Z0-9_-]+.mtl)").unwrap();
-	
-	let num = r"-?[0-9]+.[0-9]+e?(\+|-)?[0-9]*";
-	let match_geometry_vertex = Regex::new(&format!("v (?<x>{num}) (?<y>{num}) (?<z>{num})")).unwrap();
-	let match_vertex_normal = Regex::new(&format!("vn (?<x>{num}) (?<y>{num}) (?<z>{num})")).unwrap();
-	let match_texcoord = Regex::new(&format!("vt (?<u>{num}) (?<v>{num})")).unwrap();
-	
-	let match_face = Regex::new("f (?<v1>[0-9]+/?[0-9]*/?[0-9]*) (?<v2>[0-9]+/?[0-9]*/?[0-9]*) (?<v3>[0-9]+/?[0-9]*/?[0-9]*)").unwrap();
-
-	print!("detecting material file... ");
-	let mtl_filename: Option<String> = match match_mtl_filename.captures(&obj_data) {
-		Some(capture) => {
-			let capture_string = capture["mtlfile"].to_string();
-			println!("{capture_string}");
-			Some(capture_string)
-		},
-		None => {
-			println!("no material file");
-			None
-	}};
-	
-	print!("detecting triangle data format... ");
-	let first_face = match match_face.captures(&obj_data) {
-		Some(capture) => [capture["v1"].to_string(), capture["v2"].to_string(), capture["v3"].to_string()],
-		None => {
-			println!("error: unable to recognize mesh data!\n");
-			return Ok(Mesh::empty());
-	}};
-	
-	let face_data: Vec<&str> = first_face[0].split("/").collect();
-	let tex_coords_included = if face_data.len() >= 2 { face_data[1].len() != 0 }else { false };
-	let normals_included = if face_data.len() == 3 { face_data[2].len() != 0 }else { false };
-	
-	println!("normals: {normals_included}, texture coordinates: {tex_coords_included}");
-	
-	let mut vertices: Vec<Vector3D> = Vec::new();
-	let mut vertex_normals: Vec<Vector3D> = Vec::new();
-	let mut tex_coords: Vec<Point2D> = Vec::new();
-	let mut triangles: Vec<Triangle> = Vec::new();
-	let mut tex_tris: Vec<Triangle> = Vec::new();
-	
-	// vertex normals need to have the  came id as vertices so they're properly loaded into the mesh struct
-	let mut sorted_vertex_normals: Vec<Vector3D> = Vec::new();
-	
-	print!("reading vertex data... ");
-	for v in match_geometry_vertex.captures_iter(&obj_data) {
-		let capture = [v["x"].to_string(), v["y"].to_string(), v["z"].to_string()];
-		let vector = Vector3D::XYZ(
-			capture[0].parse::<f32>().unwrap(),
-			capture[1].parse::<f32>().unwrap(),
-			capture[2].parse::<f32>().unwrap()
-		);
-		vertices.push(vector);
-		sorted_vertex_normals.push(Vector3D::zero());
-		
-	}
-	println!("done!");
-	
-	if normals_included {
-		print!("reading vertex normal data... ");
-		for v in match_vertex_normal.captures_iter(&obj_data) {
-			let capture = [v["x"].to_string(), v["y"].to_string(), v["z"].to_string()];
-			let vector = Vector3D::XYZ(
-				capture[0].parse::<f32>().unwrap(),
-				capture[1].parse::<f32>().unwrap(),
-				capture[2].parse::<f32>().unwrap()
-			);
-			vertex_normals.push(vector);
-		}
-		println!("done!");
-	}else {
-		vertex_normals.push(Vector3D::zero());
-	}
-	
-	if tex_coords_included {
-		print!("reading texture coordinate data... ");
-		for v in match_texcoord.captures_iter(&obj_data) {
-			let capture = [v["u"].to_string(), v["v"].to_string()];
-			let vector = [
-				capture[0].parse::<f32>().unwrap(),
-				capture[1].parse::<f32>().unwrap()
-			];
-			tex_coords.push(vector);
-		}
-		println!("done!");
-	}else {
-		tex_coords.push([0.0; 2]);
-	}
-	
-	print!("reading triangle data... ");
-	for t in match_face.captures_iter(&obj_data) {
-		let captured_verts = [t["v1"].to_string(), t["v2"].to_string(), t["v3"].to_string()];
-		let mut triangle_data = Vec::new();
-		
-		for c in captured_verts.iter() {
-			let data: Vec<&str> = c.split("/").collect();
-			
-			let has_tex_coord = if data.len() >= 2 { data[1].len() != 0 }else { false };
-			let has_normal = if data.len() == 3 { data[2].len() != 0 }else { false };
-
-			if (has_tex_coord ^ tex_coords_included) || (has_normal ^ normals_included) { // xor is true if values don't match
-				println!("error: triangle has incorrect data, all triangles must have the name parameters\n");
-				return Ok(Mesh::empty());
-			}
-
-			let mut attributes = [1, 1];
-			
-			let vertex_id = data[0].parse::<usize>().unwrap();
-			if vertex_id > vertices.len() {
-				println!("error: vertex index is {} but there are {} vertices\n",vertex_id, vertices.len());
-				return Ok(Mesh::empty());
-			}else {
-				attributes[0] = vertex_id;
-			}
-			
-			if tex_coords_included {
-				let uv_id = data[1].parse::<usize>().unwrap();
-				if uv_id > tex_coords.len() {
-					println!("error: texture coordinate index is {} but there are {} texture coordinates\n", uv_id, tex_coords.len());
-					return Ok(Mesh::empty());
-				}else {
-					attributes[1] = uv_id;
-			}}
-			if normals_included {
-				let norm_id = data[2].parse::<usize>().unwrap();
-				if norm_id > vertex_normals.len() {
-					println!("error: vertex normal index is {} but there are {} vertex normals\n", norm_id, vertex_normals.len());
-					return Ok(Mesh::empty());
-				}else {
-					sorted_vertex_normals[attributes[0]-1] = vertex_normals[norm_id-1];
-			}}
-			triangle_data.push(attributes);
-		}
-		triangles.push([triangle_data[0][0]-1, triangle_data[1][0]-1, triangle_data[2][0]-1]);
-		tex_tris.push([triangle_data[0][1]-1, triangle_data[1][1]-1, triangle_data[2][1]-1]);
-	}
-	println!("done!");
-	
-	let (mut material, mut texture) = (Material::missing(), Texture::missing(1, 1, 1));
-	if mtl_filename.is_some() {
-		(material, texture) = load_material(mtl_filename.unwrap())?;
-	}
-	
-	let mut unset_face_normals = Vec::new();
-	for t in 0..triangles.len() { unset_face_normals.push(Vector3D::zero()); }
-	
-	println!("object imported successfully!\n");
-	Ok(Mesh{
-		vertices,
-		triangles,
-		tex_coords,
-		tex_tris,
-		face_normals: unset_face_normals,
-		vertex_normals: sorted_vertex_normals,
-		origin: Vector3D::zero(),
-		texture,
-		material,
-		cull_backfaces: true
-	})
+// png version of write_bitmap, for baked textures that are going to other tools
+pub fn write_texture_png(filename: &str, tex: &Texture) -> std::io::Result<()> {
+	println!("exporting image: {filename}.png");
+	File::create(format!("{filename}.png"))?.write_all(&encode_png(&tex.bitmap))?;
+	println!("image exported successfully!\n");
+	Ok(())
 }
 
-
 pub fn write_object(filename: String, mesh: Mesh, write_mtl: bool) -> std::io::Result<()> {
 	println!("exporting object: {filename}.obj");
 	let mut obj = File::create(format!("{filename}.obj"))?;
@@ -311,9 +64,9 @@ pub fn write_object(filename: String, mesh: Mesh, write_mtl: bool) -> std::io::R
 		println!("skipping material file");
 		print!("writing triangle data... ");
 		for t in 0..mesh.triangles.len() {
-			let p1 = [mesh.triangles[t][0], mesh.triangles[t][0]];
-			let p2 = [mesh.triangles[t][1], mesh.triangles[t][1]];
-			let p3 = [mesh.triangles[t][2], mesh.triangles[t][2]];
+			let p1 = [mesh.triangles[t].0, mesh.triangles[t].0];
+			let p2 = [mesh.triangles[t].1, mesh.triangles[t].1];
+			let p3 = [mesh.triangles[t].2, mesh.triangles[t].2];
 			
 			let string = format!("f {}//{} {}//{} {}//{}\n",
 				p1[0]+1, p1[1]+1,
@@ -331,15 +84,15 @@ pub fn write_object(filename: String, mesh: Mesh, write_mtl: bool) -> std::io::R
 
 	print!("writing texure coordinate data... ");
 	for v in mesh.tex_coords.iter() {
-		let string = format!("vt {:.6} {:.6}\n", v[0], v[1]);
+		let string = format!("vt {:.6} {:.6}\n", v.0, v.1);
 		obj_content.push_str(&string);
 	}
 	println!("done!");
 	print!("writing triangle data... ");
 	for t in 0..mesh.triangles.len() {
-		let p1 = [mesh.triangles[t][0], mesh.tex_tris[t][0], mesh.triangles[t][0]];
-		let p2 = [mesh.triangles[t][1], mesh.tex_tris[t][1], mesh.triangles[t][1]];
-		let p3 = [mesh.triangles[t][2], mesh.tex_tris[t][2], mesh.triangles[t][2]];
+		let p1 = [mesh.triangles[t].0, mesh.tex_tris[t].0, mesh.triangles[t].0];
+		let p2 = [mesh.triangles[t].1, mesh.tex_tris[t].1, mesh.triangles[t].1];
+		let p3 = [mesh.triangles[t].2, mesh.tex_tris[t].2, mesh.triangles[t].2];
 		
 		let string = format!("f {}/{}/{} {}/{}/{} {}/{}/{}\n",
 			p1[0]+1, p1[1]+1, p1[2]+1,
@@ -364,10 +117,14 @@ pub fn write_object(filename: String, mesh: Mesh, write_mtl: bool) -> std::io::R
 	let ns = mesh.material.highlights;
 	let d = mesh.material.opacity;
 	
-	let ka_str = format!("Ka {:.2} {:.2} {:.2}\n", ka[0], ka[1], ka[2]);
-	let kd_str = format!("Kd {:.2} {:.2} {:.2}\n", kd[0], kd[1], kd[2]);
-	let ks_str = format!("Ks {:.2} {:.2} {:.2}\n", ks[0], ks[1], ks[2]);
-	let other_args = format!("Ns {ns}\nd {d}\nmap_Kd {filename}_texture.ppm\n");
+	let ka_str = format!("Ka {:.2} {:.2} {:.2}\n", ka.0, ka.1, ka.2);
+	let kd_str = format!("Kd {:.2} {:.2} {:.2}\n", kd.0, kd.1, kd.2);
+	let ks_str = format!("Ks {:.2} {:.2} {:.2}\n", ks.0, ks.1, ks.2);
+	let illum = mesh.material.illumination_model();
+	let mut other_args = format!("Ns {ns}\nd {d}\nillum {illum}\nmap_Kd {filename}_texture.ppm\n");
+	if let Some(pbr) = &mesh.material.pbr { other_args += &format!("Pm {}\nPr {}\n", pbr.metallic, pbr.roughness); }
+	let ke = mesh.material.emissive.RGB;
+	if ke != (0.0, 0.0, 0.0) { other_args += &format!("Ke {:.2} {:.2} {:.2}\n", ke.0, ke.1, ke.2); }
 	
 	mtl_content.push_str(&mtl_header);
 	mtl_content.push_str(&ka_str);
@@ -384,3 +141,645 @@ pub fn write_object(filename: String, mesh: Mesh, write_mtl: bool) -> std::io::R
 	Ok(())
 }
 
+
+// binary mesh layout, everything little endian:
+//   magic "CLIM", u32 version
+//   u32 counts: vertices, vertex normals, face normals, texture coordinates, triangles, texture triangles, authored
+//   normals, normal triangles, vertex colors, face materials, materials
+//   vertices, vertex normals and face normals as 3 f32 each, texture coordinates as 2 f32, triangles and texture
+//   triangles as 3 u32, authored normals as 3 f32, normal triangles as 3 u32, vertex colors as 3 f32, face materials
+//   as u32, the origin as 3 f32
+//   the mesh's own material and texture, then one material and texture per entry in materials. a material is its
+//   colors (ambient, diffuse, specular, emissive as 3 f32), highlights and opacity (f32), the lighting mode and toon
+//   bands, specular highlights, specular model and transparency (u32 each), a u32 flag with metallic and roughness
+//   (f32) after it when it's pbr, then the texture and every optional map
+//   textures are a u32 flag (0 for a missing optional map), u32 width and height, then every pixel as 3 f32 by row
+// version 1 files (vertices, vertex normals, texture coordinates, triangles and texture triangles only) still load.
+// meshes with a fragment or vertex shader can't be written, the shaders are code. the revision and lighting cache
+// are rebuilt as the mesh is used
+const MESH_MAGIC: &[u8; 4] = b"CLIM";
+const MESH_VERSION: u32 = 2;
+
+fn push_u32(buf: &mut Vec<u8>, value: u32) { buf.extend_from_slice(&value.to_le_bytes()); }
+fn push_f32(buf: &mut Vec<u8>, value: f32) { buf.extend_from_slice(&value.to_le_bytes()); }
+
+fn push_vector(buf: &mut Vec<u8>, v: Vector3D) {
+	push_f32(buf, v.X);
+	push_f32(buf, v.Y);
+	push_f32(buf, v.Z);
+}
+
+fn push_color(buf: &mut Vec<u8>, c: Color) { push_vector(buf, Vector3D::XYZ(c.RGB.0, c.RGB.1, c.RGB.2)); }
+
+fn push_triangle(buf: &mut Vec<u8>, t: Triangle) {
+	push_u32(buf, t.0 as u32);
+	push_u32(buf, t.1 as u32);
+	push_u32(buf, t.2 as u32);
+}
+
+fn push_texture(buf: &mut Vec<u8>, texture: Option<&Texture>) {
+	let Some(texture) = texture else { return push_u32(buf, 0); };
+	push_u32(buf, 1);
+	push_u32(buf, texture.width as u32);
+	push_u32(buf, texture.height as u32);
+	for pixel in texture.bitmap.iter().flatten() { push_color(buf, *pixel); }
+}
+
+fn push_material(buf: &mut Vec<u8>, material: &Material, texture: &Texture) {
+	for color in [material.ambient, material.diffuse, material.specular, material.emissive] { push_color(buf, color); }
+	push_f32(buf, material.highlights);
+	push_f32(buf, material.opacity);
+	let (mode, bands) = match material.mode {
+		LightingMode::Flat => (0, 0),
+		LightingMode::Smooth => (1, 0),
+		LightingMode::Gouraud => (2, 0),
+		LightingMode::Toon(bands) => (3, bands),
+		LightingMode::None => (4, 0)
+	};
+	push_u32(buf, mode);
+	push_u32(buf, bands as u32);
+	push_u32(buf, material.specular_highlights as u32);
+	push_u32(buf, match material.specular_model { SpecularModel::Phong => 0, SpecularModel::BlinnPhong => 1 });
+	push_u32(buf, match material.transparency { Transparency::Blend => 0, Transparency::ScreenDoor => 1, Transparency::Noise => 2 });
+	push_u32(buf, material.pbr.is_some() as u32);
+	if let Some(pbr) = &material.pbr {
+		push_f32(buf, pbr.metallic);
+		push_f32(buf, pbr.roughness);
+	}
+	push_texture(buf, Some(texture));
+	for map in [&material.specular_map, &material.highlight_map, &material.opacity_map, &material.emissive_map, &material.normal_map] {
+		push_texture(buf, map.as_ref());
+	}
+	if let Some(pbr) = &material.pbr {
+		push_texture(buf, pbr.metallic_map.as_ref());
+		push_texture(buf, pbr.roughness_map.as_ref());
+	}
+}
+
+fn read_u32(data: &mut &[u8]) -> std::io::Result<u32> {
+	let mut bytes = [0; 4];
+	data.read_exact(&mut bytes)?;
+	Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32(data: &mut &[u8]) -> std::io::Result<f32> {
+	let mut bytes = [0; 4];
+	data.read_exact(&mut bytes)?;
+	Ok(f32::from_le_bytes(bytes))
+}
+
+fn read_vector(data: &mut &[u8]) -> std::io::Result<Vector3D> {
+	Ok(Vector3D::XYZ(read_f32(data)?, read_f32(data)?, read_f32(data)?))
+}
+
+fn read_color(data: &mut &[u8]) -> std::io::Result<Color> {
+	Ok(Color::RGB(read_f32(data)?, read_f32(data)?, read_f32(data)?))
+}
+
+fn read_triangle(data: &mut &[u8]) -> std::io::Result<Triangle> {
+	Ok((read_u32(data)? as usize, read_u32(data)? as usize, read_u32(data)? as usize))
+}
+
+fn invalid(message: &str) -> std::io::Error { std::io::Error::new(std::io::ErrorKind::InvalidData, message) }
+
+// count entries of size bytes each, checked against what's left before anything is allocated for them so a corrupted
+// count can't make us allocate gigabytes
+fn read_array<T>(data: &mut &[u8], count: usize, size: usize, read: fn(&mut &[u8]) -> std::io::Result<T>) -> std::io::Result<Vec<T>> {
+	if count.checked_mul(size).map_or(true, |bytes| bytes > data.len()) { return Err(invalid("file is smaller than the header says")); }
+	(0..count).map(|_| read(data)).collect()
+}
+
+fn read_texture(data: &mut &[u8]) -> std::io::Result<Option<Texture>> {
+	if read_u32(data)? == 0 { return Ok(None); }
+	let (width, height) = (read_u32(data)? as usize, read_u32(data)? as usize);
+	let pixels = read_array(data, width.checked_mul(height).ok_or_else(|| invalid("texture is too large"))?, 12, read_color)?;
+	let bitmap = if width == 0 { Vec::new() }else { pixels.chunks(width).map(|row| row.to_vec()).collect() };
+	Ok(Some(Texture::new(width, height, bitmap)))
+}
+
+fn read_material(data: &mut &[u8]) -> std::io::Result<(Material, Texture)> {
+	let (ambient, diffuse, specular, emissive) = (read_color(data)?, read_color(data)?, read_color(data)?, read_color(data)?);
+	let (highlights, opacity) = (read_f32(data)?, read_f32(data)?);
+	let (mode, bands) = (read_u32(data)?, read_u32(data)? as usize);
+	let mode = match mode {
+		0 => LightingMode::Flat,
+		1 => LightingMode::Smooth,
+		2 => LightingMode::Gouraud,
+		3 => LightingMode::Toon(bands),
+		4 => LightingMode::None,
+		_ => return Err(invalid("unknown lighting mode"))
+	};
+	let mut material = Material::new(ambient, diffuse, specular, highlights, opacity, mode);
+	material.emissive = emissive;
+	material.specular_highlights = read_u32(data)? != 0;
+	material.specular_model = match read_u32(data)? { 0 => SpecularModel::Phong, 1 => SpecularModel::BlinnPhong, _ => return Err(invalid("unknown specular model")) };
+	material.transparency = match read_u32(data)? { 0 => Transparency::Blend, 1 => Transparency::ScreenDoor, 2 => Transparency::Noise, _ => return Err(invalid("unknown transparency")) };
+	if read_u32(data)? != 0 { material.pbr = Some(Pbr::new(read_f32(data)?, read_f32(data)?)); }
+	let texture = read_texture(data)?.ok_or_else(|| invalid("material without a texture"))?;
+	material.specular_map = read_texture(data)?;
+	material.highlight_map = read_texture(data)?;
+	material.opacity_map = read_texture(data)?;
+	material.emissive_map = read_texture(data)?;
+	material.normal_map = read_texture(data)?;
+	if let Some(pbr) = material.pbr.as_mut() {
+		pbr.metallic_map = read_texture(data)?;
+		pbr.roughness_map = read_texture(data)?;
+	}
+	Ok((material, texture))
+}
+
+// meshes with shaders are refused with an InvalidInput error, nothing else is lost on the way through the file
+pub fn write_mesh_binary(filename: &str, mesh: &Mesh) -> std::io::Result<()> {
+	println!("exporting binary mesh: {filename}.mesh");
+	if mesh.shader.is_some() || mesh.vertex_shader.is_some() {
+		println!("error: meshes with shaders can't be stored in the binary format\n");
+		return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "mesh has a shader"));
+	}
+	print!("writing geometry and materials... ");
+	let buf = encode_mesh(mesh);
+	println!("done!");
+	File::create(format!("./objects/{filename}.mesh"))?.write_all(&buf)?;
+	println!("binary mesh exported successfully!\n");
+	Ok(())
+}
+
+// the bytes write_mesh_binary stores, see the layout above
+pub fn encode_mesh(mesh: &Mesh) -> Vec<u8> {
+	let mut buf = Vec::with_capacity(52 + (mesh.vertices.len() + mesh.vertex_normals.len() + mesh.face_normals.len() + mesh.normals.len())*12 + mesh.tex_coords.len()*8);
+	buf.extend_from_slice(MESH_MAGIC);
+	push_u32(&mut buf, MESH_VERSION);
+	for count in [mesh.vertices.len(), mesh.vertex_normals.len(), mesh.face_normals.len(), mesh.tex_coords.len(), mesh.triangles.len(), mesh.tex_tris.len(),
+		mesh.normals.len(), mesh.normal_tris.len(), mesh.vertex_colors.len(), mesh.face_materials.len(), mesh.materials.len()] {
+		push_u32(&mut buf, count as u32);
+	}
+	for v in mesh.vertices.iter().chain(mesh.vertex_normals.iter()).chain(mesh.face_normals.iter()) { push_vector(&mut buf, *v); }
+	for uv in mesh.tex_coords.iter() {
+		push_f32(&mut buf, uv.0);
+		push_f32(&mut buf, uv.1);
+	}
+	for t in mesh.triangles.iter().chain(mesh.tex_tris.iter()) { push_triangle(&mut buf, *t); }
+	for n in mesh.normals.iter() { push_vector(&mut buf, *n); }
+	for t in mesh.normal_tris.iter() { push_triangle(&mut buf, *t); }
+	for c in mesh.vertex_colors.iter() { push_color(&mut buf, *c); }
+	for m in mesh.face_materials.iter() { push_u32(&mut buf, *m as u32); }
+	push_vector(&mut buf, mesh.origin);
+	push_material(&mut buf, &mesh.material, &mesh.texture);
+	for (material, texture) in mesh.materials.iter() { push_material(&mut buf, material, texture); }
+	buf
+}
+
+// a mesh from encode_mesh's bytes. malformed data is an InvalidData error, truncated data UnexpectedEof
+pub fn decode_mesh(mut data: &[u8]) -> std::io::Result<Mesh> {
+	let data = &mut data;
+	let mut magic = [0; 4];
+	data.read_exact(&mut magic)?;
+	if &magic != MESH_MAGIC { return Err(invalid("not a binary mesh file")); }
+	let version = read_u32(data)?;
+	if version != 1 && version != MESH_VERSION { return Err(invalid(&format!("unsupported version {version}, expected {MESH_VERSION}"))); }
+	
+	let mut mesh = Mesh::empty();
+	if version == 1 {
+		let mut counts = [0; 5];
+		for count in counts.iter_mut() { *count = read_u32(data)? as usize; }
+		let [vertex_count, normal_count, uv_count, triangle_count, tex_tri_count] = counts;
+		mesh.vertices = read_array(data, vertex_count, 12, read_vector)?;
+		let vertex_normals = read_array(data, normal_count, 12, read_vector)?;
+		mesh.tex_coords = read_array(data, uv_count, 8, |data| Ok((read_f32(data)?, read_f32(data)?)))?;
+		mesh.triangles = read_array(data, triangle_count, 12, read_triangle)?;
+		mesh.tex_tris = read_array(data, tex_tri_count, 12, read_triangle)?;
+		if !data.is_empty() { return Err(invalid("file size doesn't match the header")); }
+		if normal_count != 0 && normal_count != vertex_count { return Err(invalid("vertex normal count doesn't match the vertices")); }
+		// version 1 didn't store face normals or the origin, so they're derived like the text loaders do
+		(mesh.vertex_normals, mesh.face_normals) = (vec![Vector3D::zero(); vertex_count], vec![Vector3D::zero(); triangle_count]);
+		if (0..mesh.triangles.len()).any(|t| !mesh.triangle_in_range(t)) { return Err(invalid("triangle indices out of range")); }
+		mesh.recalculate_normals();
+		if normal_count != 0 { mesh.vertex_normals = vertex_normals; } // keep the stored normals, they may have been authored rather than derived
+		mesh.origin = mesh.center();
+	}else {
+		let mut counts = [0; 11];
+		for count in counts.iter_mut() { *count = read_u32(data)? as usize; }
+		let [vertex_count, normal_count, face_normal_count, uv_count, triangle_count, tex_tri_count, authored_count, normal_tri_count, color_count, face_material_count, material_count] = counts;
+		mesh.vertices = read_array(data, vertex_count, 12, read_vector)?;
+		mesh.vertex_normals = read_array(data, normal_count, 12, read_vector)?;
+		mesh.face_normals = read_array(data, face_normal_count, 12, read_vector)?;
+		mesh.tex_coords = read_array(data, uv_count, 8, |data| Ok((read_f32(data)?, read_f32(data)?)))?;
+		mesh.triangles = read_array(data, triangle_count, 12, read_triangle)?;
+		mesh.tex_tris = read_array(data, tex_tri_count, 12, read_triangle)?;
+		mesh.normals = read_array(data, authored_count, 12, read_vector)?;
+		mesh.normal_tris = read_array(data, normal_tri_count, 12, read_triangle)?;
+		mesh.vertex_colors = read_array(data, color_count, 12, read_color)?;
+		mesh.face_materials = read_array(data, face_material_count, 4, |data| Ok(read_u32(data)? as usize))?;
+		mesh.origin = read_vector(data)?;
+		(mesh.material, mesh.texture) = read_material(data)?;
+		// every material takes at least 112 bytes, which keeps a corrupted count from reserving more than the file holds
+		mesh.materials = read_array(data, material_count, 112, read_material)?;
+		if !data.is_empty() { return Err(invalid("file size doesn't match the header")); }
+		if (0..mesh.triangles.len()).any(|t| !mesh.triangle_in_range(t)) { return Err(invalid("triangle indices out of range")); }
+	}
+	Ok(mesh)
+}
+
+
+// collects post processed frames from the render loop and saves them as an animated gif
+pub struct FrameRecorder {
+	pub frames: Vec<Vec<Vec<Color>>>,
+	pub delay: u16 // hundredths of a second each frame stays on screen
+}
+
+impl FrameRecorder {
+	pub fn new(delay: u16) -> FrameRecorder { FrameRecorder{ frames: Vec::new(), delay } }
+	
+	// call after drawing and before clear_screen, frames are scaled to the size of the first one if the viewport changes
+	pub fn record(&mut self, screen: &Viewport) {
+		let frame = screen.graded_pixels();
+		match self.frames.first() {
+			Some(first) if first.len() != frame.len() || first[0].len() != frame[0].len() => {
+				let (width, height) = (first[0].len(), first.len());
+				self.frames.push(resample(&frame, width, height));
+			},
+			_ => self.frames.push(frame)
+		}
+	}
+	
+	pub fn save(&self, filename: &str) -> std::io::Result<()> {
+		println!("exporting animation: {filename}.gif");
+		print!("encoding {} frames... ", self.frames.len());
+		let gif = encode_gif(&self.frames, self.delay);
+		println!("done!");
+		File::create(format!("{filename}.gif"))?.write_all(&gif)?;
+		println!("animation exported successfully!\n");
+		Ok(())
+	}
+}
+
+
+// a triangle as screen's camera sees it, for the vector exporters
+struct ProjectedFace {
+	face: Face,
+	corners: [Point2D; 3], // viewport pixels
+	inv_z: [f32; 3], // what the depth buffer interpolates across the screen
+	front: bool // same winding test as the rasterizer, back faces have a negative area
+}
+
+// triangles touching the camera plane are left out, clip against a near plane first to keep them
+fn project_faces(screen: &Viewport, mesh: &Mesh) -> Vec<ProjectedFace> {
+	mesh.faces().filter_map(|face| {
+		let view = face.positions.map(|p| screen.camera.to_view(p));
+		if view.iter().any(|p| p.Z.abs() < 1e-4 || !p.is_finite()) { return None; }
+		let corners = view.map(|p| screen.project(p));
+		let (side_1, side_2) = ((corners[0].0 - corners[1].0, corners[0].1 - corners[1].1), (corners[0].0 - corners[2].0, corners[0].1 - corners[2].1));
+		Some(ProjectedFace{ face, corners, inv_z: view.map(|p| 1.0/p.Z), front: side_1.0*side_2.1 - side_1.1*side_2.0 > 0.0 })
+	}).collect()
+}
+
+// each edge once, as corner indices into the face it was first seen in
+fn unique_edges(faces: &[ProjectedFace]) -> Vec<(usize, usize, usize)> {
+	let mut seen = HashSet::new();
+	let mut edges = Vec::new();
+	for (f, projected) in faces.iter().enumerate() {
+		let vertices = [projected.face.vertices.0, projected.face.vertices.1, projected.face.vertices.2];
+		for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+			let (a, b) = (vertices[i], vertices[j]);
+			if seen.insert((a.min(b), a.max(b))) { edges.push((f, i, j)); }
+	}}
+	edges
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SvgStyle {
+	Wireframe, // every edge, including the ones at the back
+	HiddenLine, // outlines of the front facing triangles only, faces are painted back to front in the background color
+	Filled // front facing triangles painted back to front with flat shading, like Visibility::Painter
+}
+
+// the meshes as seen by screen's camera, in the viewport's pixel coordinates so it lines up with a screenshot
+pub fn write_svg(filename: &str, screen: &Viewport, meshes: &[&Mesh], style: SvgStyle) -> std::io::Result<()> {
+	println!("exporting svg: {filename}.svg");
+	let hex = |color: Color| { let [R, G, B] = color.to_rgb8(); format!("#{R:02x}{G:02x}{B:02x}") };
+	let point = |p: Point2D| format!("{:.2},{:.2}", p.0, p.1);
+	let (background, line) = (Color::RGB(1.0, 1.0, 1.0), Color::black());
+	let (width, height) = screen.size();
+	let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n", width, height);
+	svg.push_str(&format!("<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n", hex(background)));
+	
+	print!("projecting triangles... ");
+	// screen corners, then the sum of 1/z over the corners that draw_mesh sorts painter mode by, and the flat color
+	let mut faces: Vec<([Point2D; 3], f32, Color)> = Vec::new();
+	let mut edges = String::new();
+	for mesh in meshes.iter() {
+		let projected = project_faces(screen, mesh);
+		if style == SvgStyle::Wireframe {
+			for (f, i, j) in unique_edges(&projected) {
+				edges.push_str(&format!("M{}L{}", point(projected[f].corners[i]), point(projected[f].corners[j])));
+			}
+			continue;
+		}
+		for ProjectedFace{ face, corners, inv_z, front } in projected {
+			if !front { continue; }
+			let (material, _) = mesh.face_material(face.index);
+			let normal = face.face_normal.normalize();
+			let weights = screen.shading_weights;
+			let mut color = material.ambient.hadamard(screen.ambient.radiance(normal)).mul(weights.ambient);
+			let bounds = Aabb::around(face.positions);
+			for light in screen.lights.iter().filter(|light| bounds.map_or(light.enabled, |b| light.reaches(b.center(), b.radius()))) {
+				let diffuse = clamp(0.0, 1.0, normal.dot(light.position.normalize()));
+				color = color.add(material.diffuse.mul(weights.diffuse*(1.0 + diffuse)*light.intensity));
+			}
+			faces.push((corners, inv_z.iter().sum(), color));
+		}
+	}
+	println!("done!");
+	
+	print!("writing shapes... ");
+	if style == SvgStyle::Wireframe {
+		svg.push_str(&format!("<path d=\"{edges}\" fill=\"none\" stroke=\"{}\" stroke-width=\"0.5\" stroke-linejoin=\"round\"/>\n", hex(line)));
+	}
+	faces.sort_by(|a, b| b.1.total_cmp(&a.1)); // farthest first
+	for (corners, _, color) in faces.iter() {
+		let points = corners.map(point).join(" ");
+		let (fill, stroke) = match style {
+			SvgStyle::HiddenLine => (hex(background), hex(line)),
+			_ => (hex(*color), hex(*color)) // outlined in the fill color so neighbouring faces don't show a seam
+		};
+		svg.push_str(&format!("<polygon points=\"{points}\" fill=\"{fill}\" stroke=\"{stroke}\" stroke-width=\"0.5\" stroke-linejoin=\"round\"/>\n"));
+	}
+	svg.push_str("</svg>\n");
+	println!("done!");
+	File::create(format!("{filename}.svg"))?.write_all(svg.as_bytes())?;
+	println!("svg exported successfully!\n");
+	Ok(())
+}
+
+// hpgl plot area in plotter units (0.025mm), a landscape A4 page with some margin
+const PLOT_SIZE: (f32, f32) = (10000.0, 7000.0);
+
+// hidden line plot for pen plotters. draw the meshes with the depth buffer on first: the edges of front facing
+// triangles are walked in half pixel steps and only the runs at least as close as what the depth buffer holds are
+// plotted, so the visibility is exactly what the rasterizer decided
+pub fn write_hpgl(filename: &str, screen: &Viewport, meshes: &[&Mesh]) -> std::io::Result<()> {
+	println!("exporting plot: {filename}.hpgl");
+	let (width, height) = screen.size();
+	let depth = screen.depth();
+	let scale = (PLOT_SIZE.0/width.max(1) as f32).min(PLOT_SIZE.1/height.max(1) as f32);
+	// plotter y goes up
+	let to_plot = |p: Point2D| format!("{},{}", (p.0*scale).round() as i64, ((height as f32 - p.1)*scale).round() as i64);
+	let visible = |p: Point2D, inv_z: f32| {
+		if !(p.0 >= 0.0 && p.1 >= 0.0 && p.0 < width as f32 && p.1 < height as f32) { return false; }
+		let stored = depth[p.1 as usize][p.0 as usize];
+		inv_z <= stored + 1e-3 + 0.01*stored.abs() // the edge's own faces wrote nearly the same depth
+	};
+	
+	let mut plot = String::from("IN;SP1;\n");
+	let mut strokes = 0;
+	print!("tracing visible edges... ");
+	for mesh in meshes.iter() {
+		let projected = project_faces(screen, mesh);
+		let front: Vec<ProjectedFace> = projected.into_iter().filter(|f| f.front).collect();
+		for (f, i, j) in unique_edges(&front) {
+			let (start, end) = (front[f].corners[i], front[f].corners[j]);
+			let (start_z, end_z) = (front[f].inv_z[i], front[f].inv_z[j]);
+			let steps = (2.0*((end.0 - start.0).abs() + (end.1 - start.1).abs())).ceil().clamp(1.0, 10000.0) as usize;
+			let mut run: Option<(Point2D, Point2D)> = None;
+			for s in 0..=steps {
+				let t = s as f32/steps as f32;
+				let p = (start.0 + (end.0 - start.0)*t, start.1 + (end.1 - start.1)*t);
+				if visible(p, start_z + (end_z - start_z)*t) {
+					run = Some((run.map_or(p, |r| r.0), p));
+					continue;
+				}
+				if let Some((from, to)) = run.take() {
+					plot.push_str(&format!("PU{};PD{};\n", to_plot(from), to_plot(to)));
+					strokes += 1;
+				}
+			}
+			if let Some((from, to)) = run {
+				plot.push_str(&format!("PU{};PD{};\n", to_plot(from), to_plot(to)));
+				strokes += 1;
+			}
+		}
+	}
+	plot.push_str("PU;SP0;\n");
+	println!("done! {strokes} strokes");
+	File::create(format!("{filename}.hpgl"))?.write_all(plot.as_bytes())?;
+	println!("plot exported successfully!\n");
+	Ok(())
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StlFormat {
+	Ascii,
+	Binary // smaller and what most slicers expect
+}
+
+// every face as a facet for slicers and printing. normals are worked out from the winding instead of taken from
+// face_normals, so meshes edited since their normals were derived still get the right ones. stl has no units, uvs or
+// materials, and faces with a non finite corner are left out
+pub fn write_stl(mesh: &Mesh, path: &str, format: StlFormat) -> std::io::Result<()> {
+	println!("exporting stl: {path}");
+	print!("writing triangle data... ");
+	let facets: Vec<[Vector3D; 4]> = mesh.faces().filter(|face| face.positions.iter().all(|p| p.is_finite())).map(|face| {
+		let [p1, p2, p3] = face.positions;
+		[p2.sub(p1).cross(p3.sub(p1)).normalize(), p1, p2, p3]
+	}).collect();
+	
+	let data = match format {
+		StlFormat::Binary => {
+			// the header mustn't start with "solid" or readers take the file for ascii
+			let mut data = b"binary stl from CLI-rendering-engine".to_vec();
+			data.resize(80, 0);
+			data.extend_from_slice(&(facets.len() as u32).to_le_bytes());
+			for facet in facets.iter() {
+				for v in facet { data.extend([v.X, v.Y, v.Z].iter().flat_map(|c| c.to_le_bytes())); }
+				data.extend_from_slice(&0u16.to_le_bytes()); // attribute byte count, unused
+			}
+			data
+		},
+		StlFormat::Ascii => {
+			let name = path.rsplit(['/', '\\']).next().unwrap_or(path).trim_end_matches(".stl");
+			let mut text = format!("solid {name}\n");
+			for [n, p1, p2, p3] in facets.iter() {
+				text += &format!("  facet normal {:e} {:e} {:e}\n    outer loop\n", n.X, n.Y, n.Z);
+				for p in [p1, p2, p3] { text += &format!("      vertex {:e} {:e} {:e}\n", p.X, p.Y, p.Z); }
+				text += "    endloop\n  endfacet\n";
+			}
+			text += &format!("endsolid {name}\n");
+			text.into_bytes()
+		}
+	};
+	println!("done! {} facets", facets.len());
+	File::create(path)?.write_all(&data)?;
+	println!("stl exported successfully!\n");
+	Ok(())
+}
+
+// the checkerboard meshes get when there's no image (Texture::missing at the sizes the loaders and Mesh::new use),
+// not worth exporting as a texture
+fn is_placeholder(texture: &Texture) -> bool {
+	texture.width == 10 && texture.height == 10 && [1, 2].iter().any(|size| {
+		texture.bitmap.iter().flatten().map(|c| c.RGB).eq(Texture::missing(10, 10, *size).bitmap.iter().flatten().map(|c| c.RGB))
+	})
+}
+
+fn json_string(text: &str) -> String {
+	format!("\"{}\"", text.chars().map(|c| match c {
+		'"' => "\\\"".to_string(),
+		'\\' => "\\\\".to_string(),
+		c if (c as u32) < 0x20 => format!("\\u{:04x}", c as u32),
+		c => c.to_string()
+	}).collect::<String>())
+}
+
+fn json_floats(values: &[f32]) -> String {
+	format!("[{}]", values.iter().map(|v| if v.is_finite() { v.to_string() }else { "0".to_string() }).collect::<Vec<String>>().join(","))
+}
+
+fn base64(data: &[u8]) -> String {
+	const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+	let mut out = String::with_capacity(data.len().div_ceil(3)*4);
+	for chunk in data.chunks(3) {
+		let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, b)| bits | (*b as u32) << (16 - 8*i));
+		for i in 0..4 {
+			out.push(if i <= chunk.len() { DIGITS[(bits >> (18 - 6*i) & 63) as usize] as char }else { '=' });
+		}
+	}
+	out
+}
+
+// gltf 2.0 with everything in one buffer: geometry, and textures as png. paths ending in .glb get the binary container,
+// anything else a .gltf with the buffer inlined as base64. every mesh is a node placed at its origin, with a primitive
+// per material its faces use. positions are exported as posed, corners sharing a vertex but not its uv or normal are
+// split like gltf needs. materials map onto metallic roughness: diffuse and opacity become the base color, the
+// specular exponent the roughness, and unlit materials use KHR_materials_unlit
+pub fn export_gltf(scene: &Scene, path: &str) -> std::io::Result<()> {
+	println!("exporting gltf: {path}");
+	let mut buffer: Vec<u8> = Vec::new();
+	let (mut views, mut accessors, mut images, mut materials, mut meshes, mut nodes) = (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+	let mut unlit = false;
+	// a buffer view over what was just appended, 4 byte aligned like accessors need
+	let mut push_view = |buffer: &mut Vec<u8>, data: &[u8], target: Option<u32>| {
+		let offset = buffer.len();
+		buffer.extend_from_slice(data);
+		buffer.resize(buffer.len().next_multiple_of(4), 0);
+		let target = target.map_or(String::new(), |t| format!(",\"target\":{t}"));
+		views.push(format!("{{\"buffer\":0,\"byteOffset\":{offset},\"byteLength\":{}{target}}}", data.len()));
+		views.len() - 1
+	};
+	
+	print!("writing meshes... ");
+	for (index, mesh) in scene.meshes.iter().enumerate() {
+		let name = scene.sources.get(index).map_or(format!("mesh_{index}"), |source| source.file.rsplit(['/', '\\']).next().unwrap_or(&source.file).to_string());
+		// this mesh's materials in gltf's list, mesh.material first and then mesh.materials
+		let first_material = materials.len();
+		for (m, (material, texture)) in std::iter::once((&mesh.material, &mesh.texture)).chain(mesh.materials.iter().map(|(m, t)| (m, t))).enumerate() {
+			let texture = if is_placeholder(texture) || texture.width == 0 || texture.height == 0 { String::new() }else {
+				let view = push_view(&mut buffer, &encode_png(&texture.bitmap), None);
+				images.push(format!("{{\"bufferView\":{view},\"mimeType\":\"image/png\"}}"));
+				format!(",\"baseColorTexture\":{{\"index\":{}}}", images.len() - 1)
+			};
+			let (r, g, b) = material.diffuse.RGB;
+			let (metallic, roughness) = match &material.pbr {
+				Some(pbr) => (pbr.metallic.clamp(0.0, 1.0), pbr.roughness.clamp(0.0, 1.0)),
+				None => (0.0, 1.0 - (material.highlights.max(0.0)/1000.0).sqrt().min(1.0)) // the mapping blender's obj importer uses
+			};
+			let blend = if material.opacity < 1.0 { ",\"alphaMode\":\"BLEND\"" }else { "" };
+			let (er, eg, eb) = material.emissive.RGB;
+			// gltf caps emission at 1 without the emissive strength extension
+			let emissive = if (er, eg, eb) == (0.0, 0.0, 0.0) { String::new() }else { format!(",\"emissiveFactor\":{}", json_floats(&[er.clamp(0.0, 1.0), eg.clamp(0.0, 1.0), eb.clamp(0.0, 1.0)])) };
+			let extensions = if matches!(material.mode, LightingMode::None) { unlit = true; ",\"extensions\":{\"KHR_materials_unlit\":{}}" }else { "" };
+			materials.push(format!("{{\"name\":{},\"pbrMetallicRoughness\":{{\"baseColorFactor\":{},\"metallicFactor\":{metallic},\"roughnessFactor\":{roughness}{texture}}}{emissive}{blend}{extensions}}}",
+				json_string(&format!("{name}_{m}")), json_floats(&[r, g, b, material.opacity.clamp(0.0, 1.0)])));
+		}
+		
+		// one gltf vertex per distinct position, uv and normal combination
+		let mut corners: HashMap<(usize, u32, u32, [u32; 3]), u32> = HashMap::new();
+		let (mut positions, mut normals, mut uvs, mut colors) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+		let mut primitives: Vec<(usize, Vec<u32>)> = Vec::new(); // material index in the mesh and triangle indices
+		for face in mesh.faces() {
+			let material = face.material.map_or(0, |m| m + 1);
+			let primitive = match primitives.iter().position(|(m, _)| *m == material) {
+				Some(p) => p,
+				None => { primitives.push((material, Vec::new())); primitives.len() - 1 }
+			};
+			let vertices = [face.vertices.0, face.vertices.1, face.vertices.2];
+			for corner in 0..3 {
+				let normal = if face.normals[corner].mag() > 0.0 { face.normals[corner].normalize() }
+					else if face.face_normal.mag() > 0.0 { face.face_normal.normalize() }else { Vector3D::XYZ(0.0, 0.0, 1.0) };
+				let uv = face.tex_coords[corner];
+				let key = (vertices[corner], uv.0.to_bits(), uv.1.to_bits(), [normal.X.to_bits(), normal.Y.to_bits(), normal.Z.to_bits()]);
+				let next = corners.len() as u32;
+				let vertex = *corners.entry(key).or_insert_with(|| {
+					let p = face.positions[corner].sub(mesh.origin);
+					positions.extend([p.X, p.Y, p.Z]);
+					normals.extend([normal.X, normal.Y, normal.Z]);
+					uvs.extend([uv.0, uv.1]);
+					if let Some(c) = mesh.vertex_colors.get(vertices[corner]) { colors.extend([c.RGB.0, c.RGB.1, c.RGB.2]); }
+					next
+				});
+				primitives[primitive].1.push(vertex);
+			}
+		}
+		if positions.is_empty() { continue; }
+		
+		let floats = |values: &[f32]| values.iter().flat_map(|v| if v.is_finite() { *v }else { 0.0 }.to_le_bytes()).collect::<Vec<u8>>();
+		let count = positions.len()/3;
+		let (mut min, mut max) = ([f32::MAX; 3], [f32::MIN; 3]);
+		for p in positions.chunks(3) {
+			for axis in 0..3 {
+				let v = if p[axis].is_finite() { p[axis] }else { 0.0 };
+				(min[axis], max[axis]) = (min[axis].min(v), max[axis].max(v));
+		}}
+		let mut attribute = |data: &[f32], kind: &str, bounds: String| {
+			let view = push_view(&mut buffer, &floats(data), Some(34962));
+			accessors.push(format!("{{\"bufferView\":{view},\"componentType\":5126,\"count\":{count},\"type\":\"{kind}\"{bounds}}}"));
+			accessors.len() - 1
+		};
+		let mut attributes = format!("\"POSITION\":{}", attribute(&positions, "VEC3", format!(",\"min\":{},\"max\":{}", json_floats(&min), json_floats(&max))));
+		attributes += &format!(",\"NORMAL\":{}", attribute(&normals, "VEC3", String::new()));
+		attributes += &format!(",\"TEXCOORD_0\":{}", attribute(&uvs, "VEC2", String::new()));
+		if colors.len() == positions.len() { attributes += &format!(",\"COLOR_0\":{}", attribute(&colors, "VEC3", String::new())); }
+		
+		let primitives = primitives.iter().map(|(material, indices)| {
+			let view = push_view(&mut buffer, &indices.iter().flat_map(|i| i.to_le_bytes()).collect::<Vec<u8>>(), Some(34963));
+			accessors.push(format!("{{\"bufferView\":{view},\"componentType\":5125,\"count\":{},\"type\":\"SCALAR\"}}", indices.len()));
+			format!("{{\"attributes\":{{{attributes}}},\"indices\":{},\"material\":{}}}", accessors.len() - 1, first_material + material)
+		}).collect::<Vec<String>>();
+		meshes.push(format!("{{\"name\":{},\"primitives\":[{}]}}", json_string(&name), primitives.join(",")));
+		nodes.push(format!("{{\"name\":{},\"mesh\":{},\"translation\":{}}}", json_string(&name), meshes.len() - 1, json_floats(&[mesh.origin.X, mesh.origin.Y, mesh.origin.Z])));
+	}
+	println!("done! {} meshes, {} materials, {} textures", meshes.len(), materials.len(), images.len());
+	
+	let glb = path.ends_with(".glb");
+	let uri = if glb { String::new() }else { format!(",\"uri\":\"data:application/octet-stream;base64,{}\"", base64(&buffer)) };
+	let list = |items: &Vec<String>| format!("[{}]", items.join(","));
+	let textures = (0..images.len()).map(|i| format!("{{\"source\":{i}}}")).collect::<Vec<String>>();
+	let mut json = format!("{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"CLI-rendering-engine\"}},\"scene\":0,\"scenes\":[{{\"nodes\":{}}}],\"nodes\":{},\"meshes\":{},\"materials\":{},\"accessors\":{},\"bufferViews\":{},\"buffers\":[{{\"byteLength\":{}{uri}}}]",
+		list(&(0..nodes.len()).map(|n| n.to_string()).collect()), list(&nodes), list(&meshes), list(&materials), list(&accessors), list(&views), buffer.len());
+	if !images.is_empty() { json += &format!(",\"images\":{},\"textures\":{}", list(&images), list(&textures)); }
+	if unlit { json += ",\"extensionsUsed\":[\"KHR_materials_unlit\"]"; }
+	json += "}";
+	
+	let mut file = File::create(path)?;
+	if glb {
+		// 12 byte header, then the json and binary chunks each padded to 4 bytes (json with spaces)
+		let mut json = json.into_bytes();
+		json.resize(json.len().next_multiple_of(4), b' ');
+		buffer.resize(buffer.len().next_multiple_of(4), 0);
+		let length = 12 + 8 + json.len() + 8 + buffer.len();
+		let mut out = Vec::with_capacity(length);
+		out.extend_from_slice(b"glTF");
+		out.extend_from_slice(&2u32.to_le_bytes());
+		out.extend_from_slice(&(length as u32).to_le_bytes());
+		out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+		out.extend_from_slice(b"JSON");
+		out.extend_from_slice(&json);
+		out.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+		out.extend_from_slice(b"BIN\0");
+		out.extend_from_slice(&buffer);
+		file.write_all(&out)?;
+	}else {
+		file.write_all(json.as_bytes())?;
+	}
+	println!("gltf exported successfully!\n");
+	Ok(())
+}
@@ -1,6 +1,8 @@
 use crate::{ Color, Vector3D, Point2D };
 use crate::clamp;
 
+use std::cmp::{ min, max };
+
 #[derive(Clone)]
 pub struct Texture {
 	pub width: usize,
@@ -12,6 +14,7 @@ impl Texture {
 	pub fn new(width: usize, height: usize, bitmap: Vec<Vec<Color>>) -> Texture { Texture { width, height, bitmap } }
 	
 	pub fn missing(width: usize, height: usize, size: usize) -> Texture {
+		let size = max(size, 1);
 		let (mut bit_row1, mut bit_row2, mut bitmap) = (Vec::new(), Vec::new(), Vec::new());
 		let c1 = Color::RGB(0.6, 0.6, 0.6);
 		let c2 = Color::RGB(0.9, 0.9, 0.9);
@@ -30,10 +33,15 @@ impl Texture {
 	}
 	
 	pub fn sample(&self, UV: Point2D) -> Color {
+		// broken image files can leave the bitmap smaller than the header claims, or empty
+		let width = min(self.width, self.bitmap.iter().map(|row| row.len()).min().unwrap_or(0));
+		let height = min(self.height, self.bitmap.len());
+		if width == 0 || height == 0 { return Color::black(); }
+		
 		// clamp U and V
 		let u = clamp(0.0, 1.0, UV.0);
 		let v = clamp(0.0, 1.0, UV.1);
-		let (tx, ty) = (u * (self.width-1) as f32, v * (self.height-1) as f32);
+		let (tx, ty) = (u * (width-1) as f32, v * (height-1) as f32);
 		
 		let (u_fac, v_fac) = (tx.fract(), ty.fract());
 		let (c0, c1, c2, c3) = (
@@ -45,6 +53,285 @@ impl Texture {
 		let (c01, c23) = (c0.lerp(c1, u_fac), c2.lerp(c3, u_fac));
 		c01.lerp(c23, v_fac)
 	}
+	
+	// whether every texel has equal red, green and blue, bump maps are usually height maps saved like this
+	pub fn is_grayscale(&self) -> bool {
+		self.bitmap.iter().flatten().all(|c| c.RGB.0 == c.RGB.1 && c.RGB.1 == c.RGB.2)
+	}
+	
+	// turn a height map (brightness is height) into a tangent space normal map, strength scales the slopes
+	pub fn normals_from_height(&self, strength: f32) -> Texture {
+		let height_at = |h: usize, w: usize| self.bitmap.get(h).and_then(|row| row.get(w)).map_or(0.0, |c| c.RGB.0);
+		let bitmap = (0..self.bitmap.len()).map(|h| (0..self.bitmap[h].len()).map(|w| {
+			// central differences, clamped at the edges
+			let du = height_at(h, min(w+1, self.bitmap[h].len()-1)) - height_at(h, w.saturating_sub(1));
+			let dv = height_at(min(h+1, self.bitmap.len()-1), w) - height_at(h.saturating_sub(1), w);
+			let normal = Vector3D::XYZ(-du*strength, -dv*strength, 1.0).normalize();
+			Color::RGB(0.5 + 0.5*normal.X, 0.5 + 0.5*normal.Y, 0.5 + 0.5*normal.Z)
+		}).collect()).collect();
+		Texture{ width: self.width, height: self.height, bitmap }
+	}
+	
+	// evaluate a procedural once per texel so it can be sampled (and exported) like any other bitmap,
+	// texel centers line up with the UVs sample() reads them at so the baked texture reproduces it exactly there
+	pub fn bake(procedural: &dyn Procedural, width: usize, height: usize) -> Texture {
+		let (width, height) = (max(width, 1), max(height, 1));
+		let step = |i: usize, n: usize| if n > 1 { i as f32 / (n-1) as f32 }else { 0.5 };
+		let bitmap = (0..height).map(|h| (0..width).map(|w| procedural.evaluate((step(w, width), step(h, height)))).collect()).collect();
+		Texture{ width, height, bitmap }
+	}
+}
+
+// a texture defined by a function of UV instead of a bitmap, closures work too
+pub trait Procedural {
+	fn evaluate(&self, UV: Point2D) -> Color;
+}
+
+impl<F: Fn(Point2D) -> Color> Procedural for F {
+	fn evaluate(&self, UV: Point2D) -> Color { self(UV) }
+}
+
+// smooth value noise blending between two colors, scale is the number of noise cells across the texture
+pub struct ValueNoise {
+	pub scale: f32,
+	pub seed: u64,
+	pub low: Color,
+	pub high: Color
+}
+
+impl ValueNoise {
+	fn lattice(&self, x: i64, y: i64) -> f32 {
+		Rng::new(self.seed ^ (x as u64).wrapping_mul(0x9e3779b97f4a7c15) ^ (y as u64).wrapping_mul(0xc2b2ae3d27d4eb4f)).float()
+	}
+}
+
+impl Procedural for ValueNoise {
+	fn evaluate(&self, UV: Point2D) -> Color {
+		let (x, y) = (UV.0*self.scale, UV.1*self.scale);
+		let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+		let smooth = |t: f32| t*t*(3.0 - 2.0*t);
+		let (fx, fy) = (smooth(x - x0 as f32), smooth(y - y0 as f32));
+		let top = self.lattice(x0, y0) + (self.lattice(x0+1, y0) - self.lattice(x0, y0))*fx;
+		let bottom = self.lattice(x0, y0+1) + (self.lattice(x0+1, y0+1) - self.lattice(x0, y0+1))*fx;
+		self.low.lerp(self.high, top + (bottom - top)*fy)
+	}
+}
+
+// what a FragmentShader is given for every pixel a mesh covers, interpolated across the triangle with perspective
+// correction like the rest of the shader inputs
+pub struct Fragment<'a> {
+	pub UV: Point2D,
+	pub normal: Vector3D, // normalized, the interpolated vertex normal without normal maps applied
+	pub depth: f32, // distance in front of the camera
+	pub position: Vector3D, // world space
+	pub material: &'a Material
+}
+
+// takes over from the built in lighting for a mesh (Mesh.shader), whatever color it returns is blended in by the
+// material's opacity and goes through post processing like any other pixel. closures work too
+pub trait FragmentShader: Send + Sync {
+	fn shade(&self, fragment: &Fragment) -> Color;
+}
+
+impl<F: Fn(&Fragment) -> Color + Send + Sync> FragmentShader for F {
+	fn shade(&self, fragment: &Fragment) -> Color { self(fragment) }
+}
+
+// colors by distance from the camera, blue at near through green to red at far
+pub struct DepthHeatmap {
+	pub near: f32,
+	pub far: f32
+}
+
+impl FragmentShader for DepthHeatmap {
+	fn shade(&self, fragment: &Fragment) -> Color {
+		let t = clamp(0.0, 1.0, (fragment.depth - self.near)/(self.far - self.near).max(1e-6));
+		let (blue, green, red) = (Color::RGB(0.0, 0.0, 1.0), Color::RGB(0.0, 1.0, 0.0), Color::RGB(1.0, 0.0, 0.0));
+		if t < 0.5 { blue.lerp(green, 2.0*t) }else { green.lerp(red, 2.0*t - 1.0) }
+	}
+}
+
+// what's seen far away in every direction, drawn behind everything instead of a flat background color. only the
+// camera's rotation matters, moving it never gets any closer
+#[derive(Clone)]
+pub enum Environment {
+	// equirectangular: u runs once around the horizon with the middle straight ahead of the default camera (-Z), v from
+	// straight up at the top to straight down at the bottom
+	Panorama(Texture),
+	// the inside of a cube, faces in the order +X, -X, +Y, -Y, +Z, -Z laid out like OpenGL cubemaps
+	Cubemap(Box<[Texture; 6]>)
+}
+
+impl Environment {
+	pub fn sample(&self, direction: Vector3D) -> Color {
+		let d = direction.normalize();
+		if !d.is_finite() { return Color::black(); }
+		match self {
+			Environment::Panorama(texture) => {
+				let u = 0.5 + d.X.atan2(-d.Z)/std::f32::consts::TAU;
+				let v = clamp(-1.0, 1.0, d.Y).acos()/std::f32::consts::PI;
+				texture.sample((u, v))
+			},
+			Environment::Cubemap(faces) => {
+				let (x, y, z) = (d.X.abs(), d.Y.abs(), d.Z.abs());
+				// the face the direction leaves through, then where on it
+				let (face, s, t, major) = if x >= y && x >= z {
+					if d.X > 0.0 { (0, -d.Z, -d.Y, x) }else { (1, d.Z, -d.Y, x) }
+				}else if y >= z {
+					if d.Y > 0.0 { (2, d.X, d.Z, y) }else { (3, d.X, -d.Z, y) }
+				}else {
+					if d.Z > 0.0 { (4, d.X, -d.Y, z) }else { (5, -d.X, -d.Y, z) }
+				};
+				faces[face].sample((0.5*(s/major + 1.0), 0.5*(t/major + 1.0)))
+			}
+		}
+	}
+}
+
+// the light an environment sends onto a surface facing each way, blurred over the whole hemisphere like diffuse
+// surfaces see it. kept as 9 spherical harmonic coefficients per channel (Ramamoorthi and Hanrahan), which is plenty
+// for something that changes as slowly with the normal as this
+#[derive(Copy, Clone, Debug)]
+pub struct Irradiance {
+	coefficients: [Color; 9]
+}
+
+impl Irradiance {
+	// integrates the environment over a grid of samples x 2*samples directions, a few dozen is enough
+	pub fn from_environment(environment: &Environment, samples: usize) -> Irradiance {
+		let samples = max(samples, 1);
+		let (d_theta, d_phi) = (std::f32::consts::PI/samples as f32, std::f32::consts::PI/samples as f32);
+		let mut coefficients = [Color::black(); 9];
+		for i in 0..samples {
+			let theta = (i as f32 + 0.5)*d_theta;
+			for j in 0..2*samples {
+				let phi = (j as f32 + 0.5)*d_phi;
+				let direction = Vector3D::XYZ(theta.sin()*phi.cos(), theta.cos(), theta.sin()*phi.sin());
+				let light = environment.sample(direction).mul(theta.sin()*d_theta*d_phi);
+				for (coefficient, basis) in coefficients.iter_mut().zip(Irradiance::basis(direction)) {
+					*coefficient = coefficient.add(light.mul(basis));
+				}
+		}}
+		Irradiance{ coefficients }
+	}
+	
+	// the light reaching a surface with this normal, scaled so a uniformly white environment gives white
+	pub fn at(&self, normal: Vector3D) -> Color {
+		// the cosine lobe's weight per band (pi, 2pi/3, pi/4), over pi
+		let bands = [1.0, 2.0/3.0, 2.0/3.0, 2.0/3.0, 0.25, 0.25, 0.25, 0.25, 0.25];
+		let normal = normal.normalize();
+		if !normal.is_finite() { return self.coefficients[0].mul(Irradiance::basis(Vector3D::zero())[0]); }
+		let mut light = Color::black();
+		for ((coefficient, basis), band) in self.coefficients.iter().zip(Irradiance::basis(normal)).zip(bands) {
+			light = light.add(coefficient.mul(basis*band));
+		}
+		Color::RGB(light.RGB.0.max(0.0), light.RGB.1.max(0.0), light.RGB.2.max(0.0)) // ringing can dip below zero
+	}
+	
+	// real spherical harmonics up to band 2 for a unit direction
+	fn basis(d: Vector3D) -> [f32; 9] {
+		[
+			0.282095,
+			0.488603*d.Y, 0.488603*d.Z, 0.488603*d.X,
+			1.092548*d.X*d.Y, 1.092548*d.Y*d.Z, 0.315392*(3.0*d.Z*d.Z - 1.0), 1.092548*d.X*d.Z, 0.546274*(d.X*d.X - d.Y*d.Y)
+		]
+	}
+}
+
+// one vertex of a mesh as a VertexShader sees it, in world space before the camera gets involved
+pub struct VertexInput {
+	pub position: Vector3D,
+	pub normal: Vector3D, // the vertex normal as stored, zero for meshes without one
+	pub index: usize, // into Mesh.vertices
+	pub time: f32 // Viewport.time, seconds
+}
+
+// moves vertices around every frame (Mesh.vertex_shader) without touching the mesh itself, returns where the vertex
+// is drawn. the normals are recalculated from the displaced vertices (Mesh::shaded), so the lighting follows the new
+// shape, while VertexInput.normal is still the one stored on the mesh. closures work too
+pub trait VertexShader: Send + Sync {
+	fn displace(&self, vertex: &VertexInput) -> Vector3D;
+}
+
+impl<F: Fn(&VertexInput) -> Vector3D + Send + Sync> VertexShader for F {
+	fn displace(&self, vertex: &VertexInput) -> Vector3D { self(vertex) }
+}
+
+// waves travelling along world X that push vertices out along their normals, for water and flags. wavelength is in
+// world units and speed in world units per second
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SineWave {
+	pub amplitude: f32,
+	pub wavelength: f32,
+	pub speed: f32
+}
+
+impl VertexShader for SineWave {
+	fn displace(&self, vertex: &VertexInput) -> Vector3D {
+		let phase = std::f32::consts::TAU*(vertex.position.X - self.speed*vertex.time)/self.wavelength;
+		let offset = self.amplitude*phase.sin();
+		if offset.is_finite() { vertex.position.add(vertex.normal.mul(offset)) }else { vertex.position }
+	}
+}
+
+// how the specular term measures how close a surface is to mirroring a light into the camera
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SpecularModel {
+	Phong, // the light reflected about the normal against the view direction
+	// the normal against the half vector between light and view, cheaper and doesn't cut off at grazing angles. the
+	// same highlights exponent gives a wider highlight, about 4x as much matches Phong
+	BlinnPhong
+}
+
+impl SpecularModel {
+	// directions are normalized and point away from the surface, towards the light and the camera
+	pub fn strength(&self, normal: Vector3D, light_direction: Vector3D, camera_direction: Vector3D, highlights: f32) -> f32 {
+		match self {
+			SpecularModel::Phong => clamp(0.0, 1.0, camera_direction.dot(light_direction.mul(-1.0).reflect(normal))).powf(highlights),
+			// the half vector can still face the normal from behind the surface, those get no highlight
+			SpecularModel::BlinnPhong if normal.dot(light_direction) <= 0.0 => 0.0,
+			SpecularModel::BlinnPhong => clamp(0.0, 1.0, normal.dot(light_direction.add(camera_direction).normalize())).powf(highlights)
+		}
+	}
+}
+
+// gltf's metallic-roughness model, materials that have it are shaded with a Cook-Torrance BRDF instead of the phong
+// terms. the base color is the material's diffuse color times the texture, like gltf's baseColorFactor. only the
+// ambient shading weight applies, the BRDF balances diffuse and specular itself
+#[derive(Clone)]
+pub struct Pbr {
+	pub metallic: f32,
+	pub roughness: f32,
+	// grayscale maps scaling the factors by their brightness (mtl's map_Pm and map_Pr)
+	pub metallic_map: Option<Texture>,
+	pub roughness_map: Option<Texture>
+}
+
+impl Pbr {
+	pub fn new(metallic: f32, roughness: f32) -> Pbr { Pbr{ metallic, roughness, metallic_map: None, roughness_map: None } }
+}
+
+// light reflected towards the camera from one white light of intensity 1, directions normalized and pointing away
+// from the surface. scaled by pi so a white rough surface facing the light comes out white rather than 1/pi
+pub fn cook_torrance(base_color: Color, metallic: f32, roughness: f32, normal: Vector3D, light_direction: Vector3D, camera_direction: Vector3D) -> Color {
+	let n_dot_l = normal.dot(light_direction);
+	if n_dot_l <= 0.0 { return Color::black(); }
+	let n_dot_v = normal.dot(camera_direction).max(1e-4);
+	let half = light_direction.add(camera_direction).normalize();
+	let (n_dot_h, v_dot_h) = (normal.dot(half).max(0.0), camera_direction.dot(half).max(0.0));
+	let (metallic, roughness) = (clamp(0.0, 1.0, metallic), clamp(0.04, 1.0, roughness)); // perfectly smooth highlights vanish between pixels
+	
+	// ggx distribution, schlick-ggx geometry and schlick fresnel
+	let alpha = roughness*roughness;
+	let distribution = alpha*alpha/(std::f32::consts::PI*(n_dot_h*n_dot_h*(alpha*alpha - 1.0) + 1.0).powi(2));
+	let k = (roughness + 1.0).powi(2)/8.0;
+	let geometry = n_dot_l/(n_dot_l*(1.0 - k) + k)*n_dot_v/(n_dot_v*(1.0 - k) + k);
+	let f0 = Color::RGB(0.04, 0.04, 0.04).lerp(base_color, metallic);
+	let fresnel = f0.lerp(Color::RGB(1.0, 1.0, 1.0), (1.0 - v_dot_h).powi(5));
+	
+	let specular = fresnel.mul(distribution*geometry/(4.0*n_dot_l*n_dot_v));
+	let diffuse = Color::RGB(1.0 - fresnel.RGB.0, 1.0 - fresnel.RGB.1, 1.0 - fresnel.RGB.2).hadamard(base_color).mul(1.0 - metallic);
+	diffuse.add(specular.mul(std::f32::consts::PI)).mul(n_dot_l)
 }
 
 #[derive(Clone)]
@@ -52,14 +339,34 @@ pub struct Material {
 	pub ambient: Color,
 	pub diffuse: Color,
 	pub specular: Color,
+	pub emissive: Color, // light the surface gives off itself, added however it's lit (or not)
 	pub highlights: f32,
 	pub opacity: f32,
 	pub mode: LightingMode,
+	// false leaves out the specular term (mtl illum 1), the lighting cache and the shader both respect it
+	pub specular_highlights: bool,
+	pub specular_model: SpecularModel,
+	pub transparency: Transparency,
+	// optional mtl maps sampled with the diffuse texture's coordinates: map_Ks tints the specular term, map_Ns and
+	// map_d scale highlights and opacity by their brightness
+	pub specular_map: Option<Texture>,
+	pub highlight_map: Option<Texture>,
+	pub opacity_map: Option<Texture>,
+	pub emissive_map: Option<Texture>, // map_Ke, tinted by emissive
+	// tangent space normal map (map_bump), red and green run along the texture's u and v directions and blue along the
+	// surface normal. grayscale bump maps are converted with Texture::normals_from_height when loaded
+	pub normal_map: Option<Texture>,
+	pub pbr: Option<Pbr>
 }
 
 impl Material {
 	pub fn new(ambient: Color, diffuse: Color, specular: Color, highlights: f32, opacity: f32, mode: LightingMode) -> Material {
-		Material{ ambient, diffuse, specular, highlights, opacity, mode }
+		Material{ ambient, diffuse, specular, emissive: Color::black(), highlights, opacity, mode, specular_highlights: true, specular_model: SpecularModel::Phong, transparency: Transparency::Blend, specular_map: None, highlight_map: None, opacity_map: None, emissive_map: None, normal_map: None, pbr: None }
+	}
+	
+	// starts from a plain smooth shaded gray, see MaterialBuilder
+	pub fn builder() -> MaterialBuilder {
+		MaterialBuilder{ material: Material::new(Color::RGB(0.6, 0.6, 0.6), Color::RGB(0.7, 0.7, 0.7), Color::RGB(1.0, 1.0, 1.0), 20.0, 1.0, LightingMode::Smooth) }
 	}
 	
 	pub fn missing() -> Material {
@@ -67,28 +374,209 @@ impl Material {
 			ambient: Color::RGB(0.75, 0.75, 0.75),
 			diffuse: Color::RGB(1.0, 0.0, 1.0),
 			specular: Color::RGB(1.0, 1.0, 1.0),
+			emissive: Color::black(),
 			highlights: 20.0,
 			opacity: 1.0,
-			mode: LightingMode::None
+			mode: LightingMode::None,
+			specular_highlights: true,
+			specular_model: SpecularModel::Phong,
+			transparency: Transparency::Blend,
+			specular_map: None,
+			highlight_map: None,
+			opacity_map: None,
+			emissive_map: None,
+			normal_map: None,
+			pbr: None
+		}
+	}
+	
+	// mtl illum: 0 is unlit, 1 diffuse only and 2 full phong. the higher models (reflections, refraction) are
+	// shaded like 2 since there's nothing to reflect
+	pub fn set_illumination_model(&mut self, illum: u32) {
+		(self.mode, self.specular_highlights) = match illum {
+			0 => (LightingMode::None, true),
+			1 => (LightingMode::Smooth, false),
+			_ => (LightingMode::Smooth, true)
+		};
+	}
+	
+	pub fn illumination_model(&self) -> u32 {
+		match (self.mode, self.specular_highlights) {
+			(LightingMode::None, _) => 0,
+			(_, false) => 1,
+			_ => 2
 		}
 	}
 }
 
+// per vertex diffuse + specular light for a mesh, ambient is left out since it depends on the texture
+// key identifies the mesh revision, lights and material it was computed for
 #[derive(Clone)]
+pub struct LightingCache {
+	pub key: u64,
+	pub vertex_light: Vec<Color>
+}
+
+#[derive(Copy, Clone, Debug)]
 pub enum LightingMode {
 	Flat,
 	Smooth,
+	Gouraud, // lit at the corners only and the colors interpolated across, fast but highlights smear out on big triangles
+	Toon(usize), // diffuse light in this many flat bands, hard edged highlights and a dark outline around the mesh
 	None
 }
 
+// how opacity below 1 is drawn. Blend mixes with whatever was drawn before, the other two keep or drop whole
+// fragments against a threshold so surfaces come out with holes instead, which needs no sorting or blending
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transparency {
+	Blend,
+	ScreenDoor, // 4x4 ordered pattern, stable from frame to frame
+	Noise // hashed per pixel and depth, so stacked surfaces don't all drop the same pixels
+}
+
 #[derive(Copy, Clone)]
 pub struct LightSource {
 	pub color: Color,
-	pub position: Vector3D
+	pub position: Vector3D,
+	pub intensity: f32, // scales the diffuse and specular light it gives, 1.0 unless changed
+	pub enabled: bool,
+	pub range: Option<f32> // lights shine in one direction everywhere, unless a range limits them to geometry this close to position
 }
 
 impl LightSource {
-	pub fn new(color: Color, position: Vector3D) -> LightSource { LightSource{ color, position } }
-	pub fn magenta(position: Vector3D) -> LightSource { LightSource{ color: Color::RGB(1.0, 0.0, 1.0), position } }
+	pub fn new(color: Color, position: Vector3D) -> LightSource { LightSource{ color, position, intensity: 1.0, enabled: true, range: None } }
+	pub fn magenta(position: Vector3D) -> LightSource { LightSource::new(Color::RGB(1.0, 0.0, 1.0), position) }
+	
+	// white light from straight ahead of the default camera, see LightSourceBuilder
+	pub fn builder() -> LightSourceBuilder {
+		LightSourceBuilder{ light: LightSource::new(Color::RGB(1.0, 1.0, 1.0), Vector3D::XYZ(0.0, 0.0, 1.0)) }
+	}
+	
+	// whether any part of a bounding sphere is in range, disabled lights reach nothing
+	pub fn reaches(&self, center: Vector3D, radius: f32) -> bool {
+		self.enabled && self.range.map_or(true, |range| self.position.sub(center).mag() - radius <= range)
+	}
+}
+
+pub struct MaterialBuilder {
+	material: Material
+}
+
+// carries on from an existing material, scene files adjust the one a mesh was loaded with this way
+impl From<Material> for MaterialBuilder {
+	fn from(material: Material) -> MaterialBuilder { MaterialBuilder{ material } }
 }
 
+impl MaterialBuilder {
+	pub fn ambient(mut self, color: Color) -> MaterialBuilder { self.material.ambient = color; self }
+	pub fn diffuse(mut self, color: Color) -> MaterialBuilder { self.material.diffuse = color; self }
+	pub fn specular(mut self, color: Color) -> MaterialBuilder { self.material.specular = color; self }
+	pub fn emissive(mut self, color: Color) -> MaterialBuilder { self.material.emissive = color; self }
+	pub fn highlights(mut self, highlights: f32) -> MaterialBuilder { self.material.highlights = highlights; self }
+	pub fn opacity(mut self, opacity: f32) -> MaterialBuilder { self.material.opacity = opacity; self }
+	pub fn mode(mut self, mode: LightingMode) -> MaterialBuilder { self.material.mode = mode; self }
+	pub fn specular_model(mut self, model: SpecularModel) -> MaterialBuilder { self.material.specular_model = model; self }
+	// either one makes the material pbr, the other starts at a dielectric's 0 metallic and 0.5 roughness
+	pub fn metallic(mut self, metallic: f32) -> MaterialBuilder { self.material.pbr.get_or_insert(Pbr::new(0.0, 0.5)).metallic = metallic; self }
+	pub fn roughness(mut self, roughness: f32) -> MaterialBuilder { self.material.pbr.get_or_insert(Pbr::new(0.0, 0.5)).roughness = roughness; self }
+	pub fn build(self) -> Material { self.material }
+}
+
+// the light that reaches everything, added once per fragment however many lights the viewport has. with irradiance
+// set it also depends on which way the surface faces, tinted by color
+#[derive(Copy, Clone)]
+pub struct AmbientLight {
+	pub color: Color,
+	pub intensity: f32,
+	pub irradiance: Option<Irradiance>
+}
+
+impl AmbientLight {
+	pub fn new(color: Color, intensity: f32) -> AmbientLight { AmbientLight{ color, intensity, irradiance: None } }
+	pub fn radiance(&self, normal: Vector3D) -> Color {
+		let light = self.irradiance.map_or(self.color, |irradiance| irradiance.at(normal).hadamard(self.color));
+		light.mul(self.intensity)
+	}
+}
+
+pub struct LightSourceBuilder {
+	light: LightSource
+}
+
+impl LightSourceBuilder {
+	pub fn color(mut self, color: Color) -> LightSourceBuilder { self.light.color = color; self }
+	// lights are directional, only the direction of position matters
+	pub fn position(mut self, position: Vector3D) -> LightSourceBuilder { self.light.position = position; self }
+	pub fn intensity(mut self, intensity: f32) -> LightSourceBuilder { self.light.intensity = intensity; self }
+	pub fn range(mut self, range: f32) -> LightSourceBuilder { self.light.range = Some(range); self }
+	pub fn enabled(mut self, enabled: bool) -> LightSourceBuilder { self.light.enabled = enabled; self }
+	pub fn build(self) -> LightSource { self.light }
+}
+
+// three point lighting presets (key, fill, rim) for previewing models, each with a matching backdrop gray
+#[derive(Copy, Clone, Debug)]
+pub enum StudioRig {
+	Neutral, // warm key, cool fill, even contrast
+	HighKey, // bright and soft, almost no shadow side
+	LowKey // strong key and rim, dark fill and backdrop
+}
+
+impl StudioRig {
+	pub fn lights(&self) -> Vec<LightSource> {
+		// key from above right in front, fill from the left, rim from behind
+		let (key, fill, rim) = (Vector3D::XYZ(1.0, 1.0, 1.0), Vector3D::XYZ(-1.0, 0.2, 0.6), Vector3D::XYZ(0.0, 0.6, -1.0));
+		match self {
+			StudioRig::Neutral => vec![
+				LightSource::new(Color::RGB(1.0, 0.95, 0.85), key),
+				LightSource::new(Color::RGB(0.35, 0.4, 0.5), fill),
+				LightSource::new(Color::RGB(0.6, 0.6, 0.6), rim)
+			],
+			StudioRig::HighKey => vec![
+				LightSource::new(Color::RGB(1.0, 1.0, 1.0), key),
+				LightSource::new(Color::RGB(0.8, 0.8, 0.8), fill),
+				LightSource::new(Color::RGB(0.5, 0.5, 0.5), rim)
+			],
+			StudioRig::LowKey => vec![
+				LightSource::new(Color::RGB(1.0, 0.9, 0.8), key),
+				LightSource::new(Color::RGB(0.1, 0.1, 0.15), fill),
+				LightSource::new(Color::RGB(0.9, 0.9, 1.0), rim)
+			]
+		}
+	}
+	
+	pub fn backdrop(&self) -> Color {
+		match self {
+			StudioRig::Neutral => Color::RGB(0.18, 0.18, 0.2),
+			StudioRig::HighKey => Color::RGB(0.7, 0.7, 0.72),
+			StudioRig::LowKey => Color::RGB(0.05, 0.05, 0.06)
+		}
+	}
+}
+
+
+// xorshift64*, good enough for noise and fuzzing and always gives the same sequence for the same seed
+#[derive(Copy, Clone)]
+pub struct Rng {
+	state: u64
+}
+
+impl Rng {
+	pub fn new(seed: u64) -> Rng { Rng{ state: seed ^ 0x9e3779b97f4a7c15 } }
+	
+	pub fn next_u64(&mut self) -> u64 {
+		self.state ^= self.state >> 12;
+		self.state ^= self.state << 25;
+		self.state ^= self.state >> 27;
+		self.state.wrapping_mul(0x2545f4914f6cdd1d)
+	}
+	
+	// uniform in 0-1
+	pub fn float(&mut self) -> f32 { (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32 }
+	
+	// uniform in min..max, max excluded
+	pub fn range(&mut self, min: usize, max: usize) -> usize {
+		if max <= min { return min; }
+		min + (self.next_u64() % (max - min) as u64) as usize
+	}
+}
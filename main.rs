@@ -1,16 +1,37 @@
 use std::{ thread, time };
 use mesh::{ Mesh, Transform };
-use graphicsutils::{ LightSource, LightingMode, Texture, Material };
-use viewport::Viewport;
+use graphicsutils::{ LightSource, LightingMode, Texture, Material, Pbr, StudioRig, DepthHeatmap, Fragment, SineWave, Environment, ValueNoise };
+use viewport::{ Viewport, ShadingView, Quality, QualitySettings, Transition, TraceTarget, Watchdog, Overrun };
+use displayutils::{ DisplayMode, Dithering };
 
 use std::fs::File;
-use std::io::Read;
-
-use regex::Regex;
+use std::io::{ Read, BufRead, BufReader, IsTerminal };
+use std::path::{ Path, PathBuf };
+use std::collections::HashMap;
+use std::cmp::min;
+use std::sync::{ Arc, Mutex };
 
 mod mesh;
 mod viewport;
+mod camera;
 mod graphicsutils;
+mod displayutils;
+mod exportutils;
+mod terminalutils;
+mod testutils;
+mod scene;
+#[cfg(feature = "http")]
+mod netutils;
+
+// set on asset loading threads so the step by step import logs of several files don't interleave
+thread_local! { static QUIET_IMPORT: std::cell::Cell<bool> = std::cell::Cell::new(false); }
+
+macro_rules! import_print {
+	($($arg:tt)*) => { if !QUIET_IMPORT.with(|quiet| quiet.get()) { print!($($arg)*); } }
+}
+macro_rules! import_println {
+	($($arg:tt)*) => { if !QUIET_IMPORT.with(|quiet| quiet.get()) { println!($($arg)*); } }
+}
 
 type Point2D = (f32, f32);
 type Triangle = (usize, usize, usize);
@@ -55,11 +76,15 @@ impl Vector3D {
 		(self.X*self.X + self.Y*self.Y + self.Z*self.Z).sqrt()
 	}
 	
+	// zero length (or non finite) vectors come back as zero instead of spreading NaN through everything that uses them
 	fn normalize(&self) -> Vector3D {
 		let mag = self.mag();
+		if mag == 0.0 || !mag.is_finite() { return Vector3D::zero(); }
 		Vector3D { X: self.X / mag, Y: self.Y / mag, Z: self.Z / mag }
 	}
 	
+	fn is_finite(&self) -> bool { self.X.is_finite() && self.Y.is_finite() && self.Z.is_finite() }
+	
 	fn cross(&self, other: Vector3D) -> Vector3D {
 		Vector3D {
 			X: self.Y*other.Z - self.Z*other.Y,
@@ -77,6 +102,72 @@ impl Vector3D {
 		let axis = other.normalize();
 		self.sub(axis.mul(2.0).mul(self.dot(axis)))
 	}
+	
+	fn approx_eq(&self, other: Vector3D, epsilon: f32) -> bool {
+		approx_eq(self.X, other.X, epsilon) && approx_eq(self.Y, other.Y, epsilon) && approx_eq(self.Z, other.Z, epsilon)
+	}
+	
+	fn to_array(&self) -> [f32; 3] { [self.X, self.Y, self.Z] }
+}
+
+impl From<[f32; 3]> for Vector3D {
+	fn from(v: [f32; 3]) -> Vector3D { Vector3D::XYZ(v[0], v[1], v[2]) }
+}
+impl From<(f32, f32, f32)> for Vector3D {
+	fn from(v: (f32, f32, f32)) -> Vector3D { Vector3D::XYZ(v.0, v.1, v.2) }
+}
+impl From<Vector3D> for [f32; 3] {
+	fn from(v: Vector3D) -> [f32; 3] { v.to_array() }
+}
+impl From<Vector3D> for (f32, f32, f32) {
+	fn from(v: Vector3D) -> (f32, f32, f32) { (v.X, v.Y, v.Z) }
+}
+
+// operators forward to the methods above, a + b*0.5 is the same as a.add(b.mul(0.5))
+impl std::ops::Add for Vector3D {
+	type Output = Vector3D;
+	fn add(self, other: Vector3D) -> Vector3D { Vector3D::add(&self, other) }
+}
+impl std::ops::Sub for Vector3D {
+	type Output = Vector3D;
+	fn sub(self, other: Vector3D) -> Vector3D { Vector3D::sub(&self, other) }
+}
+impl std::ops::Mul<f32> for Vector3D {
+	type Output = Vector3D;
+	fn mul(self, fac: f32) -> Vector3D { Vector3D::mul(&self, fac) }
+}
+impl std::ops::Mul<Vector3D> for f32 {
+	type Output = Vector3D;
+	fn mul(self, v: Vector3D) -> Vector3D { v.mul(self) }
+}
+impl std::ops::Div<f32> for Vector3D {
+	type Output = Vector3D;
+	fn div(self, fac: f32) -> Vector3D { Vector3D::div(&self, fac) }
+}
+impl std::ops::Neg for Vector3D {
+	type Output = Vector3D;
+	fn neg(self) -> Vector3D { Vector3D::XYZ(-self.X, -self.Y, -self.Z) }
+}
+// 0, 1, 2 are X, Y, Z
+impl std::ops::Index<usize> for Vector3D {
+	type Output = f32;
+	fn index(&self, i: usize) -> &f32 {
+		match i { 0 => &self.X, 1 => &self.Y, 2 => &self.Z, _ => panic!("Vector3D index {i} out of range") }
+	}
+}
+impl std::ops::IndexMut<usize> for Vector3D {
+	fn index_mut(&mut self, i: usize) -> &mut f32 {
+		match i { 0 => &mut self.X, 1 => &mut self.Y, 2 => &mut self.Z, _ => panic!("Vector3D index {i} out of range") }
+	}
+}
+// precision applies to every component, {:.3} prints (1.000, 0.500, 0.000)
+impl std::fmt::Display for Vector3D {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match f.precision() {
+			Some(p) => write!(f, "({:.p$}, {:.p$}, {:.p$})", self.X, self.Y, self.Z),
+			None => write!(f, "({}, {}, {})", self.X, self.Y, self.Z)
+		}
+	}
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -113,218 +204,1118 @@ impl Color {
 		Color { RGB: (fac*self.RGB.0, fac*self.RGB.1, fac*self.RGB.2) }
 	}
 	
+	// not clamped, lighting is accumulated in hdr and brought back into 0-1 by tone mapping in display()
 	fn add(&self, other: Color) -> Color {
-		Color { RGB: (
-			clamp(0.0, 1.0, self.RGB.0 + other.RGB.0),
-			clamp(0.0, 1.0, self.RGB.1 + other.RGB.1),
-			clamp(0.0, 1.0, self.RGB.2 + other.RGB.2)
-		)}
+		Color { RGB: (self.RGB.0 + other.RGB.0, self.RGB.1 + other.RGB.1, self.RGB.2 + other.RGB.2) }
+	}
+	
+	fn approx_eq(&self, other: Color, epsilon: f32) -> bool {
+		approx_eq(self.RGB.0, other.RGB.0, epsilon) && approx_eq(self.RGB.1, other.RGB.1, epsilon) && approx_eq(self.RGB.2, other.RGB.2, epsilon)
+	}
+	
+	// same truncation as the encoders, out of range channels are clamped
+	fn to_rgb8(&self) -> [u8; 3] {
+		let (R, G, B) = self.to_24bit();
+		[min(R, 255) as u8, min(G, 255) as u8, min(B, 255) as u8]
+	}
+	
+	fn from_rgb8(rgb: [u8; 3]) -> Color { Color::RGB(rgb[0] as f32/255.0, rgb[1] as f32/255.0, rgb[2] as f32/255.0) }
+	
+	// hue in degrees (any angle, it wraps), saturation and value/lightness from 0 to 1
+	fn HSV(H: f32, S: f32, V: f32) -> Color {
+		let chroma = V*S;
+		Color::from_hue(H, chroma, V - chroma)
+	}
+	
+	fn HSL(H: f32, S: f32, L: f32) -> Color {
+		let chroma = (1.0 - (2.0*L - 1.0).abs())*S;
+		Color::from_hue(H, chroma, L - chroma*0.5)
+	}
+	
+	// the color of a hue with the given chroma, lifted by base on every channel
+	fn from_hue(H: f32, chroma: f32, base: f32) -> Color {
+		let sector = H.rem_euclid(360.0)/60.0;
+		let x = chroma*(1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+		let (R, G, B) = match sector as usize {
+			0 => (chroma, x, 0.0),
+			1 => (x, chroma, 0.0),
+			2 => (0.0, chroma, x),
+			3 => (0.0, x, chroma),
+			4 => (x, 0.0, chroma),
+			_ => (chroma, 0.0, x)
+		};
+		Color::RGB(R + base, G + base, B + base)
+	}
+	
+	// grays have hue 0, black has saturation 0
+	fn to_hsv(&self) -> (f32, f32, f32) {
+		let (hue, max, min) = self.hue();
+		(hue, if max > 0.0 { (max - min)/max }else { 0.0 }, max)
+	}
+	
+	fn to_hsl(&self) -> (f32, f32, f32) {
+		let (hue, max, min) = self.hue();
+		let lightness = 0.5*(max + min);
+		let spread = 1.0 - (2.0*lightness - 1.0).abs();
+		(hue, if spread > 0.0 { (max - min)/spread }else { 0.0 }, lightness)
+	}
+	
+	// hue in degrees plus the largest and smallest channel, what both conversions start from
+	fn hue(&self) -> (f32, f32, f32) {
+		let (R, G, B) = self.RGB;
+		let (max, min) = (R.max(G).max(B), R.min(G).min(B));
+		let delta = max - min;
+		let hue = if delta <= 0.0 { 0.0 }
+			else if max == R { 60.0*((G - B)/delta).rem_euclid(6.0) }
+			else if max == G { 60.0*((B - R)/delta + 2.0) }
+			else { 60.0*((R - G)/delta + 4.0) };
+		(hue, max, min)
+	}
+	
+	// turn around the color wheel keeping saturation and value
+	fn rotate_hue(&self, degrees: f32) -> Color {
+		let (H, S, V) = self.to_hsv();
+		Color::HSV(H + degrees, S, V)
+	}
+	
+	// 0 gives the gray of the same value, above 1 pushes towards the pure hue (and is clamped there)
+	fn scale_saturation(&self, fac: f32) -> Color {
+		let (H, S, V) = self.to_hsv();
+		Color::HSV(H, clamp(0.0, 1.0, S*fac), V)
+	}
+}
+
+impl std::ops::Add for Color {
+	type Output = Color;
+	fn add(self, other: Color) -> Color { Color::add(&self, other) }
+}
+impl std::ops::Sub for Color {
+	type Output = Color;
+	fn sub(self, other: Color) -> Color { Color::RGB(self.RGB.0 - other.RGB.0, self.RGB.1 - other.RGB.1, self.RGB.2 - other.RGB.2) }
+}
+impl std::ops::Mul<f32> for Color {
+	type Output = Color;
+	fn mul(self, fac: f32) -> Color { Color::mul(&self, fac) }
+}
+impl std::ops::Mul<Color> for f32 {
+	type Output = Color;
+	fn mul(self, c: Color) -> Color { c.mul(self) }
+}
+// per channel, same as hadamard
+impl std::ops::Mul for Color {
+	type Output = Color;
+	fn mul(self, other: Color) -> Color { self.hadamard(other) }
+}
+impl std::ops::Neg for Color {
+	type Output = Color;
+	fn neg(self) -> Color { Color::RGB(-self.RGB.0, -self.RGB.1, -self.RGB.2) }
+}
+// 0, 1, 2 are red, green, blue
+impl std::ops::Index<usize> for Color {
+	type Output = f32;
+	fn index(&self, i: usize) -> &f32 {
+		match i { 0 => &self.RGB.0, 1 => &self.RGB.1, 2 => &self.RGB.2, _ => panic!("Color index {i} out of range") }
+	}
+}
+impl std::ops::IndexMut<usize> for Color {
+	fn index_mut(&mut self, i: usize) -> &mut f32 {
+		match i { 0 => &mut self.RGB.0, 1 => &mut self.RGB.1, 2 => &mut self.RGB.2, _ => panic!("Color index {i} out of range") }
+	}
+}
+impl std::fmt::Display for Color {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match f.precision() {
+			Some(p) => write!(f, "rgb({:.p$}, {:.p$}, {:.p$})", self.RGB.0, self.RGB.1, self.RGB.2),
+			None => write!(f, "rgb({}, {}, {})", self.RGB.0, self.RGB.1, self.RGB.2)
+		}
 	}
 }
 
+impl From<[u8; 3]> for Color {
+	fn from(rgb: [u8; 3]) -> Color { Color::from_rgb8(rgb) }
+}
+impl From<Color> for [u8; 3] {
+	fn from(color: Color) -> [u8; 3] { color.to_rgb8() }
+}
+impl From<[f32; 3]> for Color {
+	fn from(rgb: [f32; 3]) -> Color { Color::RGB(rgb[0], rgb[1], rgb[2]) }
+}
+
 fn clamp(min: f32, max: f32, val: f32) -> f32 {
 	if val >= max { max }else if val < min { min }else { val }
 }
 
+// epsilon is absolute up to magnitude 1 and relative beyond it, so it works for both unit vectors and world coordinates
+fn approx_eq(a: f32, b: f32, epsilon: f32) -> bool {
+	a == b || (a - b).abs() <= epsilon*a.abs().max(b.abs()).max(1.0)
+}
 
-fn load_bitmap(filename: &str) -> std::io::Result<Texture> {
-	println!("importing image: {filename}");
-	let mut file = File::open(format!("./textures/{filename}.ppm"))?;
-	let mut image_data = String::new();
-	file.read_to_string(&mut image_data)?;
-	let to_usize = |s: &str| s.to_string().parse::<usize>().unwrap();
-	
-	let match_header = Regex::new("P3[\n ](?<w>[0-9]+)[\n ](?<h>[0-9]+)[\n ]255").unwrap();
-	let match_pixel = Regex::new("(?<r>[0-9]{1,3})[ ]+(?<g>[0-9]{1,3})[ ]+(?<b>[0-9]{1,3})").unwrap();
+
+// next whitespace separated header field, skipping # comments
+fn netpbm_token<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a str> {
+	loop {
+		while *pos < data.len() && data[*pos].is_ascii_whitespace() { *pos += 1; }
+		if *pos < data.len() && data[*pos] == b'#' {
+			while *pos < data.len() && data[*pos] != b'\n' { *pos += 1; }
+		}else { break; }
+	}
+	let start = *pos;
+	while *pos < data.len() && !data[*pos].is_ascii_whitespace() && data[*pos] != b'#' { *pos += 1; }
+	if start == *pos { None }else { std::str::from_utf8(&data[start..*pos]).ok() }
+}
+
+// every netpbm flavor: P1/P4 bitmaps, P2/P5 grayscale and P3/P6 color, ascii and binary
+// gray and black/white images are expanded to rgb
+fn decode_netpbm(data: &[u8]) -> Result<(usize, usize, Vec<Vec<Color>>), String> {
+	let magic = std::str::from_utf8(data.get(0..2).unwrap_or(&[])).unwrap_or("").to_string();
+	if !["P1", "P2", "P3", "P4", "P5", "P6"].contains(&magic.as_str()) { return Err(format!("unrecognized format {magic:?}, expected P1 to P6")); }
+	let mut pos = 2;
+	let mut field = |name: &str| netpbm_token(data, &mut pos).and_then(|t| t.parse::<usize>().ok()).ok_or(format!("missing or invalid {name}"));
+	let (width, height) = (field("width")?, field("height")?);
+	let max_value = if magic == "P1" || magic == "P4" { 1 }else { field("max value")? };
+	if width == 0 || height == 0 { return Err(format!("image is {width}x{height}, nothing to import")); }
+	if max_value == 0 || max_value > 65535 { return Err(format!("max value {max_value} is out of range")); }
+	let channels = if magic == "P3" || magic == "P6" { 3 }else { 1 };
+	// a header can claim any size, so check it against what the file could hold before trusting it. text formats need
+	// at least a byte per sample, binary ones their exact size
+	let too_large = || format!("{width}x{height} image is larger than the file could hold");
+	let count = width.checked_mul(height).and_then(|n| n.checked_mul(channels)).ok_or_else(too_large)?;
+	let needed = match magic.as_str() {
+		"P4" => ((width + 7)/8).checked_mul(height),
+		"P5" | "P6" => count.checked_mul(if max_value > 255 { 2 }else { 1 }),
+		_ => Some(count)
+	};
+	if needed.map_or(true, |needed| needed > data.len().saturating_sub(pos)) { return Err(too_large()); }
 	
-	print!("extracting header...");
-	let (header, [w, h]) = if let Some(capture) = match_header.captures(&image_data) { capture.extract() }
-	else {
-		println!("error: unable to recognize header, check if the image is ppm version 3");
-		return Ok(Texture::missing(10, 10, 1));
+	let samples: Vec<f32> = match magic.as_str() {
+		"P1" => data[pos..].iter().filter(|c| **c == b'0' || **c == b'1').map(|c| if *c == b'1' { 0.0 }else { 1.0 }).collect(), // digits don't need whitespace between them
+		"P2" | "P3" => {
+			let mut values = Vec::new();
+			while let Some(token) = netpbm_token(data, &mut pos) {
+				values.push(token.parse::<usize>().map_err(|_| format!("invalid sample {token:?}"))? as f32 / max_value as f32);
+			}
+			values
+		},
+		"P4" => { // rows are padded to whole bytes, 1 is black
+			let row_bytes = (width + 7)/8;
+			let raw = data.get(pos+1..pos+1 + row_bytes*height).ok_or("image data is cut off")?;
+			(0..height).flat_map(|h| (0..width).map(move |w| if raw[h*row_bytes + w/8] & (0x80 >> (w % 8)) != 0 { 0.0 }else { 1.0 })).collect()
+		},
+		_ => { // P5/P6, 16 bit big endian samples when the max value doesn't fit in a byte
+			let bytes = if max_value > 255 { 2 }else { 1 };
+			let raw = data.get(pos+1..pos+1 + count*bytes).ok_or("image data is cut off")?;
+			raw.chunks(bytes).map(|c| c.iter().fold(0, |v, b| (v << 8) | *b as usize) as f32 / max_value as f32).collect()
+		}
 	};
-	let (width, height) = (to_usize(w), to_usize(h));
-	image_data = (&image_data[header.len()..]).to_string();
-	println!("done!");
+	if samples.len() < count { return Err(format!("expected {count} samples but found {}", samples.len())); }
+	
+	let pixels = samples[..count].chunks(width*channels).map(|row| row.chunks(channels).map(|c| {
+		if channels == 3 { Color::RGB(c[0], c[1], c[2]) }else { Color::RGB(c[0], c[0], c[0]) }
+	}).collect()).collect();
+	Ok((width, height, pixels))
+}
+
+// truecolor and grayscale targa, raw or run length encoded. color mapped images aren't supported
+fn decode_tga(data: &[u8]) -> Result<(usize, usize, Vec<Vec<Color>>), String> {
+	let header = data.get(0..18).ok_or("file is too short for a tga header")?;
+	let (id_length, color_map_type, image_type) = (header[0] as usize, header[1], header[2]);
+	let color_map_size = if color_map_type == 1 { u16::from_le_bytes([header[5], header[6]]) as usize * (header[7] as usize + 7)/8 }else { 0 };
+	let width = u16::from_le_bytes([header[12], header[13]]) as usize;
+	let height = u16::from_le_bytes([header[14], header[15]]) as usize;
+	let (bits, top_down) = (header[16] as usize, header[17] & 0x20 != 0);
+	if ![2, 3, 10, 11].contains(&image_type) { return Err(format!("unsupported tga image type {image_type}")); }
+	if width == 0 || height == 0 { return Err(format!("image is {width}x{height}, nothing to import")); }
+	let gray = image_type == 3 || image_type == 11;
+	if (gray && bits != 8) || (!gray && ![16, 24, 32].contains(&bits)) { return Err(format!("unsupported tga pixel depth {bits}")); }
 	
-	print!("extracting color data...");
-	let (mut pix_buf, mut pix_row) = (Vec::new(), Vec::new());
-	for (i, c) in match_pixel.captures_iter(&image_data).enumerate() {
-		pix_row.push(Color::RGB(to_usize(&c["r"]) as f32 / 255.0, to_usize(&c["g"]) as f32 / 255.0, to_usize(&c["b"]) as f32 / 255.0));
-		if (i+1) % width == 0 { pix_buf.push(pix_row.clone()); pix_row.clear();}
+	let bytes = bits/8;
+	let mut raw = &data[min(18 + id_length + color_map_size, data.len())..];
+	let count = width*height;
+	let mut pixels = Vec::with_capacity(count*bytes);
+	if image_type >= 10 {
+		// packets start with a byte, the top bit says whether one pixel repeats or the pixels follow as is
+		while pixels.len() < count*bytes {
+			let (&packet, rest) = raw.split_first().ok_or("image data is cut off")?;
+			let length = (packet & 0x7f) as usize + 1;
+			if packet & 0x80 != 0 {
+				let pixel = rest.get(..bytes).ok_or("image data is cut off")?;
+				for _ in 0..length { pixels.extend_from_slice(pixel); }
+				raw = &rest[bytes..];
+			}else {
+				pixels.extend_from_slice(rest.get(..length*bytes).ok_or("image data is cut off")?);
+				raw = &rest[length*bytes..];
+		}}
+		pixels.truncate(count*bytes);
+	}else {
+		pixels.extend_from_slice(raw.get(..count*bytes).ok_or("image data is cut off")?);
 	}
-	println!("done!");
-	println!("texture imported successfully!");
+	
+	// stored blue green red, 16 bit pixels are 5 bits per channel
+	let color = |p: &[u8]| match p.len() {
+		1 => Color::RGB(p[0] as f32/255.0, p[0] as f32/255.0, p[0] as f32/255.0),
+		2 => {
+			let v = u16::from_le_bytes([p[0], p[1]]);
+			Color::RGB(((v >> 10) & 31) as f32/31.0, ((v >> 5) & 31) as f32/31.0, (v & 31) as f32/31.0)
+		},
+		_ => Color::RGB(p[2] as f32/255.0, p[1] as f32/255.0, p[0] as f32/255.0)
+	};
+	let mut rows: Vec<Vec<Color>> = pixels.chunks(width*bytes).map(|row| row.chunks(bytes).map(color).collect()).collect();
+	if !top_down { rows.reverse(); }
+	Ok((width, height, rows))
+}
+
+// netpbm or tga by extension, see AssetManager::texture for how the file is found
+fn read_bitmap(path: &Path) -> std::io::Result<Texture> {
+	import_println!("importing image: {}", path.display());
+	let mut image_data = Vec::new();
+	File::open(path)?.read_to_end(&mut image_data)?;
+	
+	import_print!("extracting color data...");
+	let image = if path.extension().is_some_and(|ext| ext == "tga") { decode_tga(&image_data) }else { decode_netpbm(&image_data) };
+	let (width, height, pix_buf) = match image {
+		Ok(image) => image,
+		Err(error) => {
+			println!("error: {error}");
+			return Ok(Texture::missing(10, 10, 1));
+	}};
+	import_println!("done!");
+	import_println!("texture imported successfully!");
 	
 	Ok(Texture::new(width, height, pix_buf))
 }
 
 
-fn load_material(filename: String) -> std::io::Result<(Material, Texture)> {
-	println!("importing material: {filename}");
-	let mut mtl = File::open(format!("./materials/{filename}"))?;
-	let mut mtl_data = String::new();
-	mtl.read_to_string(&mut mtl_data);
-
-	let attrib_patterns = vec![
-		("header", Regex::new("newmtl (?<result>[a-zA-Z0-9_-]+)\n").unwrap()),
-		("ambient", Regex::new("Ka (?<result>[0-9]+.[0-9]+ [0-9]+.[0-9]+ [0-9]+.[0-9]+)\n").unwrap()),
-		("diffuse", Regex::new("Kd (?<result>[0-9]+.[0-9]+ [0-9]+.[0-9]+ [0-9]+.[0-9]+)\n").unwrap()),
-		("specular", Regex::new("Ks (?<result>[0-9]+.[0-9]+ [0-9]+.[0-9]+ [0-9]+.[0-9]+)\n").unwrap()),
-		("highlights", Regex::new("Ns (?<result>[0-9]+.?[0-9]*)\n").unwrap()),
-		("opacity", Regex::new("d (?<result>[0-9]+.?[0-9]*)\n").unwrap()),
-		("texture", Regex::new("map_Kd (?<result>[a-zA-Z0-9_-]+).ppm").unwrap())
-	];
-	
-	let mut string_components = Vec::new();
-	let mut material = Material::missing();
-	let mut texture = Texture::missing(10, 10, 1);
-	
-	for attrib in attrib_patterns.iter() {
-		print!("reading material component {}... ", attrib.0);
-		if let Some(capture) = attrib.1.captures(&mtl_data) {
-			let component = capture["result"].to_string();
-			println!("{component}");
-			string_components.push((attrib.0, component));
-		}else {
-			println!("component missing, setting to default");
-	}}
-	let unpack_color = |component: String| {
-		let RGB: Vec<f32> = component.split(" ").map(|s| s.parse::<f32>().unwrap()).collect();
-		Color::RGB(RGB[0], RGB[1], RGB[2])
-	};
-	
-	for component in string_components {
-		match component.0 {
-			"ambient" => { material.ambient = unpack_color(component.1); },
-			"diffuse" => { material.diffuse = unpack_color(component.1); },
-			"specular" => { material.specular = unpack_color(component.1);},
-			"highlights" => { material.highlights = component.1.parse::<f32>().unwrap(); },
-			"opacity" => { material.opacity = component.1.parse::<f32>().unwrap(); },
-			"texture" => { texture = load_bitmap(&component.1)?; },
-			"header" => (),
-			other => {
-				println!("error: unrecognized component: {other}");
-				return Ok((Material::missing(), Texture::missing(10, 10, 1)));
-			}
-	}}
-	println!("material imported successfully!");
-	Ok((material, texture))
+// which asset file an AssetManager loaded, they stay valid for as long as the manager does
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct TextureHandle(usize);
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct MaterialHandle(usize);
+
+// where objects, material files and textures are looked up, and the textures and material files loaded so far.
+// every root is searched in order for objects/, materials/ and textures/ subdirectories. textures and material files
+// are cached by the file they resolved to, so a texture that several material files use is only decoded once.
+// with the http feature roots and asset names can also be http:// urls, files found there are downloaded into
+// cache_dir (mirroring the url) and read from there from then on
+struct AssetManager {
+	roots: Vec<PathBuf>,
+	cache_dir: PathBuf,
+	loaded: Arc<Mutex<LoadedAssets>> // shared with the copies loading threads use, see shared_copy
+}
+
+#[derive(Default)]
+struct LoadedAssets {
+	textures: Vec<Texture>,
+	texture_files: HashMap<PathBuf, TextureHandle>,
+	materials: Vec<Vec<(String, Material, Texture)>>,
+	material_files: HashMap<PathBuf, MaterialHandle>
 }
 
+fn is_url(path: &str) -> bool { path.starts_with("http://") || path.starts_with("https://") }
 
-fn load_object(filename: &str) -> std::io::Result<Mesh> {
-	println!("importing object: {filename}.obj");
-	let mut obj_file = File::open(format!("./objects/{filename}.obj"))?;
-	let mut obj_data = String::new();
-	obj_file.read_to_string(&mut obj_data)?;
+impl AssetManager {
+	// the working directory only, where the loaders always looked
+	fn new() -> AssetManager {
+		AssetManager{ roots: vec![PathBuf::from(".")], cache_dir: PathBuf::from("asset_cache"), loaded: Arc::new(Mutex::new(LoadedAssets::default())) }
+	}
 	
-	let num = r"-?[0-9]+.[0-9]+e?(\+|-)?[0-9]*";
-	let match_mtl_filename = Regex::new("mtllib (?<mtlfile>[a-zA-Z0-9_-]+.mtl)").unwrap();
-	let match_geometry_vertex = Regex::new(&format!("v {num} {num} {num}")).unwrap();
-	let match_texture_coord = Regex::new(&format!("vt {num} {num}")).unwrap();
+	// searched after the roots already added
+	fn root(mut self, dir: &str) -> AssetManager { self.add_root(dir); self }
 	
-	let detect_tri = Regex::new("f [0-9]+/?(?<tx>[0-9]*)/?(?<vn>[0-9]*)").unwrap();
+	fn add_root(&mut self, dir: &str) {
+		if is_url(dir) && cfg!(not(feature = "http")) { println!("warning: asset root {dir} is a url, fetching needs the http feature"); }
+		self.roots.push(PathBuf::from(dir));
+	}
 	
-	let to_usize = |s: &str| s.parse::<usize>().unwrap();
-	let to_f32 = |s: &str| s.parse::<f32>().unwrap();
-
-	print!("detecting material file... ");
+	// urls laid out like an asset root (http://host/demo/objects/column.obj) add that root, so the material files and
+	// textures the asset uses are found next to it
+	fn add_root_of(&mut self, url: &str) {
+		let base = ["/objects/", "/materials/", "/textures/"].iter().find_map(|dir| url.rsplit_once(dir).map(|(base, _)| base));
+		if let Some(base) = base.filter(|_| is_url(url)) {
+			if !self.roots.iter().any(|root| root.to_str() == Some(base)) { self.add_root(base); }
+		}
+	}
 	
-	let mtl_filename = if let Some(capture) = match_mtl_filename.captures(&obj_data) { Some(capture["mtlfile"].to_string()) }
-	else { None };
-	if mtl_filename.is_some() { println!("{}", mtl_filename.clone().unwrap()); }else { println!("no material file"); }
+	// where fetched files are kept, asset_cache in the working directory by default
+	fn cache(mut self, dir: &str) -> AssetManager { self.cache_dir = PathBuf::from(dir); self }
 	
-	print!("detecting triangle data format... ");
-	let (_, [tex, norm]) = if let Some(capture) = detect_tri.captures(&obj_data) { capture.extract() }
-	else {
-		println!("error: unable to recognize triangle data!");
-		return Ok(Mesh::empty());
-	};
+	// same roots and the same loaded textures and material files, for loading on other threads. whatever one copy loads
+	// the others find already loaded
+	fn shared_copy(&self) -> AssetManager { AssetManager{ roots: self.roots.clone(), cache_dir: self.cache_dir.clone(), loaded: Arc::clone(&self.loaded) } }
 	
-	let mut tri = "[0-9]+".to_string();
-	let (tex_coords_included, normals_included) = (tex.len() != 0, norm.len() != 0);
-	if tex_coords_included { tri.push_str("/[0-9]+"); }
-	if normals_included {
-		if tex_coords_included { tri.push_str("/[0-9]+"); }else { tri.push_str("//[0-9]+"); }
+	// the first root with subdir/file in it. when none has it the first root's path comes back, so opening it
+	// fails with the usual not found error
+	fn find(&self, subdir: &str, file: &str) -> PathBuf {
+		self.find_any(subdir, &[file.to_string()])
 	}
-	let match_face_data = Regex::new(&format!("f {tri} {tri} {tri}")).unwrap();
 	
-	println!("normals: {normals_included}, texture coordinates: {tex_coords_included}");
+	// like find, for several candidate names that are tried in order in each root. a url as the name is fetched
+	// directly, anything it references (an obj's mtllib, a material's textures) is still looked up through the roots
+	fn find_any(&self, subdir: &str, files: &[String]) -> PathBuf {
+		if files.iter().any(|file| is_url(file)) {
+			return files.iter().find_map(|file| self.fetch(file)).unwrap_or(self.cached(&files[0]));
+		}
+		let mut candidates = self.roots.iter().flat_map(|root| files.iter().map(move |file| (root, file)));
+		let found = candidates.clone().find_map(|(root, file)| match root.to_str().filter(|root| is_url(root)) {
+			Some(url) => self.fetch(&format!("{}/{subdir}/{file}", url.trim_end_matches('/'))),
+			None => Some(root.join(subdir).join(file)).filter(|path| path.exists())
+		});
+		found.or_else(|| candidates.find(|(root, _)| !root.to_str().is_some_and(is_url)).map(|(root, file)| root.join(subdir).join(file))).unwrap_or_default()
+	}
 	
-	// normals can be easily derived from other mesh data
-	// vn values can be either vertex or face normals, not worth the extra implementation complexity tbh
-	// I'll implement a system to handle this logic if it ever becomes necessary
-	let mut vertices: Vec<Vector3D> = Vec::new();
-	let mut tex_coords: Vec<Point2D> = Vec::new();
-	let mut triangles: Vec<Triangle> = Vec::new();
-	let mut tex_tris: Vec<Triangle> = Vec::new();
+	// where url is kept in the cache, cache_dir/host/path with anything odd in the names replaced
+	fn cached(&self, url: &str) -> PathBuf {
+		let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+		let rest = rest.split(['?', '#']).next().unwrap_or(rest);
+		rest.split('/').filter(|part| !part.is_empty() && *part != "." && *part != "..")
+			.map(|part| part.chars().map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c }else { '_' }).collect::<String>())
+			.fold(self.cache_dir.clone(), |path, part| path.join(part))
+	}
 	
-	print!("reading vertex data... ");
-	for v in match_geometry_vertex.captures_iter(&obj_data) {
-		let vertex: Vec<&str> = v.get(0).unwrap().as_str().split(" ").collect();
-		vertices.push(Vector3D::XYZ(to_f32(vertex[1]), to_f32(vertex[2]), to_f32(vertex[3])));
+	// the cached copy of url, downloaded first if it isn't there yet. None when the server doesn't have it
+	#[cfg(feature = "http")]
+	fn fetch(&self, url: &str) -> Option<PathBuf> {
+		let path = self.cached(url);
+		if path.exists() { return Some(path); }
+		match netutils::http_get(url) {
+			Ok(Some(data)) => {
+				import_println!("fetched {url} ({} bytes)", data.len());
+				let written = path.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|_| std::fs::write(&path, data));
+				if let Err(error) = written { println!("warning: couldn't cache {url} at {}: {error}", path.display()); return None; }
+				Some(path)
+			},
+			Ok(None) => None,
+			Err(error) => { println!("warning: couldn't fetch {url}: {error}"); None }
+		}
+	}
+	
+	// without the http feature urls never resolve, only what an earlier build left in the cache is used
+	#[cfg(not(feature = "http"))]
+	fn fetch(&self, url: &str) -> Option<PathBuf> {
+		Some(self.cached(url)).filter(|path| path.exists())
 	}
-	println!("done!");
+	
+	// files that exist are cached under their canonical path, so different ways of spelling it still match
+	fn cache_key(path: &Path) -> PathBuf { std::fs::canonicalize(path).unwrap_or(path.to_path_buf()) }
+	
+	// looks for name.ppm, .pgm, .pbm and .tga in that order. files are read with the lock released so other threads
+	// can load at the same time, two threads reading the same file both end up with the handle of whichever finished first
+	fn texture(&mut self, name: &str) -> std::io::Result<TextureHandle> {
+		let path = self.find_any("textures", &["ppm", "pgm", "pbm", "tga"].map(|ext| format!("{name}.{ext}")));
+		let key = AssetManager::cache_key(&path);
+		if let Some(handle) = self.loaded.lock().unwrap().texture_files.get(&key) {
+			import_println!("image {name} is already loaded");
+			return Ok(*handle);
+		}
+		let texture = read_bitmap(&path)?;
+		let mut loaded = self.loaded.lock().unwrap();
+		if let Some(handle) = loaded.texture_files.get(&key) { return Ok(*handle); }
+		loaded.textures.push(texture);
+		let handle = TextureHandle(loaded.textures.len() - 1);
+		loaded.texture_files.insert(key, handle);
+		Ok(handle)
+	}
+	
+	fn get_texture(&self, handle: TextureHandle) -> Texture { self.loaded.lock().unwrap().textures[handle.0].clone() }
+	
+	// a copy of the texture for meshes and materials, which own theirs
+	fn load_texture(&mut self, name: &str) -> std::io::Result<Texture> {
+		let handle = self.texture(name)?;
+		Ok(self.get_texture(handle))
+	}
+	
+	// every material in an mtl file, filename includes the extension like mtllib does. read like texture is
+	fn material_file(&mut self, filename: &str) -> std::io::Result<MaterialHandle> {
+		let path = self.find("materials", filename);
+		let key = AssetManager::cache_key(&path);
+		if let Some(handle) = self.loaded.lock().unwrap().material_files.get(&key) {
+			import_println!("material file {filename} is already loaded");
+			return Ok(*handle);
+		}
+		let materials = read_materials(self, &path)?;
+		let mut loaded = self.loaded.lock().unwrap();
+		if let Some(handle) = loaded.material_files.get(&key) { return Ok(*handle); }
+		loaded.materials.push(materials);
+		let handle = MaterialHandle(loaded.materials.len() - 1);
+		loaded.material_files.insert(key, handle);
+		Ok(handle)
+	}
+	
+	fn get_materials(&self, handle: MaterialHandle) -> Vec<(String, Material, Texture)> { self.loaded.lock().unwrap().materials[handle.0].clone() }
+}
+
+// calls f with the keyword and the remaining whitespace separated tokens of every statement in an obj or mtl file,
+// comments and blank lines are skipped. the file is read a line at a time into one reused buffer
+fn read_statements(path: &Path, mut f: impl FnMut(&str, std::str::SplitWhitespace) -> std::io::Result<()>) -> std::io::Result<()> {
+	let mut reader = BufReader::new(File::open(path)?);
+	let mut line = String::new();
+	while reader.read_line(&mut line)? > 0 {
+		let mut tokens = line.split('#').next().unwrap_or("").split_whitespace();
+		if let Some(keyword) = tokens.next() { f(keyword, tokens)?; }
+		line.clear();
+	}
+	Ok(())
+}
 
-	if tex_coords_included {
-		print!("reading texture coordinate data... ");
-		for vt in match_texture_coord.captures_iter(&obj_data) {
-			let texcoord: Vec<&str> = vt.get(0).unwrap().as_str().split(" ").collect();
-			tex_coords.push((to_f32(texcoord[1]), to_f32(texcoord[2])));
+// malformed statements stop the loaders with this, they print it and return an empty mesh like other bad input
+fn invalid_data(message: String) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+// every newmtl block in the file with its name, a file without any newmtl is read as one unnamed material. unknown
+// statements are ignored. textures are loaded through assets
+fn read_materials(assets: &mut AssetManager, path: &Path) -> std::io::Result<Vec<(String, Material, Texture)>> {
+	import_println!("importing material: {}", path.display());
+	let mut materials: Vec<(String, Material, Texture)> = Vec::new();
+	// texture statements can have options before the file name (-bm 1.0 and the like), only the last token matters
+	let texture_name = |keyword: &str, tokens: std::str::SplitWhitespace| {
+		let path = tokens.last().unwrap_or("");
+		match path.rsplit_once('.') {
+			Some((name, "ppm" | "pgm" | "pbm" | "tga")) => Some(name.rsplit(['/', '\\']).next().unwrap_or(name).to_string()),
+			_ => { println!("warning: {keyword} {path:?} isn't a ppm, pgm, pbm or tga image, ignoring it"); None }
+	}};
+	let result = read_statements(path, |keyword, mut tokens| {
+		if keyword == "newmtl" {
+			let name = tokens.next().unwrap_or("").to_string();
+			import_println!("reading material {name}");
+			materials.push((name, Material::missing(), Texture::missing(10, 10, 1)));
+			return Ok(());
 		}
-		println!("done!");
-	}else {
-		tex_coords.push((0.0, 0.0));
+		if materials.is_empty() { materials.push((String::new(), Material::missing(), Texture::missing(10, 10, 1))); }
+		let (_, material, texture) = materials.last_mut().unwrap();
+		let number = |tokens: &mut std::str::SplitWhitespace| tokens.next().and_then(|n| n.parse::<f32>().ok()).ok_or(invalid_data(format!("{keyword} needs a number")));
+		match keyword {
+			"Ka" | "Kd" | "Ks" | "Ke" => {
+				// one value is a shade of gray
+				let values: Vec<f32> = tokens.map(|n| n.parse::<f32>()).collect::<Result<_, _>>().map_err(|_| invalid_data(format!("{keyword} has a value that isn't a number")))?;
+				let color = match values.as_slice() {
+					[gray] => Color::RGB(*gray, *gray, *gray),
+					[R, G, B] => Color::RGB(*R, *G, *B),
+					_ => return Err(invalid_data(format!("{keyword} has {} values, expected 1 or 3", values.len())))
+				};
+				match keyword { "Ka" => material.ambient = color, "Kd" => material.diffuse = color, "Ke" => material.emissive = color, _ => material.specular = color };
+			},
+			"Ns" => material.highlights = number(&mut tokens)?,
+			"d" => material.opacity = number(&mut tokens)?,
+			"illum" => material.set_illumination_model(tokens.next().and_then(|n| n.parse::<u32>().ok()).unwrap_or(2)),
+			"map_Kd" => if let Some(name) = texture_name(keyword, tokens) { *texture = assets.load_texture(&name)?; },
+			"map_Ks" => if let Some(name) = texture_name(keyword, tokens) { material.specular_map = Some(assets.load_texture(&name)?); },
+			"map_Ns" => if let Some(name) = texture_name(keyword, tokens) { material.highlight_map = Some(assets.load_texture(&name)?); },
+			"map_d" => if let Some(name) = texture_name(keyword, tokens) { material.opacity_map = Some(assets.load_texture(&name)?); },
+			"map_Ke" => if let Some(name) = texture_name(keyword, tokens) {
+				material.emissive_map = Some(assets.load_texture(&name)?);
+				// a map with no Ke (or Ke 0 0 0 before it) would never show
+				if material.emissive.RGB == (0.0, 0.0, 0.0) { material.emissive = Color::RGB(1.0, 1.0, 1.0); }
+			},
+			// the pbr extension blender and others write, either factor turns the material into a pbr one
+			"Pm" => material.pbr.get_or_insert(Pbr::new(0.0, 0.5)).metallic = number(&mut tokens)?,
+			"Pr" => material.pbr.get_or_insert(Pbr::new(0.0, 0.5)).roughness = number(&mut tokens)?,
+			"map_Pm" => if let Some(name) = texture_name(keyword, tokens) { material.pbr.get_or_insert(Pbr::new(1.0, 0.5)).metallic_map = Some(assets.load_texture(&name)?); },
+			"map_Pr" => if let Some(name) = texture_name(keyword, tokens) { material.pbr.get_or_insert(Pbr::new(0.0, 1.0)).roughness_map = Some(assets.load_texture(&name)?); },
+			"map_Bump" | "map_bump" | "bump" => if let Some(name) = texture_name(keyword, tokens) {
+				let map = assets.load_texture(&name)?;
+				material.normal_map = Some(if map.is_grayscale() { map.normals_from_height(4.0) }else { map });
+			},
+			_ => ()
+		}
+		Ok(())
+	});
+	match result {
+		Err(error) if error.kind() == std::io::ErrorKind::InvalidData => {
+			println!("error: {error}");
+			return Ok(vec![(String::new(), Material::missing(), Texture::missing(10, 10, 1))]);
+		},
+		result => result?
 	}
+	// statements before the first newmtl only count in files without any
+	if materials.len() > 1 && materials[0].0.is_empty() { materials.remove(0); }
+	if materials.is_empty() { materials.push((String::new(), Material::missing(), Texture::missing(10, 10, 1))); }
+	import_println!("material imported successfully!");
+	Ok(materials)
+}
+
+
+fn load_object(assets: &mut AssetManager, filename: &str) -> std::io::Result<Mesh> {
+	Ok(parse_object(assets, filename)?.0)
+}
+
+// every o/g statement in the file as a separate named mesh, in the order they first appear. faces before the first
+// statement go in "default", repeated names are merged like obj readers usually do
+fn load_object_groups(assets: &mut AssetManager, filename: &str) -> std::io::Result<Vec<(String, Mesh)>> {
+	let (object, groups) = parse_object(assets, filename)?;
+	Ok(groups.into_iter().map(|(name, triangles)| (name, object.submesh(&triangles))).collect())
+}
+
+// one face corner as written, 1 based with 0 for an index that couldn't be read
+type ObjCorner = (usize, Option<usize>, Option<usize>);
+
+// a face's first corner in the corner list, its group, its usemtl name and smoothing group
+type ObjFace = (usize, usize, Option<usize>, u32);
+
+// the whole object plus the triangles in each group
+fn parse_object(assets: &mut AssetManager, filename: &str) -> std::io::Result<(Mesh, Vec<(String, Vec<usize>)>)> {
+	import_println!("importing object: {filename}.obj");
+	let mut mtl_filename: Option<String> = None;
+	let mut vertices: Vec<Vector3D> = Vec::new();
+	let mut tex_coords: Vec<Point2D> = Vec::new();
+	let mut normals: Vec<Vector3D> = Vec::new();
+	let mut corners: Vec<ObjCorner> = Vec::new();
+	let mut faces: Vec<ObjFace> = Vec::new();
+	let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+	let mut material_names: Vec<String> = Vec::new();
+	// group name until a face needs its index, so o/g statements without faces don't leave empty groups behind
+	let mut group: Result<usize, String> = Err("default".to_string());
+	let mut material: Option<usize> = None;
+	let mut smoothing_group = 0;
+	let mut smoothing_used = false;
 	
-	print!("reading triangle data... ");
-	for f in match_face_data.captures_iter(&obj_data) {
-		let tri_verts: Vec<&str> = f.get(0).unwrap().as_str().split(" ").skip(1).collect();
-		let mut triangle_data = Vec::new();
-		
-		for v in tri_verts {
-			let data: Vec<&str> = v.split("/").collect();
-			let vertex_id = to_usize(data[0]);
-			
-			let uv_id = if tex_coords_included { to_usize(data[1]) }else { 1 };
-			triangle_data.push([vertex_id, uv_id]);
+	import_print!("reading object data... ");
+	let result = read_statements(&assets.find("objects", &format!("{filename}.obj")), |keyword, mut tokens| {
+		let numbers = |count: usize, tokens: std::str::SplitWhitespace| {
+			let values: Vec<f32> = tokens.take(count).map(|n| n.parse::<f32>()).collect::<Result<_, _>>().map_err(|_| invalid_data(format!("{keyword} has a value that isn't a number")))?;
+			if values.len() < count { return Err(invalid_data(format!("{keyword} has {} values, expected {count}", values.len()))); }
+			Ok(values)
+		};
+		match keyword {
+			"v" => { let v = numbers(3, tokens)?; vertices.push(Vector3D::XYZ(v[0], v[1], v[2])); },
+			"vt" => { let vt = numbers(1, tokens.clone())?; tex_coords.push((vt[0], numbers(2, tokens).map_or(0.0, |vt| vt[1]))); }, // v is optional
+			"vn" => { let vn = numbers(3, tokens)?; normals.push(Vector3D::XYZ(vn[0], vn[1], vn[2]).normalize()); },
+			"f" => {
+				// negative indices count back from the last element read so far
+				let index = |id: &str, count: usize| match id.parse::<i64>() {
+					Ok(id) if id < 0 => (count as i64 + id + 1).max(0) as usize,
+					Ok(id) => id as usize,
+					Err(_) => 0
+				};
+				let first = corners.len();
+				for corner in tokens {
+					let mut ids = corner.split('/');
+					let vertex = index(ids.next().unwrap_or(""), vertices.len());
+					let uv = ids.next().filter(|id| !id.is_empty()).map(|id| index(id, tex_coords.len()));
+					let normal = ids.next().filter(|id| !id.is_empty()).map(|id| index(id, normals.len()));
+					corners.push((vertex, uv, normal));
+				}
+				if corners.len() - first < 3 { return Err(invalid_data(format!("face {} has {} corners, expected at least 3", faces.len() + 1, corners.len() - first))); }
+				let group_index = match &group {
+					Ok(index) => *index,
+					Err(name) => {
+						let index = groups.iter().position(|g| g.0 == *name).unwrap_or_else(|| { groups.push((name.clone(), Vec::new())); groups.len() - 1 });
+						group = Ok(index);
+						index
+				}};
+				faces.push((first, group_index, material, smoothing_group));
+			},
+			"o" | "g" => {
+				let name = tokens.collect::<Vec<&str>>().join(" ");
+				group = Err(if name.is_empty() { "default".to_string() }else { name });
+			},
+			"usemtl" => {
+				let name = tokens.next().unwrap_or("");
+				material = Some(material_names.iter().position(|m| m == name).unwrap_or_else(|| { material_names.push(name.to_string()); material_names.len() - 1 }));
+			},
+			"s" => { smoothing_group = tokens.next().and_then(|s| s.parse().ok()).unwrap_or(0); smoothing_used = true; }, // off and anything unreadable is 0
+			"mtllib" => if mtl_filename.is_none() { mtl_filename = tokens.next().map(|name| name.to_string()); },
+			_ => ()
+		}
+		Ok(())
+	});
+	match result {
+		Err(error) if error.kind() == std::io::ErrorKind::InvalidData => {
+			println!("error: {error}\n");
+			return Ok((Mesh::empty(), Vec::new()));
+		},
+		result => result?
+	}
+	import_println!("done! {} vertices, {} faces", vertices.len(), faces.len());
+	if faces.is_empty() {
+		println!("error: unable to recognize triangle data!");
+		return Ok((Mesh::empty(), Vec::new()));
+	}
+	if let Some(name) = &mtl_filename { import_println!("material file: {name}"); }else { import_println!("no material file"); }
+	
+	// vn values are referenced per face corner, so a vertex shared by faces on both sides of a hard edge keeps a
+	// different normal in each. vertex_normals are still derived for the lighting cache and anything else per vertex
+	let tex_coords_included = corners.iter().any(|c| c.1.is_some());
+	let normals_included = corners.iter().all(|c| c.2.is_some());
+	if !normals_included && corners.iter().any(|c| c.2.is_some()) { println!("warning: only some face corners have normals, ignoring them"); }
+	import_println!("normals: {normals_included}, texture coordinates: {tex_coords_included}");
+	// corners without a texture coordinate all share one at (0, 0)
+	if !tex_coords_included { tex_coords.clear(); }
+	let default_uv = tex_coords.len() + 1;
+	if corners.iter().any(|c| c.1.is_none()) { tex_coords.push((0.0, 0.0)); }
+	
+	let mut triangles: Vec<Triangle> = Vec::with_capacity(corners.len());
+	let mut tex_tris: Vec<Triangle> = Vec::with_capacity(corners.len());
+	let mut normal_tris: Vec<Triangle> = Vec::new();
+	let mut face_material_names: Vec<Option<usize>> = Vec::with_capacity(corners.len()); // per triangle
+	let mut smoothing_groups: Vec<u32> = Vec::with_capacity(corners.len()); // per triangle
+	
+	import_print!("reading triangle data... ");
+	for (f, &(first, group, material, smoothing_group)) in faces.iter().enumerate() {
+		let face_corners = &corners[first..faces.get(f+1).map_or(corners.len(), |next| next.0)];
+		let mut triangle_data = Vec::with_capacity(face_corners.len());
+		for &(vertex_id, uv_id, normal_id) in face_corners {
+			if vertex_id == 0 || vertex_id > vertices.len() {
+				println!("error: vertex index is {vertex_id} but there are {} vertices\n", vertices.len());
+				return Ok((Mesh::empty(), Vec::new()));
+			}
+			let uv_id = uv_id.unwrap_or(default_uv);
+			if uv_id == 0 || uv_id > tex_coords.len() {
+				println!("error: texture coordinate index is {uv_id} but there are {} texture coordinates\n", tex_coords.len());
+				return Ok((Mesh::empty(), Vec::new()));
+			}
+			let normal_id = normal_id.unwrap_or(1);
+			if normals_included && (normal_id == 0 || normal_id > normals.len()) {
+				println!("error: normal index is {normal_id} but there are {} normals\n", normals.len());
+				return Ok((Mesh::empty(), Vec::new()));
+			}
+			triangle_data.push([vertex_id, uv_id, normal_id]);
+		}
+		let polygon = if triangle_data.len() == 3 { vec![(0, 1, 2)] }
+			else { mesh::triangulate(&triangle_data.iter().map(|[vertex_id, _, _]| vertices[vertex_id-1]).collect::<Vec<Vector3D>>()) }; // quads and larger polygons
+		for (c1, c2, c3) in polygon {
+			groups[group].1.push(triangles.len());
+			face_material_names.push(material);
+			smoothing_groups.push(smoothing_group);
+			triangles.push((triangle_data[c1][0]-1, triangle_data[c2][0]-1, triangle_data[c3][0]-1));
+			tex_tris.push((triangle_data[c1][1]-1, triangle_data[c2][1]-1, triangle_data[c3][1]-1));
+			if normals_included { normal_tris.push((triangle_data[c1][2]-1, triangle_data[c2][2]-1, triangle_data[c3][2]-1)); }
 		}
-		triangles.push((triangle_data[0][0]-1, triangle_data[1][0]-1, triangle_data[2][0]-1));
-		tex_tris.push((triangle_data[0][1]-1, triangle_data[1][1]-1, triangle_data[2][1]-1));
 	}
-	println!("done!");
+	if !normals_included { normals.clear(); }
+	import_println!("done! {} groups", groups.len());
 	
+	// the first material is the mesh's own, the rest only come into play when faces switch between several with usemtl
 	let (mut material, mut texture) = (Material::missing(), Texture::missing(10, 10, 1));
-	if mtl_filename.is_some() {
-		(material, texture) = load_material(mtl_filename.unwrap())?;
+	let mut materials = Vec::new();
+	let mut face_materials = Vec::new();
+	if let Some(mtl_filename) = mtl_filename {
+		let handle = assets.material_file(&mtl_filename)?;
+		let mut loaded = assets.get_materials(handle);
+		let used: Vec<&str> = face_material_names.iter().flatten().map(|m| material_names[*m].as_str()).collect();
+		if used.iter().any(|name| *name != used[0]) {
+			let lookup: Vec<usize> = material_names.iter().map(|name| loaded.iter().position(|m| m.0 == *name).unwrap_or(usize::MAX)).collect();
+			face_materials = face_material_names.iter().map(|name| name.map_or(usize::MAX, |name| lookup[name])).collect();
+			if let Some(name) = used.iter().find(|name| !loaded.iter().any(|m| m.0 == **name)) { println!("warning: material {name} isn't in the material file, its faces use the default"); }
+			materials = loaded.iter().map(|(_, material, texture)| (material.clone(), texture.clone())).collect();
+			import_println!("{} materials", materials.len());
+		}
+		if !loaded.is_empty() { (_, material, texture) = loaded.swap_remove(0); }
 	}
 
+	let (vertex_count, triangle_count) = (vertices.len(), triangles.len());
 	let mut object = Mesh{
-		vertices: vertices.clone(),
-		triangles: triangles.clone(),
+		vertices,
+		triangles,
 		tex_coords,
 		tex_tris,
-		face_normals: vec![Vector3D::zero(); triangles.len()],
-		vertex_normals: vec![Vector3D::zero(); vertices.len()],
+		face_normals: vec![Vector3D::zero(); triangle_count],
+		vertex_normals: vec![Vector3D::zero(); vertex_count],
+		normals,
+		normal_tris,
+		vertex_colors: Vec::new(),
 		origin: Vector3D::zero(),
 		texture,
-		material
+		material,
+		materials,
+		face_materials,
+		shader: None,
+		vertex_shader: None,
+		revision: 0,
+		lighting_cache: None
 	};
-	print!("deriving mesh properties... ");
+	import_print!("deriving mesh properties... ");
+	// authored normals win over smoothing groups, files without either are smooth shaded everywhere like before
+	if !normals_included && smoothing_used { object.recalculate_grouped_normals(&smoothing_groups); }
+	else { object.recalculate_normals(); }
+	object.origin = object.center();
+	import_println!("done!");
+	
+	import_println!("object imported successfully!\n");
+	Ok((object, groups))
+}
+
+
+// stl triangles don't share vertices, so every triangle gets its own three with the face normal as their vertex normal
+fn load_stl(assets: &AssetManager, filename: &str) -> std::io::Result<Mesh> {
+	import_println!("importing object: {filename}.stl");
+	let mut stl_data = Vec::new();
+	File::open(assets.find("objects", &format!("{filename}.stl")))?.read_to_end(&mut stl_data)?;
+	
+	// binary files can start with "solid" too, the size matching the triangle count is the reliable check
+	import_print!("detecting format... ");
+	let triangle_count = stl_data.get(80..84).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+	let binary = triangle_count.is_some_and(|n| stl_data.len() == 84 + n*50);
+	import_println!("{}", if binary { "binary" }else { "ascii" });
+	
+	import_print!("reading triangle data... ");
+	// normal followed by the three corners
+	let mut facets: Vec<[Vector3D; 4]> = Vec::new();
+	if binary {
+		let float = |b: &[u8], i: usize| f32::from_le_bytes([b[i], b[i+1], b[i+2], b[i+3]]);
+		for record in stl_data[84..].chunks(50) {
+			let v = |i: usize| Vector3D::XYZ(float(record, i*12), float(record, i*12 + 4), float(record, i*12 + 8));
+			facets.push([v(0), v(1), v(2), v(3)]);
+		}
+	}else {
+		let text = String::from_utf8_lossy(&stl_data);
+		let mut tokens = text.split_whitespace();
+		let (mut normal, mut corners) = (Vector3D::zero(), Vec::new());
+		while let Some(token) = tokens.next() {
+			let mut vector = || -> Option<Vector3D> {
+				let mut next = || tokens.next().and_then(|t| t.parse::<f32>().ok());
+				Some(Vector3D::XYZ(next()?, next()?, next()?))
+			};
+			match token {
+				"normal" => normal = vector().unwrap_or(Vector3D::zero()),
+				"vertex" => match vector() {
+					Some(corner) => corners.push(corner),
+					None => {
+						println!("error: unreadable vertex in facet {}\n", facets.len() + 1);
+						return Ok(Mesh::empty());
+				}},
+				"endfacet" => {
+					if corners.len() != 3 {
+						println!("error: facet {} has {} vertices, expected 3\n", facets.len() + 1, corners.len());
+						return Ok(Mesh::empty());
+					}
+					facets.push([normal, corners[0], corners[1], corners[2]]);
+					corners.clear();
+				},
+				_ => ()
+		}}
+	}
+	import_println!("done!");
+	
+	let mut object = Mesh::new(Vec::new(), Vec::new());
+	for (t, [normal, p1, p2, p3]) in facets.into_iter().enumerate() {
+		// plenty of exporters write zero normals, work them out from the winding then
+		let normal = if normal.mag() > 0.0 && normal.is_finite() { normal.normalize() }else { p2.sub(p1).cross(p3.sub(p1)).normalize() };
+		object.vertices.extend_from_slice(&[p1, p2, p3]);
+		object.vertex_normals.extend_from_slice(&[normal, normal, normal]);
+		object.triangles.push((3*t, 3*t + 1, 3*t + 2));
+		object.tex_tris.push((0, 0, 0));
+		object.face_normals.push(normal);
+	}
+	object.tex_coords.push((0.0, 0.0));
+	object.material.mode = LightingMode::Flat;
+	object.origin = object.center();
+	
+	import_println!("object imported successfully!\n");
+	Ok(object)
+}
+
+// ascii and binary little endian ply, vertex positions plus optional normals and colors, polygon faces are triangulated
+// other elements and properties are read past and ignored
+fn load_ply(assets: &AssetManager, filename: &str) -> std::io::Result<Mesh> {
+	import_println!("importing object: {filename}.ply");
+	let mut ply_data = Vec::new();
+	File::open(assets.find("objects", &format!("{filename}.ply")))?.read_to_end(&mut ply_data)?;
+	
+	import_print!("reading header... ");
+	let Some(header_end) = ply_data.windows(11).position(|w| w == b"end_header\n").map(|p| p + 11)
+		.or_else(|| ply_data.windows(12).position(|w| w == b"end_header\r\n").map(|p| p + 12)) else {
+		println!("error: no end_header line\n");
+		return Ok(Mesh::empty());
+	};
+	let header = String::from_utf8_lossy(&ply_data[..header_end]).to_string();
+	// element name, count, and its properties as (name, type, list count type)
+	let mut elements: Vec<(String, usize, Vec<(String, String, Option<String>)>)> = Vec::new();
+	let mut binary = false;
+	for line in header.lines() {
+		let words: Vec<&str> = line.split_whitespace().collect();
+		match words.as_slice() {
+			["format", "ascii", ..] => binary = false,
+			["format", "binary_little_endian", ..] => binary = true,
+			["format", other, ..] => {
+				println!("error: unsupported ply format: {other}\n");
+				return Ok(Mesh::empty());
+			},
+			["element", name, count] => elements.push((name.to_string(), count.parse().unwrap_or(0), Vec::new())),
+			["property", "list", count_type, item_type, name] => if let Some(element) = elements.last_mut() {
+				element.2.push((name.to_string(), item_type.to_string(), Some(count_type.to_string())));
+			},
+			["property", value_type, name] => if let Some(element) = elements.last_mut() {
+				element.2.push((name.to_string(), value_type.to_string(), None));
+			},
+			_ => ()
+	}}
+	import_println!("{}, {} elements", if binary { "binary" }else { "ascii" }, elements.len());
+	
+	let type_size = |t: &str| match t {
+		"char" | "uchar" | "int8" | "uint8" => 1,
+		"short" | "ushort" | "int16" | "uint16" => 2,
+		"int" | "uint" | "int32" | "uint32" | "float" | "float32" => 4,
+		"double" | "float64" => 8,
+		_ => 0
+	};
+	let body = &ply_data[header_end..];
+	let text = String::from_utf8_lossy(body);
+	let mut tokens = text.split_whitespace();
+	let mut pos = 0;
+	// one scalar, either the next ascii token or the next value_type sized chunk of the binary body
+	let mut read_value = |value_type: &str| -> Option<f64> {
+		if !binary { return tokens.next()?.parse().ok(); }
+		let size = type_size(value_type);
+		let b = body.get(pos..pos + size)?;
+		pos += size;
+		Some(match value_type {
+			"char" | "int8" => b[0] as i8 as f64,
+			"uchar" | "uint8" => b[0] as f64,
+			"short" | "int16" => i16::from_le_bytes([b[0], b[1]]) as f64,
+			"ushort" | "uint16" => u16::from_le_bytes([b[0], b[1]]) as f64,
+			"int" | "int32" => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+			"uint" | "uint32" => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+			"float" | "float32" => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+			"double" | "float64" => f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]),
+			_ => return None
+		})
+	};
+	
+	let (mut vertices, mut normals, mut colors, mut faces) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+	for (name, count, properties) in elements.iter() {
+		import_print!("reading {name} data... ");
+		let find = |property: &str| properties.iter().position(|p| p.0 == property);
+		let (has_normals, has_colors) = (find("nx").is_some(), find("red").is_some());
+		for i in 0..*count {
+			let mut values = Vec::new();
+			for (_, value_type, list) in properties.iter() {
+				let items = match list {
+					Some(count_type) => read_value(count_type).map(|n| n as usize),
+					None => Some(1)
+				};
+				let Some(items) = items else {
+					println!("error: {name} {} is cut short\n", i + 1);
+					return Ok(Mesh::empty());
+				};
+				let mut list_values = Vec::new();
+				for _ in 0..items {
+					let Some(value) = read_value(value_type) else {
+						println!("error: {name} {} is cut short\n", i + 1);
+						return Ok(Mesh::empty());
+					};
+					list_values.push(value);
+				}
+				values.push(list_values);
+			}
+			let get = |property: &str| find(property).and_then(|p| values[p].first().copied()).unwrap_or(0.0) as f32;
+			match name.as_str() {
+				"vertex" => {
+					vertices.push(Vector3D::XYZ(get("x"), get("y"), get("z")));
+					if has_normals { normals.push(Vector3D::XYZ(get("nx"), get("ny"), get("nz"))); }
+					if has_colors {
+						// integer channels are 0-255, float ones are already 0-1
+						let scale = match find("red").map(|p| properties[p].1.as_str()) { Some("float" | "float32" | "double" | "float64") => 1.0, _ => 1.0/255.0 };
+						colors.push(Color::RGB(get("red")*scale, get("green")*scale, get("blue")*scale));
+					}
+				},
+				"face" => {
+					let Some(indices) = find("vertex_indices").or(find("vertex_index")).map(|p| &values[p]) else { continue; };
+					faces.push(indices.iter().map(|i| *i as usize).collect::<Vec<usize>>());
+				},
+				_ => ()
+		}}
+		import_println!("done!");
+	}
+	if let Some(f) = faces.iter().position(|face| face.iter().any(|i| *i >= vertices.len())) {
+		println!("error: face {} uses a vertex index past the {} vertices\n", f + 1, vertices.len());
+		return Ok(Mesh::empty());
+	}
+	let mut triangles = Vec::new();
+	for face in faces.iter() {
+		let corners: Vec<Vector3D> = face.iter().map(|i| vertices[*i]).collect();
+		triangles.extend(mesh::triangulate(&corners).into_iter().map(|(c1, c2, c3)| (face[c1], face[c2], face[c3])));
+	}
+	
+	let mut object = Mesh::new(vertices, triangles);
+	object.tex_coords.push((0.0, 0.0));
+	object.tex_tris = vec![(0, 0, 0); object.triangles.len()];
+	import_print!("deriving mesh properties... ");
 	object.recalculate_normals();
+	if normals.len() == object.vertices.len() { object.vertex_normals = normals.iter().map(|n| n.normalize()).collect(); }
+	// scans usually have their lighting baked into the colors already, so colored meshes are drawn unlit
+	if colors.len() == object.vertices.len() {
+		object.vertex_colors = colors;
+		object.texture = Texture::new(1, 1, vec![vec![Color::RGB(1.0, 1.0, 1.0)]]);
+	}else {
+		object.material.mode = LightingMode::Smooth;
+	}
 	object.origin = object.center();
-	println!("done!");
+	import_println!("done!");
 	
-	println!("object imported successfully!\n");
+	import_println!("object imported successfully!\n");
 	Ok(object)
 }
 
+// off as used by geometry datasets: vertex/face counts, vertex positions, then polygons with an optional color after the
+// indices. polygons are triangulated, colored faces get their own vertices so the colors don't bleed together
+fn load_off(assets: &AssetManager, filename: &str) -> std::io::Result<Mesh> {
+	import_println!("importing object: {filename}.off");
+	let mut off_data = String::new();
+	File::open(assets.find("objects", &format!("{filename}.off")))?.read_to_string(&mut off_data)?;
+	
+	// lines keep their meaning in off (face colors are whatever trails the indices), so comments are stripped per line
+	let mut lines = off_data.lines().map(|l| l.split('#').next().unwrap_or("").trim()).filter(|l| !l.is_empty());
+	let mut header = lines.next().unwrap_or("");
+	let vertex_colors_included = header.starts_with("COFF");
+	if header.ends_with("OFF") { header = lines.next().unwrap_or(""); }else if let Some(rest) = header.strip_prefix("OFF") { header = rest.trim(); }
+	let counts: Vec<usize> = header.split_whitespace().filter_map(|c| c.parse().ok()).collect();
+	let (vertex_count, face_count) = match counts.as_slice() {
+		[v, f, ..] => (*v, *f),
+		_ => {
+			println!("error: missing vertex and face counts\n");
+			return Ok(Mesh::empty());
+		}
+	};
+	// 0-255 when every channel is a whole number above 1, otherwise already 0-1
+	let to_color = |c: &[f32]| {
+		let scale = if c.iter().any(|v| *v > 1.0) { 1.0/255.0 }else { 1.0 };
+		Color::RGB(c[0]*scale, c[1]*scale, c[2]*scale)
+	};
+	
+	import_print!("reading vertex data... ");
+	let (mut vertices, mut colors) = (Vec::new(), Vec::new());
+	for v in 0..vertex_count {
+		let values: Vec<f32> = lines.next().unwrap_or("").split_whitespace().filter_map(|n| n.parse().ok()).collect();
+		if values.len() < 3 {
+			println!("error: vertex {} has {} coordinates, expected 3\n", v + 1, values.len());
+			return Ok(Mesh::empty());
+		}
+		vertices.push(Vector3D::XYZ(values[0], values[1], values[2]));
+		if vertex_colors_included && values.len() >= 6 { colors.push(to_color(&values[3..6])); }
+	}
+	import_println!("done!");
+	
+	import_print!("reading face data... ");
+	let mut faces: Vec<(Vec<usize>, Option<Color>)> = Vec::new();
+	for f in 0..face_count {
+		let values: Vec<&str> = lines.next().unwrap_or("").split_whitespace().collect();
+		let corners = values.first().and_then(|n| n.parse::<usize>().ok()).unwrap_or(0);
+		let indices: Vec<usize> = values.iter().skip(1).take(corners).filter_map(|i| i.parse().ok()).collect();
+		if indices.len() != corners || corners < 3 {
+			println!("error: face {} is malformed\n", f + 1);
+			return Ok(Mesh::empty());
+		}
+		if let Some(i) = indices.iter().find(|i| **i >= vertices.len()) {
+			println!("error: vertex index is {i} but there are {} vertices\n", vertices.len());
+			return Ok(Mesh::empty());
+		}
+		let color: Vec<f32> = values.iter().skip(1 + corners).filter_map(|c| c.parse().ok()).collect();
+		faces.push((indices, if color.len() >= 3 { Some(to_color(&color)) }else { None }));
+	}
+	import_println!("done!");
+	
+	let face_colors_included = faces.iter().any(|f| f.1.is_some());
+	import_println!("face colors: {face_colors_included}, vertex colors: {}", colors.len() == vertices.len());
+	let mut object = Mesh::new(Vec::new(), Vec::new());
+	if face_colors_included {
+		for (indices, color) in faces.iter() {
+			let first = object.vertices.len();
+			object.vertices.extend(indices.iter().map(|i| vertices[*i]));
+			object.vertex_colors.extend(std::iter::repeat(color.unwrap_or(Color::RGB(1.0, 1.0, 1.0))).take(indices.len()));
+			let corners: Vec<Vector3D> = indices.iter().map(|i| vertices[*i]).collect();
+			object.triangles.extend(mesh::triangulate(&corners).into_iter().map(|(c1, c2, c3)| (first + c1, first + c2, first + c3)));
+		}
+	}else {
+		for (indices, _) in faces.iter() {
+			let corners: Vec<Vector3D> = indices.iter().map(|i| vertices[*i]).collect();
+			object.triangles.extend(mesh::triangulate(&corners).into_iter().map(|(c1, c2, c3)| (indices[c1], indices[c2], indices[c3])));
+		}
+		object.vertices = vertices;
+		if colors.len() == object.vertices.len() { object.vertex_colors = colors; }
+	}
+	object.vertex_normals = vec![Vector3D::zero(); object.vertices.len()];
+	object.face_normals = vec![Vector3D::zero(); object.triangles.len()];
+	object.tex_coords.push((0.0, 0.0));
+	object.tex_tris = vec![(0, 0, 0); object.triangles.len()];
+	
+	import_print!("deriving mesh properties... ");
+	object.recalculate_normals();
+	// same as ply, colored models are drawn unlit with their own colors
+	if object.vertex_colors.is_empty() {
+		object.material.mode = LightingMode::Flat;
+	}else {
+		object.texture = Texture::new(1, 1, vec![vec![Color::RGB(1.0, 1.0, 1.0)]]);
+	}
+	object.origin = object.center();
+	import_println!("done!");
+	
+	import_println!("object imported successfully!\n");
+	Ok(object)
+}
 
-fn main() {
-    let mut screen = Viewport::new(160, 120, 120.0, Color::RGB(0.251, 0.263, 0.655)); //64, 67, 167
-	let mut cube = load_object("column").unwrap();
-	let tex = load_bitmap("space_1").unwrap();
+// the binary format exportutils::write_mesh_binary stores, see the layout there. truncated files are an error, anything
+// else that's wrong gives an empty mesh like load_object
+fn load_binary_mesh(assets: &AssetManager, filename: &str) -> std::io::Result<Mesh> {
+	import_println!("importing binary mesh: {filename}.mesh");
+	let mut content = Vec::new();
+	File::open(assets.find("objects", &format!("{filename}.mesh")))?.read_to_end(&mut content)?;
+	import_print!("reading geometry and materials... ");
+	match exportutils::decode_mesh(&content) {
+		Err(error) if error.kind() == std::io::ErrorKind::InvalidData => {
+			import_println!("error: {error}\n");
+			Ok(Mesh::empty())
+		},
+		Err(error) => Err(error),
+		Ok(mesh) => {
+			import_println!("done!");
+			import_println!("binary mesh imported successfully!\n");
+			Ok(mesh)
+		}
+	}
+}
+
+// loads several files at once on a small pool of threads, results come back in the same order as the names
+// the per file import logs are silenced while this runs (errors still print) and replaced with one progress line per file
+// every thread shares assets' loaded textures and material files, so a texture used by several objects is read once
+fn load_concurrently<T: Send>(assets: &AssetManager, kind: &str, names: &[&str], load: fn(&mut AssetManager, &str) -> std::io::Result<T>) -> Vec<std::io::Result<T>> {
+	if names.is_empty() { return Vec::new(); }
+	let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(names.len()).max(1);
+	println!("importing {} {kind} on {workers} threads", names.len());
+	let next = std::sync::atomic::AtomicUsize::new(0);
+	let done = std::sync::atomic::AtomicUsize::new(0);
+	let results = Mutex::new((0..names.len()).map(|_| None).collect::<Vec<Option<std::io::Result<T>>>>());
+	
+	thread::scope(|scope| {
+		for _ in 0..workers {
+			scope.spawn(|| {
+				QUIET_IMPORT.with(|quiet| quiet.set(true));
+				let mut assets = assets.shared_copy();
+				loop {
+					let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+					if i >= names.len() { break; }
+					let result = load(&mut assets, names[i]);
+					let status = match &result { Ok(_) => "done".to_string(), Err(e) => format!("failed ({e})") };
+					let count = done.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+					println!("[{count}/{}] {}: {status}", names.len(), names[i]);
+					results.lock().unwrap()[i] = Some(result);
+			}});
+	}});
+	println!("{kind} imported!\n");
+	results.into_inner().unwrap().into_iter().map(|r| r.unwrap()).collect()
+}
+
+fn load_objects(assets: &AssetManager, filenames: &[&str]) -> Vec<std::io::Result<Mesh>> {
+	load_concurrently(assets, "objects", filenames, load_object)
+}
+
+// any of the mesh formats, picked by the extension with obj when there's none
+fn load_mesh_file(assets: &mut AssetManager, name: &str) -> std::io::Result<Mesh> {
+	match name.rsplit_once('.') {
+		Some((stem, "stl")) => load_stl(assets, stem),
+		Some((stem, "ply")) => load_ply(assets, stem),
+		Some((stem, "off")) => load_off(assets, stem),
+		Some((stem, "mesh")) => load_binary_mesh(assets, stem),
+		Some((stem, "obj")) => load_object(assets, stem),
+		_ => load_object(assets, name)
+	}
+}
+
+
+// the spinning column everything gets tested on, lights are added to the screen
+fn load_demo_scene(screen: &mut Viewport) -> Mesh {
+	let mut assets = AssetManager::new();
+	let mut cube = load_object(&mut assets, "column").unwrap();
+	let tex = assets.load_texture("space_1").unwrap();
 	cube.texture = tex;
 	
 	cube.transform(Transform::Translate(Vector3D::XYZ(0.0, -5.0, -5.0)));
@@ -334,12 +1325,602 @@ fn main() {
 	
 	screen.lights.push(LightSource::new(Color::RGB(0.9, 0.9, 0.9), Vector3D::XYZ(30.0, 20.0, -5.0)));
 	screen.lights.push(LightSource::new(Color::RGB(0.9, 0.9, 0.9), Vector3D::XYZ(-30.0, -20.0, -5.0)));
+	cube
+}
+
+// plays a scene file in the terminal, or with an output name renders one frame of it to that image instead
+// space pauses, h/l or the arrow keys step a frame, [ and ] ten, 0-9 jump to tenths of the way through, f frames the
+// meshes as they are posed, m shows a loupe (moved with H/J/K/L) with the exact values of the pixel under it, tab
+// picks a mesh and x/y/z move it half a unit (X/Y/Z back), w saves the scene starting from the frame shown to
+// saved_scene.toml and q quits. without a terminal to read keys from it
+// plays once through
+// budget in milliseconds replaces the scene's own, frames that go over it are finished as wireframe. with a record name
+// every frame shown is saved to record.gif when the player quits. paused opens on frame without playing
+fn scene_demo(assets: &mut AssetManager, path: &str, frames: usize, frame: usize, output: Option<String>, budget: Option<f32>, record: Option<String>, paused: bool) -> std::io::Result<()> {
+	let mut scene = scene::Scene::from_file(path, assets)?;
+	if let Some(ms) = budget.filter(|ms| *ms > 0.0 && ms.is_finite()) {
+		scene.viewport.watchdog = Some(Watchdog::new(time::Duration::from_secs_f32(ms/1000.0), Overrun::Wireframe));
+	}
+	let mut timeline = scene::Timeline::new(frames);
+	timeline.seek(frame as isize);
+	if let Some(output) = output {
+		scene.pose(timeline.frame as f32);
+		scene.draw();
+		return scene.viewport.screenshot(&output);
+	}
+	
+	let interactive = std::io::stdin().is_terminal();
+	timeline.looping = interactive;
+	scene.detect_terminal();
+	// there are no keys to unpause with without a terminal
+	if paused && interactive { timeline.pause(); }else { timeline.play(); }
+	let (send, keys) = std::sync::mpsc::channel();
+	if interactive {
+		let _ = scene.viewport.backend.enter_raw_mode();
+		// reads block, so they happen off to the side and the player picks them up between frames
+		thread::spawn(move || {
+			let mut key = [0u8; 3];
+			while let Ok(read @ 1..) = std::io::stdin().read(&mut key) {
+				if send.send(key[..read].to_vec()).is_err() { break; }
+			}
+		});
+	}
+	let (width, height) = scene.viewport.size();
+	let (mut shown, mut loupe, mut selected) = (None, None, 0);
+	let mut recorder = record.as_ref().map(|_| exportutils::FrameRecorder::new((100.0/scene::SCENE_FPS).round() as u16));
+	loop {
+		if !interactive && !timeline.playing { break; }
+		if shown != Some((timeline.frame, timeline.playing)) {
+			scene.pose(timeline.frame as f32);
+			scene.draw();
+			if let Some(recorder) = recorder.as_mut() { recorder.record(&scene.viewport); }
+			let sample = loupe.and_then(|(x, y)| scene.viewport.draw_loupe(x, y, 3, 3));
+			let y = height as f32 - 1.0;
+			scene.viewport.draw_line((0.0, y), (timeline.progress()*(width as f32 - 1.0), y), Color::RGB(1.0, 1.0, 1.0));
+			print!("\x1b[H");
+			scene.viewport.display();
+			let slow = scene.viewport.watchdog.is_some_and(|watchdog| watchdog.degraded());
+			print!("frame {}/{}{}{}", timeline.frame + 1, timeline.length, if timeline.playing { "" }else { " (paused)" }, if slow { " (over budget)" }else { "" });
+			if let Some(sample) = sample {
+				let (r, g, b) = sample.color.RGB;
+				let normal = sample.normal;
+				print!(", pixel {:?}: rgb {r:.3} {g:.3} {b:.3} depth {:.3} normal {:.3} {:.3} {:.3}", sample.position, sample.depth, normal.X, normal.Y, normal.Z);
+			}
+			if scene.meshes.len() > 1 { print!(", mesh {}/{}", selected + 1, scene.meshes.len()); }
+			print!("\x1b[K");
+			shown = Some((timeline.frame, timeline.playing));
+		}
+		thread::sleep(time::Duration::from_secs_f32(1.0/scene::SCENE_FPS));
+		
+		let mut quit = false;
+		while let Ok(key) = keys.try_recv() {
+			match &key[..] {
+				b"q" => quit = true,
+				b" " => timeline.toggle(),
+				b"h" | b"\x1b[D" => timeline.step(-1),
+				b"l" | b"\x1b[C" => timeline.step(1),
+				b"[" => timeline.step(-10),
+				b"]" => timeline.step(10),
+				[digit @ b'0'..=b'9'] => timeline.scrub((digit - b'0') as f32/10.0),
+				b"f" => {
+					scene.frame(0.1);
+					shown = None;
+				},
+				b"m" => {
+					loupe = if loupe.is_some() { None }else { Some((width/2, height/2)) };
+					shown = None;
+				},
+				[key @ (b'H' | b'J' | b'K' | b'L')] => if let Some((x, y)) = loupe.as_mut() {
+					match key {
+						b'H' => *x = x.saturating_sub(1),
+						b'L' => *x = min(*x + 1, width - 1),
+						b'K' => *y = y.saturating_sub(1),
+						_ => *y = min(*y + 1, height - 1)
+					}
+					shown = None;
+				},
+				b"\t" => {
+					selected = (selected + 1) % scene.meshes.len().max(1);
+					shown = None;
+				},
+				[axis @ (b'x' | b'y' | b'z' | b'X' | b'Y' | b'Z')] => if selected < scene.meshes.len() {
+					let step = if axis.is_ascii_uppercase() { -0.5 }else { 0.5 };
+					let offset = match axis.to_ascii_lowercase() {
+						b'x' => Vector3D::XYZ(step, 0.0, 0.0),
+						b'y' => Vector3D::XYZ(0.0, step, 0.0),
+						_ => Vector3D::XYZ(0.0, 0.0, step)
+					};
+					scene.transform(selected, Transform::Translate(offset));
+					shown = None;
+				},
+				b"w" => {
+					print!("\r\n");
+					scene.save("saved_scene.toml", timeline.frame as f32)?;
+					shown = None; // the messages scrolled the frame, draw it again
+				},
+				_ => ()
+			}
+		}
+		if quit { break; }
+		timeline.tick();
+	}
+	println!();
+	if interactive { let _ = scene.viewport.backend.leave_raw_mode(); }
+	if let (Some(recorder), Some(name)) = (recorder, record) { recorder.save(name.trim_end_matches(".gif"))?; }
+	Ok(())
+}
+
+// renders count frames of an animation authored at SCENE_FPS as fps frames a second, render draws the scene posed at a
+// (fractional) authored frame and returns the finished image. faster rates re-render the in between poses rather than
+// repeating frames, so the motion stays smooth. format gif writes one animated output.gif, gif delays are in
+// hundredths of a second so rates above 50 play back slower than they should. anything else writes numbered images
+// ffmpeg -framerate 60 -i frames/frame_%05d.png demo.mp4
+fn export_animation<F: FnMut(f32) -> Vec<Vec<Color>>>(count: usize, fps: f32, output: &str, format: &str, mut render: F) -> std::io::Result<()> {
+	let step = scene::SCENE_FPS/fps.max(1.0);
+	let total = (count as f32/step).round().max(1.0) as usize;
+	let mut recorder = exportutils::FrameRecorder::new((100.0*step/scene::SCENE_FPS).round().max(1.0) as u16);
+	if format != "gif" { std::fs::create_dir_all(output)?; }
+	
+	println!("exporting {total} frames at {fps} fps to {output}{}", if format == "gif" { ".gif" }else { "/" });
+	for i in 0..total {
+		let frame = render(i as f32*step);
+		if format == "gif" { recorder.frames.push(frame); }
+		else { std::fs::write(format!("{output}/frame_{i:05}.{format}"), if format == "ppm" { displayutils::encode_ppm(&frame) }else { displayutils::encode_png(&frame) })?; }
+		print!("\rframe {}/{total}", i+1);
+		let _ = std::io::Write::flush(&mut std::io::stdout());
+	}
+	println!();
+	if format == "gif" { return recorder.save(output); }
+	println!("frames exported successfully!");
+	Ok(())
+}
+
+// headless rendering of the demo animation, nothing is drawn to the terminal so any resolution works. count is in
+// frames of the 20 fps demo
+fn export_frames(count: usize, fps: f32, width: usize, height: usize, output: &str, format: &str) -> std::io::Result<()> {
+	let mut screen = Viewport::headless(width, height, 120.0*width as f32/160.0, Color::RGB(0.251, 0.263, 0.655)); // focal length scaled to keep the 160x120 framing
+	let mut cube = load_demo_scene(&mut screen);
+	cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+	let spin = Transform::Rotate(Vector3D::XYZ(1.0, 0.01, -0.01), Vector3D::XYZ(1.0, 0.02, 0.0));
+	
+	export_animation(count, fps, output, format, |frame| {
+		let mut clipped_cube = cube.clone();
+		clipped_cube.transform(spin.scaled(frame));
+		screen.clip_against_plane(&mut clipped_cube, Vector3D::XYZ(0.0, 0.0, -3.0), Vector3D::XYZ(0.0, 0.0, -1.0));
+		screen.clear_screen();
+		screen.draw_mesh(&clipped_cube);
+		screen.graded_pixels()
+	})
+}
+
+// the same for a scene file, at the viewport size the scene asks for
+fn export_scene(assets: &mut AssetManager, path: &str, count: usize, fps: f32, output: &str, format: &str) -> std::io::Result<()> {
+	let mut scene = scene::Scene::from_file(path, assets)?;
+	export_animation(count, fps, output, format, |frame| {
+		scene.pose(frame);
+		scene.draw();
+		scene.viewport.graded_pixels()
+	})
+}
+
+// renders the demo scene a few ways in deterministic mode and checks every frame against the reference images in dir,
+// or with bless replaces the references with what it renders now
+fn golden_test(dir: &str, bless: bool) -> std::io::Result<bool> {
+	if bless { std::fs::create_dir_all(dir)?; }
+	let mut passed = true;
+	for (name, mode) in [("smooth", LightingMode::Smooth), ("flat", LightingMode::Flat), ("gouraud", LightingMode::Gouraud), ("toon", LightingMode::Toon(3)), ("unlit", LightingMode::None)] {
+		let mut screen = Viewport::headless(160, 120, 120.0, Color::RGB(0.251, 0.263, 0.655));
+		screen.set_deterministic(1);
+		QUIET_IMPORT.with(|quiet| quiet.set(true));
+		let mut cube = load_demo_scene(&mut screen);
+		QUIET_IMPORT.with(|quiet| quiet.set(false));
+		cube.material.mode = mode;
+		cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+		screen.draw_mesh(&cube);
+		screen.draw_wireframe(&cube);
+		passed &= testutils::check_golden(&screen, &format!("{dir}/{name}.ppm"), 0, bless)?;
+	}
+	// the same model cut diagonally through its middle, so the clipped edges and their interpolated attributes are covered
+	let mut screen = Viewport::headless(160, 120, 120.0, Color::RGB(0.251, 0.263, 0.655));
+	screen.set_deterministic(1);
+	QUIET_IMPORT.with(|quiet| quiet.set(true));
+	let mut cube = load_demo_scene(&mut screen);
+	QUIET_IMPORT.with(|quiet| quiet.set(false));
+	cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+	let middle = cube.bounds().center();
+	screen.clip_against_plane(&mut cube, middle, Vector3D::XYZ(1.0, 1.0, 0.5));
+	screen.draw_mesh(&cube);
+	screen.draw_wireframe(&cube);
+	passed &= testutils::check_golden(&screen, &format!("{dir}/clipped.ppm"), 0, bless)?;
+	// a custom fragment shader in place of the lighting
+	let mut screen = Viewport::headless(160, 120, 120.0, Color::RGB(0.251, 0.263, 0.655));
+	screen.set_deterministic(1);
+	QUIET_IMPORT.with(|quiet| quiet.set(true));
+	let mut cube = load_demo_scene(&mut screen);
+	QUIET_IMPORT.with(|quiet| quiet.set(false));
+	cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+	cube.shader = Some(std::sync::Arc::new(DepthHeatmap{ near: 3.0, far: 10.0 }));
+	screen.draw_mesh(&cube);
+	passed &= testutils::check_golden(&screen, &format!("{dir}/heatmap.ppm"), 0, bless)?;
+	// a closure shader reading the other inputs: stripes along u, darker where the normal turns away from the camera
+	cube.shader = Some(std::sync::Arc::new(|fragment: &Fragment| {
+		let stripe = if (fragment.UV.0*8.0).rem_euclid(1.0) < 0.5 { fragment.material.diffuse }else { Color::RGB(1.0, 1.0, 1.0) };
+		stripe.mul(fragment.normal.Z.abs())
+	}));
+	screen.clear_screen();
+	screen.draw_mesh(&cube);
+	passed &= testutils::check_golden(&screen, &format!("{dir}/stripes.ppm"), 0, bless)?;
+	// and a vertex shader rippling the surface, partway through the animation
+	let mut screen = Viewport::headless(160, 120, 120.0, Color::RGB(0.251, 0.263, 0.655));
+	screen.set_deterministic(1);
+	QUIET_IMPORT.with(|quiet| quiet.set(true));
+	let mut cube = load_demo_scene(&mut screen);
+	QUIET_IMPORT.with(|quiet| quiet.set(false));
+	cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+	cube.vertex_shader = Some(std::sync::Arc::new(SineWave{ amplitude: 0.2, wavelength: 1.5, speed: 1.0 }));
+	screen.time = 0.4;
+	screen.draw_mesh(&cube);
+	passed &= testutils::check_golden(&screen, &format!("{dir}/wave.ppm"), 0, bless)?;
+	// a cubemap behind the model, each face a different color shaded across u and v so a flipped face shows
+	let mut screen = Viewport::headless(160, 120, 120.0, Color::RGB(0.251, 0.263, 0.655));
+	screen.set_deterministic(1);
+	QUIET_IMPORT.with(|quiet| quiet.set(true));
+	let mut cube = load_demo_scene(&mut screen);
+	QUIET_IMPORT.with(|quiet| quiet.set(false));
+	cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+	let face = |tint: Color| Texture::bake(&move |uv: Point2D| tint.mul(0.25 + 0.5*uv.0 + 0.25*uv.1), 8, 8);
+	let tints = [Color::RGB(1.0, 0.0, 0.0), Color::RGB(0.0, 1.0, 1.0), Color::RGB(0.0, 1.0, 0.0), Color::RGB(1.0, 0.0, 1.0), Color::RGB(0.0, 0.0, 1.0), Color::RGB(1.0, 1.0, 0.0)];
+	screen.environment = Some(Environment::Cubemap(Box::new(tints.map(face))));
+	screen.camera.look_at(Vector3D::XYZ(-1.0, 0.5, -1.0));
+	screen.clear_screen();
+	screen.draw_mesh(&cube);
+	passed &= testutils::check_golden(&screen, &format!("{dir}/environment.ppm"), 0, bless)?;
+	println!("{}", if bless { "golden images updated!" }else if passed { "golden images match!" }else { "golden image mismatch, see the .diff.ppm files" });
+	Ok(passed)
+}
+
+// dump every pipeline intermediate for one pixel ("x,y") or triangle ("t12") of the smooth golden frame to path
+fn trace_demo(target: &str, path: &str) -> std::io::Result<()> {
+	let target = if let Some(t) = target.strip_prefix('t') { t.parse().ok().map(TraceTarget::Triangle) }
+		else { target.split_once(',').and_then(|(x, y)| Some(TraceTarget::Pixel(x.parse().ok()?, y.parse().ok()?))) };
+	let Some(target) = target else {
+		println!("error: trace target should be a pixel like 80,60 or a triangle like t12");
+		return Ok(());
+	};
+	let mut screen = Viewport::headless(160, 120, 120.0, Color::RGB(0.251, 0.263, 0.655));
+	screen.set_deterministic(1);
+	let mut cube = load_demo_scene(&mut screen);
+	cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+	screen.trace_frame(target, path);
+	screen.draw_mesh(&cube);
+	screen.finish_trace()?;
+	println!("trace of {target:?} written to {path}");
+	Ok(())
+}
+
+// the demo scene as 1 bit text art, printed or saved to a file for printing
+fn text_art(style: &str, dithering: &str, ink: bool, path: Option<String>) -> std::io::Result<()> {
+	let display_mode = match style {
+		"braille" => DisplayMode::Braille,
+		"ascii" => DisplayMode::Ascii,
+		_ => { println!("error: text art style should be braille or ascii"); return Ok(()); }
+	};
+	let dithering = match dithering {
+		"none" => Dithering::None,
+		"bayer" => Dithering::Bayer,
+		"floyd-steinberg" => Dithering::FloydSteinberg,
+		_ => { println!("error: dithering should be none, bayer or floyd-steinberg"); return Ok(()); }
+	};
+	let mut screen = Viewport::builder().headless().display_mode(display_mode).dithering(dithering).ink(ink).deterministic(1).build();
+	let mut cube = load_demo_scene(&mut screen);
+	cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+	screen.draw_mesh(&cube);
+	let art = screen.render_to_string();
+	match path {
+		Some(path) => { std::fs::write(&path, art)?; println!("text art written to {path}"); },
+		None => print!("{art}")
+	}
+	Ok(())
+}
+
+// the demo scene as a vector drawing, name is the output path without .svg
+fn svg_demo(style: &str, name: &str) -> std::io::Result<()> {
+	let style = match style {
+		"wireframe" => exportutils::SvgStyle::Wireframe,
+		"hidden" => exportutils::SvgStyle::HiddenLine,
+		"filled" => exportutils::SvgStyle::Filled,
+		_ => { println!("error: svg style should be wireframe, hidden or filled"); return Ok(()); }
+	};
+	let mut screen = Viewport::headless(160, 120, 120.0, Color::RGB(0.251, 0.263, 0.655));
+	let mut cube = load_demo_scene(&mut screen);
+	cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+	exportutils::write_svg(name, &screen, &[&cube], style)
+}
+
+// the demo scene as a hidden line pen plot, name is the output path without .hpgl
+fn hpgl_demo(name: &str) -> std::io::Result<()> {
+	let mut screen = Viewport::headless(320, 240, 120.0, Color::RGB(0.251, 0.263, 0.655)); // more pixels, finer visibility
+	let mut cube = load_demo_scene(&mut screen);
+	cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+	screen.draw_mesh(&cube); // fills the depth buffer the plot is checked against
+	exportutils::write_hpgl(name, &screen, &[&cube])
+}
+
+// the demo cube's wireframe spinning as ReGIS vectors, for terminals (or xterm -ti vt340) that can draw them
+fn regis_demo(frames: usize) {
+	let mut screen = Viewport::builder().display_mode(DisplayMode::Regis).build();
+	let mut cube = load_demo_scene(&mut screen);
+	for _ in 0..frames {
+		cube.transform(Transform::rotation(Vector3D::XYZ(0.3, 1.0, 0.1), 3.0));
+		screen.draw_wireframe(&cube);
+		screen.display();
+		screen.clear_screen();
+		thread::sleep(time::Duration::from_millis(50));
+	}
+}
+
+// unlit models (usually ones without a material file) get a plain clay material so previews have something to light
+fn use_clay_if_unlit(object: &mut Mesh) {
+	if matches!(object.material.mode, LightingMode::None) {
+		object.material = Material::builder().build();
+	}
+	// per face materials come from a material file, so they keep their colors and just get lit
+	for (material, _) in object.materials.iter_mut() {
+		if matches!(material.mode, LightingMode::None) { material.mode = LightingMode::Smooth; }
+	}
+}
+
+// a/b comparison of the demo scene: flat shaded without antialiasing on the left of the divider, smooth shaded with 4x
+// antialiasing on the right. h/l or the arrow keys move the divider, c switches to a crossfade where the divider
+// position is how much of the flat shaded side shows through, q quits
+// post effects (bloom, grain, dithering) come from the screen both halves are composited into, so they can't differ per side
+fn compare() -> std::io::Result<()> {
+	let (width, height) = (160, 120);
+	let mut screen = Viewport::builder().size(width, height).focal_length(120.0).detect_terminal().build();
+	let low = Quality::Low.settings();
+	let high = Quality::High.settings();
+	let mut sides: Vec<Viewport> = [
+		QualitySettings{ render_scale: 1.0, ..low },
+		QualitySettings{ bloom: None, dithering: Dithering::None, ..high }
+	].iter().map(|settings| {
+		let mut side = Viewport::headless(width, height, 120.0, Color::RGB(0.251, 0.263, 0.655));
+		side.set_quality(Quality::Custom(*settings));
+		side
+	}).collect();
+	let mut cube = load_demo_scene(&mut sides[0]);
+	sides[1].lights = sides[0].lights.clone();
+	cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+	for side in sides.iter_mut() { side.draw_frame(|side| side.draw_mesh(&cube)); }
+	
+	let _ = screen.backend.enter_raw_mode(); // without a tty keys still work, they just need enter
+	let (mut divider, mut transition) = (0.5, Transition::Wipe);
+	loop {
+		// the wipe shows the second view left of t, so the fast side is passed in second
+		screen.composite(&sides[1], &sides[0], transition, divider);
+		if transition == Transition::Wipe {
+			let x = (divider*width as f32).round();
+			screen.draw_line((x, 0.0), (x, height as f32 - 1.0), Color::RGB(1.0, 1.0, 1.0));
+		}
+		print!("\x1b[H");
+		screen.display();
+		
+		let mut key = [0u8; 3];
+		let read = std::io::stdin().read(&mut key)?;
+		match &key[..read] {
+			b"q" | [] => break,
+			b"h" | b"\x1b[D" => divider = (divider - 1.0/16.0).max(0.0),
+			b"l" | b"\x1b[C" => divider = (divider + 1.0/16.0).min(1.0),
+			b"c" => transition = if transition == Transition::Wipe { Transition::Crossfade }else { Transition::Wipe },
+			_ => ()
+		}
+	}
+	let _ = screen.backend.leave_raw_mode();
+	Ok(())
+}
+
+// material inspection: the camera orbits the model while a key light sweeps around it, in four quadrants showing
+// the full shading, diffuse only, specular only and normals
+fn inspect(assets: &mut AssetManager, filename: &str, frames: usize) -> std::io::Result<()> {
+	let mut object = load_object(assets, filename)?;
+	use_clay_if_unlit(&mut object);
+	let center = object.bounds().center();
+	let (width, height) = (160, 120);
+	let mut screen = Viewport::builder().size(width, height).focal_length(120.0).detect_terminal().build();
+	let mut views: Vec<Viewport> = [ShadingView::Full, ShadingView::Diffuse, ShadingView::Specular, ShadingView::Normals].iter().map(|view| {
+		let mut quadrant = Viewport::builder().size(width/2, height/2).focal_length(60.0).headless().studio(StudioRig::Neutral).build();
+		quadrant.shading_view = *view;
+		quadrant.frame(&object, 0.15);
+		quadrant
+	}).collect();
+	let distance = views[0].camera.position.sub(center).mag();
+	
+	for f in 0..frames {
+		let (orbit, sweep) = (f as f32*0.05, f as f32*0.13);
+		for (i, view) in views.iter_mut().enumerate() {
+			view.camera.position = center.add(Vector3D::XYZ(orbit.sin(), 0.35, orbit.cos()).normalize().mul(distance));
+			view.camera.look_at(center);
+			view.lights[0].position = Vector3D::XYZ(sweep.cos(), 0.8, sweep.sin());
+			view.clear_screen();
+			view.draw_mesh(&object);
+			screen.blit(view, (i % 2)*width/2, (i / 2)*height/2);
+		}
+		print!("\x1b[H"); // redraw in place
+		screen.display();
+		thread::sleep(time::Duration::from_millis(50));
+	}
+	Ok(())
+}
+
+// png previews of everything in the objects directories of assets' roots, the camera is pulled back until each model's
+// bounding sphere fits the view
+fn generate_thumbnails(assets: &AssetManager, size: usize, dir: &str) -> std::io::Result<()> {
+	let mut names = Vec::new();
+	for root in assets.roots.iter() {
+		let Ok(entries) = std::fs::read_dir(root.join("objects")) else { continue; };
+		for entry in entries {
+			let path = entry?.path();
+			if path.extension().is_some_and(|e| e == "obj") {
+				if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) { names.push(stem.to_string()); }
+	}}}
+	names.sort();
+	names.dedup(); // earlier roots win, like they do when loading
+	let objects = load_objects(assets, &names.iter().map(|n| n.as_str()).collect::<Vec<&str>>());
+	std::fs::create_dir_all(dir)?;
+	
+	for (name, object) in names.iter().zip(objects) {
+		let Ok(mut object) = object else { continue; };
+		if object.vertices.is_empty() { println!("skipping {name}: no geometry"); continue; }
+		use_clay_if_unlit(&mut object);
+		// three quarter view
+		object.origin = object.bounds().center();
+		object.transform(Transform::rotation(Vector3D::XYZ(0.0, -1.0, 0.0), 30.0));
+		
+		let mut screen = Viewport::headless(size, size, size as f32, Color::black());
+		screen.set_quality(viewport::Quality::High);
+		screen.set_studio(StudioRig::Neutral);
+		screen.frame(&object, 0.1);
+		screen.draw_frame(|screen| screen.draw_mesh(&object));
+		screen.screenshot(&format!("{dir}/{name}.png"))?;
+		println!("thumbnail written: {dir}/{name}.png");
+	}
+	Ok(())
+}
+
+
+fn main() {
+	if std::env::args().any(|arg| arg == "--fuzz") {
+		testutils::fuzz_render(2000, 1);
+		return;
+	}
+	let args: Vec<String> = std::env::args().collect();
+	let arg = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i+1)).cloned();
+	if args.iter().any(|a| a == "--math-tests") {
+		std::process::exit(if testutils::check_math() { 0 }else { 1 });
+	}
+	if args.iter().any(|a| a == "--clip-tests") {
+		let passed = testutils::check_clipping(5000, 1);
+		std::process::exit(if passed { 0 }else { 1 });
+	}
+	if args.iter().any(|a| a == "--golden") {
+		// --bless rewrites the reference images instead of checking against them
+		let passed = golden_test(&arg("--output").unwrap_or("golden".to_string()), args.iter().any(|a| a == "--bless")).unwrap();
+		std::process::exit(if passed { 0 }else { 1 });
+	}
+	// extra asset root searched after the working directory, for --inspect, --thumbnails and scenes. it can be a url
+	// with the http feature, --asset-cache is where files fetched from urls are kept
+	let mut assets = match arg("--assets") { Some(dir) => AssetManager::new().root(&dir), None => AssetManager::new() };
+	if let Some(dir) = arg("--asset-cache") { assets = assets.cache(&dir); }
+	if let Some(name) = arg("--inspect") {
+		inspect(&mut assets, &name, arg("--frames").and_then(|f| f.parse().ok()).unwrap_or(200)).unwrap();
+		return;
+	}
+	if let Some(target) = arg("--trace") {
+		trace_demo(&target, &arg("--output").unwrap_or("trace.txt".to_string())).unwrap();
+		return;
+	}
+	if let Some(style) = arg("--text-art") {
+		text_art(&style, &arg("--dither").unwrap_or("floyd-steinberg".to_string()), args.iter().any(|a| a == "--ink"), arg("--output")).unwrap();
+		return;
+	}
+	if let Some(style) = arg("--svg") {
+		svg_demo(&style, &arg("--output").unwrap_or("render".to_string())).unwrap();
+		return;
+	}
+	// value noise baked to a png for other tools, --scale is the number of noise cells across
+	if let Some(name) = arg("--bake-noise") {
+		let size = arg("--size").unwrap_or("256x256".to_string());
+		let (width, height) = size.split_once('x').map(|(w, h)| (w.parse().unwrap_or(256), h.parse().unwrap_or(256))).unwrap_or((256, 256));
+		let noise = ValueNoise{ scale: arg("--scale").and_then(|s| s.parse().ok()).unwrap_or(8.0), seed: arg("--seed").and_then(|s| s.parse().ok()).unwrap_or(1), low: Color::black(), high: Color::RGB(1.0, 1.0, 1.0) };
+		exportutils::write_texture_png(&name, &Texture::bake(&noise, width, height)).unwrap();
+		return;
+	}
+	if args.iter().any(|a| a == "--hpgl") {
+		hpgl_demo(&arg("--output").unwrap_or("render".to_string())).unwrap();
+		return;
+	}
+	if args.iter().any(|a| a == "--regis") {
+		regis_demo(arg("--frames").and_then(|f| f.parse().ok()).unwrap_or(100));
+		return;
+	}
+	if args.iter().any(|a| a == "--compare") {
+		compare().unwrap();
+		return;
+	}
+	if args.iter().any(|a| a == "--thumbnails") {
+		let size = arg("--size").and_then(|s| s.parse().ok()).unwrap_or(256);
+		generate_thumbnails(&assets, size, &arg("--output").unwrap_or("thumbnails".to_string())).unwrap();
+		return;
+	}
+	if let (Some(path), Some(count)) = (arg("--scene"), arg("--export-frames")) {
+		let fps = arg("--fps").and_then(|f| f.parse().ok()).unwrap_or(scene::SCENE_FPS);
+		export_scene(&mut assets, &path, count.parse().unwrap_or(1), fps, &arg("--output").unwrap_or("frames".to_string()), &arg("--format").unwrap_or("png".to_string())).unwrap();
+		return;
+	}
+	if let (Some(path), Some(output)) = (arg("--scene"), arg("--save")) {
+		let frame = arg("--frame").and_then(|f| f.parse().ok()).unwrap_or(0.0);
+		scene::Scene::from_file(&path, &mut assets).and_then(|scene| scene.save(&output, frame)).unwrap();
+		return;
+	}
+	// the scene posed at --frame as a .gltf or .glb, for opening in blender and the like
+	if let (Some(path), Some(output)) = (arg("--scene"), arg("--gltf")) {
+		let frame = arg("--frame").and_then(|f| f.parse().ok()).unwrap_or(0.0);
+		let mut scene = scene::Scene::from_file(&path, &mut assets).unwrap();
+		scene.pose(frame);
+		exportutils::export_gltf(&scene, &output).unwrap();
+		return;
+	}
+	// any mesh file (name.obj, .stl, .ply or .off) stored as objects/name.mesh, which loads without parsing text
+	if let Some(name) = arg("--convert") {
+		let mesh = load_mesh_file(&mut assets, &name).unwrap();
+		exportutils::write_mesh_binary(name.rsplit_once('.').map_or(name.as_str(), |(stem, _)| stem), &mesh).unwrap();
+		return;
+	}
+	// a mesh file written back out as obj, with an mtl carrying its material (illum included) and the texture as ppm.
+	// meshes without texture coordinates (stl and the like) get the obj only. --output is the name without extension
+	if let Some(name) = arg("--export-obj") {
+		let mesh = load_mesh_file(&mut assets, &name).unwrap();
+		let output = arg("--output").unwrap_or(format!("{}_export", name.rsplit_once('.').map_or(name.as_str(), |(stem, _)| stem)));
+		let textured = !mesh.triangles.is_empty() && mesh.tex_tris.len() == mesh.triangles.len();
+		exportutils::write_object(output, mesh, textured).unwrap();
+		return;
+	}
+	// every mesh of the scene posed at --frame as stl, binary unless --ascii. scenes with several meshes get one file
+	// each, numbered before the extension
+	if let (Some(path), Some(output)) = (arg("--scene"), arg("--stl")) {
+		let frame = arg("--frame").and_then(|f| f.parse().ok()).unwrap_or(0.0);
+		let format = if args.iter().any(|a| a == "--ascii") { exportutils::StlFormat::Ascii }else { exportutils::StlFormat::Binary };
+		let mut scene = scene::Scene::from_file(&path, &mut assets).unwrap();
+		scene.pose(frame);
+		for (i, mesh) in scene.meshes.iter().enumerate() {
+			let output = if scene.meshes.len() == 1 { output.clone() }else { format!("{}_{i}.stl", output.trim_end_matches(".stl")) };
+			exportutils::write_stl(mesh, &output, format).unwrap();
+		}
+		return;
+	}
+	if let Some(path) = arg("--scene") {
+		let frame = arg("--frame").and_then(|f| f.parse().ok()).unwrap_or(0);
+		scene_demo(&mut assets, &path, arg("--frames").and_then(|f| f.parse().ok()).unwrap_or(100), frame, arg("--output"), arg("--budget").and_then(|b| b.parse().ok()), arg("--record"), args.iter().any(|a| a == "--paused")).unwrap();
+		return;
+	}
+	if let Some(count) = arg("--export-frames") {
+		let size = arg("--size").unwrap_or("640x480".to_string());
+		let (width, height) = size.split_once('x').map(|(w, h)| (w.parse().unwrap_or(640), h.parse().unwrap_or(480))).unwrap_or((640, 480));
+		let format = arg("--format").unwrap_or("png".to_string());
+		let fps = arg("--fps").and_then(|f| f.parse().ok()).unwrap_or(scene::SCENE_FPS);
+		export_frames(count.parse().unwrap_or(1), fps, width, height, &arg("--output").unwrap_or("frames".to_string()), &format).unwrap();
+		return;
+	}
+	
+    let mut screen = Viewport::builder().focal_length(120.0).background(Color::RGB(0.251, 0.263, 0.655)).detect_terminal().build(); //64, 67, 167
+	if args.iter().any(|a| a == "--fit") { screen.fit_terminal(); }
+	let mut cube = load_demo_scene(&mut screen);
 	
 	cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.6, -0.01), Vector3D::XYZ(1.0, -0.5, 0.3)));
+	screen.frame(&cube, 0.1);
 	screen.draw_mesh(&cube);
 	screen.display();
 
-	for i in 0..2 {
+	for _ in 0..2 {
 		cube.transform(Transform::Rotate(Vector3D::XYZ(1.0, 0.01, -0.01), Vector3D::XYZ(1.0, 0.02, 0.0)));
 		//cube2.transform(Transform::Rotate(Vector3D::XYZ(0.02, -1.02, 0.01), Vector3D::XYZ(0.0, 1.02, 0.0)));
 		
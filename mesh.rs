@@ -1,12 +1,105 @@
-use crate::{ Triangle, Vector3D, Point2D };
-use crate::graphicsutils::{ Texture, Material };
+use crate::{ Triangle, Vector3D, Point2D, Color };
+use crate::graphicsutils::{ Texture, Material, LightingCache, FragmentShader, VertexShader, VertexInput };
 
+use std::borrow::Cow;
+use std::cmp::min;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// axis aligned bounding box
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+	pub min: Vector3D,
+	pub max: Vector3D
+}
+
+impl Aabb {
+	// the box around every finite point, None when there aren't any
+	pub fn around(points: impl IntoIterator<Item = Vector3D>) -> Option<Aabb> {
+		let mut points = points.into_iter().filter(|p| p.is_finite());
+		let first = points.next()?;
+		Some(points.fold(Aabb{ min: first, max: first }, |b, p| Aabb{
+			min: Vector3D::XYZ(b.min.X.min(p.X), b.min.Y.min(p.Y), b.min.Z.min(p.Z)),
+			max: Vector3D::XYZ(b.max.X.max(p.X), b.max.Y.max(p.Y), b.max.Z.max(p.Z))
+		}))
+	}
+	
+	pub fn center(&self) -> Vector3D { self.min.add(self.max).mul(0.5) }
+	
+	// size along each axis
+	pub fn extent(&self) -> Vector3D { self.max.sub(self.min) }
+	
+	// radius of the sphere through the corners
+	pub fn radius(&self) -> f32 { self.extent().mag()*0.5 }
+	
+	// grown by epsilon on every side, so flat meshes (a plane, a single triangle) still enclose some volume
+	pub fn padded(&self, epsilon: f32) -> Aabb {
+		let pad = Vector3D::XYZ(epsilon, epsilon, epsilon);
+		Aabb{ min: self.min.sub(pad), max: self.max.add(pad) }
+	}
+	
+	pub fn contains(&self, p: Vector3D) -> bool {
+		p.X >= self.min.X && p.Y >= self.min.Y && p.Z >= self.min.Z && p.X <= self.max.X && p.Y <= self.max.Y && p.Z <= self.max.Z
+	}
+}
+
+impl From<&Mesh> for Aabb {
+	fn from(mesh: &Mesh) -> Aabb { mesh.bounds() }
+}
+
+// one triangle's data gathered from the parallel arrays, see Mesh::faces
+#[derive(Copy, Clone, Debug)]
+pub struct Face {
+	pub index: usize, // position in mesh.triangles
+	pub vertices: Triangle, // indices into mesh.vertices
+	pub positions: [Vector3D; 3],
+	pub tex_coords: [Point2D; 3],
+	pub normals: [Vector3D; 3], // corner normals, see Mesh::corner_normals
+	pub face_normal: Vector3D,
+	pub material: Option<usize> // index into mesh.materials, None for faces using mesh.material
+}
+
+#[derive(Copy, Clone, Debug)]
 pub enum Transform {
 	Scale(Vector3D),
 	Translate(Vector3D),
 	Rotate(Vector3D, Vector3D)
 }
 
+impl Transform {
+	// Rotate by an angle around an axis: the two reflection vectors are perpendicular to the axis and half the angle apart
+	pub fn rotation(axis: Vector3D, degrees: f32) -> Transform {
+		let axis = axis.normalize();
+		let helper = if axis.X.abs() < 0.9 { Vector3D::XYZ(1.0, 0.0, 0.0) }else { Vector3D::XYZ(0.0, 1.0, 0.0) };
+		let a = helper.sub(axis.mul(helper.dot(axis))).normalize();
+		let half = (degrees*0.5).to_radians();
+		let b = a.mul(half.cos()).add(axis.cross(a).mul(half.sin()));
+		Transform::Rotate(a, b)
+	}
+
+	// amount of the way there, for posing between two frames: applying t of a transform n times is like applying it
+	// t*n times. rotations turn part of their angle around the same axis, scales grow geometrically
+	pub fn scaled(&self, amount: f32) -> Transform {
+		match self {
+			Transform::Scale(s) => Transform::Scale(Vector3D::XYZ(s.X.abs().powf(amount).copysign(s.X), s.Y.abs().powf(amount).copysign(s.Y), s.Z.abs().powf(amount).copysign(s.Z))),
+			Transform::Translate(v) => Transform::Translate(v.mul(amount)),
+			Transform::Rotate(a, _) => match self.axis_angle() {
+				Some((axis, degrees)) => Transform::rotation(axis, degrees*amount),
+				None => Transform::Rotate(*a, *a) // parallel reflections, nothing turns
+			}
+		}
+	}
+	
+	// the unit axis and degrees a Rotate turns by, the inverse of rotation. None for other transforms and for
+	// reflections too close to parallel to have an axis
+	pub fn axis_angle(&self) -> Option<(Vector3D, f32)> {
+		let Transform::Rotate(a, b) = self else { return None; };
+		let axis = a.cross(*b);
+		if axis.mag() < 1e-9 { return None; }
+		Some((axis.normalize(), 2.0*axis.mag().atan2(a.dot(*b)).to_degrees()))
+	}
+}
+
 #[derive(Clone)]
 pub struct Mesh {
 	pub vertices: Vec<Vector3D>,
@@ -17,10 +110,24 @@ pub struct Mesh {
 
 	pub face_normals: Vec<Vector3D>,
 	pub vertex_normals: Vec<Vector3D>,
+	// authored normals (obj vn data), normal_tris indexes them per corner like tex_tris does tex_coords so faces meeting
+	// at a hard edge can keep different normals. triangles without an entry use vertex_normals
+	pub normals: Vec<Vector3D>,
+	pub normal_tris: Vec<Triangle>,
+	pub vertex_colors: Vec<Color>, // multiplied into the texture color, empty for meshes without per vertex colors
 
 	pub texture: Texture,
 	pub material: Material,
-	pub origin: Vector3D
+	// per face materials for models that switch with usemtl. face_materials holds an index into materials for every
+	// triangle, faces without one (or past the end of materials) use material and texture above
+	pub materials: Vec<(Material, Texture)>,
+	pub face_materials: Vec<usize>,
+	pub shader: Option<Arc<dyn FragmentShader>>, // replaces the built in lighting for every face when set
+	pub vertex_shader: Option<Arc<dyn VertexShader>>, // moves vertices where they're drawn, see shaded_vertices
+	pub origin: Vector3D,
+	
+	pub revision: u64, // bumped by every edit that changes shading, call touch() after editing the fields directly
+	pub lighting_cache: Option<LightingCache>
 }
 
 impl Mesh {
@@ -31,13 +138,22 @@ impl Mesh {
 			
 			vertex_normals: vec![Vector3D::zero(); vertices.len()],
 			face_normals: vec![Vector3D::zero(); triangles.len()],
+			normals: Vec::new(),
+			normal_tris: Vec::new(),
+			vertex_colors: Vec::new(),
 			
 			vertices,
 			triangles,
 
 			texture: Texture::missing(10, 10, 2),
 			material: Material::missing(),
+			materials: Vec::new(),
+			face_materials: Vec::new(),
+			shader: None,
+			vertex_shader: None,
 			origin: Vector3D::zero(),
+			revision: 0,
+			lighting_cache: None
 		}
 	}
 	
@@ -49,36 +165,140 @@ impl Mesh {
 			tex_tris: Vec::new(),
 			face_normals: Vec::new(),
 			vertex_normals: Vec::new(),
+			normals: Vec::new(),
+			normal_tris: Vec::new(),
+			vertex_colors: Vec::new(),
 			origin: Vector3D::zero(),
 			texture: Texture::missing(10, 10, 1),
 			material: Material::missing(),
+			materials: Vec::new(),
+			face_materials: Vec::new(),
+			shader: None,
+			vertex_shader: None,
+			revision: 0,
+			lighting_cache: None
 		}
 	}
 	
-	pub fn center(&self) -> Vector3D {
-		let mut center = Vector3D::zero();
-		// find center of mesh bounding box
-		let (mut x_min, mut x_max) = (999.0, -999.0);
-		let (mut y_min, mut y_max) = (999.0, -999.0);
-		let (mut z_min, mut z_max) = (999.0, -999.0);
-		for v in self.vertices.iter() {
-			if x_min > v.X { x_min = v.X; }
-			if x_max < v.X { x_max = v.X; }
-			if y_min > v.Y { y_min = v.Y; }
-			if y_max < v.Y { y_max = v.Y; }
-			if z_min > v.Z { z_min = v.Z; }
-			if z_max < v.Z { z_max = v.Z; }
+	// midpoint of the bounding box, where transforms pivot after loading
+	pub fn center(&self) -> Vector3D { self.bounds().center() }
+	
+	// non finite vertices are left out, meshes without any finite vertex give a zero size box at the origin
+	pub fn bounds(&self) -> Aabb {
+		Aabb::around(self.vertices.iter().copied()).unwrap_or(Aabb{ min: Vector3D::zero(), max: Vector3D::zero() })
+	}
+	
+	// where the vertices are drawn at time (in seconds): run through vertex_shader, or vertices as they are without one
+	pub fn shaded_vertices(&self, time: f32) -> Cow<'_, [Vector3D]> {
+		let Some(shader) = &self.vertex_shader else { return Cow::Borrowed(&self.vertices); };
+		Cow::Owned(self.vertices.iter().enumerate().map(|(index, &position)| {
+			let normal = self.vertex_normals.get(index).copied().unwrap_or(Vector3D::zero());
+			shader.displace(&VertexInput{ position, normal, index, time })
+		}).collect())
+	}
+	
+	// the mesh as drawn at time: itself without a vertex shader, otherwise a copy with the displaced vertices and normals
+	// recalculated from them. the copy drops authored per corner normals and the lighting cache, both belong to the
+	// surface before it moved
+	pub fn shaded(&self, time: f32) -> Cow<'_, Mesh> {
+		if self.vertex_shader.is_none() { return Cow::Borrowed(self); }
+		let mut mesh = self.clone();
+		(mesh.vertices, mesh.vertex_shader, mesh.lighting_cache) = (self.shaded_vertices(time).into_owned(), None, None);
+		(mesh.normals, mesh.normal_tris) = (Vec::new(), Vec::new());
+		if mesh.triangles.iter().all(|&(t1, t2, t3)| t1 < mesh.vertices.len() && t2 < mesh.vertices.len() && t3 < mesh.vertices.len()) {
+			(mesh.vertex_normals, mesh.face_normals) = (vec![Vector3D::zero(); mesh.vertices.len()], vec![Vector3D::zero(); mesh.triangles.len()]);
+			mesh.recalculate_normals();
+		}
+		Cow::Owned(mesh)
+	}
+	
+	pub fn touch(&mut self) { self.revision += 1; }
+	
+	pub fn face_material(&self, t: usize) -> (&Material, &Texture) {
+		match self.face_materials.get(t).and_then(|m| self.materials.get(*m)) {
+			Some((material, texture)) => (material, texture),
+			None => (&self.material, &self.texture)
+		}
+	}
+	
+	// the normals interpolated across triangle t, out of range normal_tris entries fall back to vertex_normals too
+	pub fn corner_normals(&self, t: usize) -> [Vector3D; 3] {
+		match self.normal_tris.get(t) {
+			Some(&(n1, n2, n3)) if n1 < self.normals.len() && n2 < self.normals.len() && n3 < self.normals.len() => [self.normals[n1], self.normals[n2], self.normals[n3]],
+			_ => {
+				let (t1, t2, t3) = self.triangles[t];
+				[self.vertex_normals[t1], self.vertex_normals[t2], self.vertex_normals[t3]]
+			}
 		}
-		Vector3D::XYZ((x_max+x_min)/2.0, (y_max+y_min)/2.0, (z_max+z_min)/2.0)
+	}
+	
+	// a new mesh with only the listed triangles and the vertices and texture coordinates they use, sharing the
+	// material and texture. out of range triangles are left out
+	pub fn submesh(&self, triangles: &[usize]) -> Mesh {
+		let mut mesh = Mesh::new(Vec::new(), Vec::new());
+		let (mut vertex_map, mut uv_map) = (vec![None; self.vertices.len()], vec![None; self.tex_coords.len()]);
+		let mut normal_map = vec![None; self.normals.len()];
+		for &t in triangles.iter() {
+			if !self.triangle_in_range(t) { continue; }
+			let (t1, t2, t3) = self.triangles[t];
+			let (uv1, uv2, uv3) = self.tex_tris[t];
+			let mut vertex = |v: usize| *vertex_map[v].get_or_insert_with(|| {
+				mesh.vertices.push(self.vertices[v]);
+				mesh.vertex_normals.push(self.vertex_normals[v]);
+				if let Some(color) = self.vertex_colors.get(v) { mesh.vertex_colors.push(*color); }
+				mesh.vertices.len() - 1
+			});
+			let triangle = (vertex(t1), vertex(t2), vertex(t3));
+			let mut uv = |i: usize| *uv_map[i].get_or_insert_with(|| { mesh.tex_coords.push(self.tex_coords[i]); mesh.tex_coords.len() - 1 });
+			let tex_tri = (uv(uv1), uv(uv2), uv(uv3));
+			mesh.triangles.push(triangle);
+			mesh.tex_tris.push(tex_tri);
+			mesh.face_normals.push(self.face_normals[t]);
+			if let Some(material) = self.face_materials.get(t) { mesh.face_materials.push(*material); }
+			if let Some(&(n1, n2, n3)) = self.normal_tris.get(t).filter(|n| n.0 < self.normals.len() && n.1 < self.normals.len() && n.2 < self.normals.len()) {
+				let mut normal = |i: usize| *normal_map[i].get_or_insert_with(|| { mesh.normals.push(self.normals[i]); mesh.normals.len() - 1 });
+				let normal_tri = (normal(n1), normal(n2), normal(n3));
+				mesh.normal_tris.push(normal_tri);
+			}
+		}
+		if mesh.vertex_colors.len() != mesh.vertices.len() { mesh.vertex_colors.clear(); }
+		if mesh.face_materials.len() != mesh.triangles.len() { mesh.face_materials.clear(); }
+		if mesh.normal_tris.len() != mesh.triangles.len() { (mesh.normals, mesh.normal_tris) = (Vec::new(), Vec::new()); }
+		mesh.texture = self.texture.clone();
+		mesh.material = self.material.clone();
+		mesh.materials = self.materials.clone();
+		mesh.shader = self.shader.clone();
+		mesh.vertex_shader = self.vertex_shader.clone();
+		mesh.origin = mesh.center();
+		mesh
+	}
+	
+	// every triangle whose indices are all in range, broken ones are skipped like draw_mesh skips them
+	pub fn faces(&self) -> impl Iterator<Item = Face> + '_ {
+		(0..self.triangles.len()).filter(|t| self.triangle_in_range(*t)).map(|t| {
+			let (t1, t2, t3) = self.triangles[t];
+			let (uv1, uv2, uv3) = self.tex_tris[t];
+			Face {
+				index: t,
+				vertices: (t1, t2, t3),
+				positions: [self.vertices[t1], self.vertices[t2], self.vertices[t3]],
+				tex_coords: [self.tex_coords[uv1], self.tex_coords[uv2], self.tex_coords[uv3]],
+				normals: self.corner_normals(t),
+				face_normal: self.face_normals[t],
+				material: self.face_materials.get(t).copied().filter(|m| *m < self.materials.len())
+			}
+		})
 	}
 	
 	pub fn transform(&mut self, action: Transform) {
+		self.touch();
 		self.vertices = match action {
 			// rotatation using double reflection
 			Transform::Rotate(a, b) => {
 				// rotate normals so they don't need to be recalculted each frame
 				self.face_normals = self.face_normals.iter().map(|f| f.reflect(a).reflect(b)).collect();
 				self.vertex_normals = self.vertex_normals.iter().map(|v| v.reflect(a).reflect(b)).collect();
+				self.normals = self.normals.iter().map(|n| n.reflect(a).reflect(b)).collect();
 				self.vertices.iter().map(|v| v.sub(self.origin).reflect(a).reflect(b).add(self.origin)).collect()
 			},
 			Transform::Translate(vec) => {
@@ -89,7 +309,18 @@ impl Mesh {
 		};
 	}
 	
+	// whether every index triangle t uses points at existing data, malformed meshes are skipped instead of panicking
+	pub fn triangle_in_range(&self, t: usize) -> bool {
+		if t >= self.triangles.len() || t >= self.tex_tris.len() || t >= self.face_normals.len() { return false; }
+		let (t1, t2, t3) = self.triangles[t];
+		let (uv1, uv2, uv3) = self.tex_tris[t];
+		let max_vertex = min(self.vertices.len(), self.vertex_normals.len());
+		t1 < max_vertex && t2 < max_vertex && t3 < max_vertex &&
+			uv1 < self.tex_coords.len() && uv2 < self.tex_coords.len() && uv3 < self.tex_coords.len()
+	}
+	
 	pub fn recalculate_normals(&mut self) {
+		self.touch();
 		for t in 0..self.triangles.len() {
 			let (t1, t2, t3) = self.triangles[t];
 			let (p1, p2, p3) = (self.vertices[t1], self.vertices[t2], self.vertices[t3]);
@@ -104,4 +335,67 @@ impl Mesh {
 		}
 		for v in 0..self.vertices.len() { self.vertex_normals[v] = self.vertex_normals[v].normalize(); }
 	}
+	
+	// obj smoothing groups, one per triangle: corners are only averaged with faces in the same group, so a vertex on
+	// the border between groups gets a normal for each side. group 0 (s off) is faceted. fills normals and normal_tris
+	pub fn recalculate_grouped_normals(&mut self, smoothing_groups: &[u32]) {
+		self.recalculate_normals();
+		let (mut normals, mut normal_tris) = (Vec::new(), Vec::new());
+		let mut corner_normal: HashMap<(usize, u32), usize> = HashMap::new();
+		for t in 0..self.triangles.len() {
+			let (t1, t2, t3) = self.triangles[t];
+			let group = smoothing_groups.get(t).copied().unwrap_or(0);
+			if group == 0 {
+				normals.push(self.face_normals[t]);
+				normal_tris.push((normals.len() - 1, normals.len() - 1, normals.len() - 1));
+				continue;
+			}
+			let mut corner = |v: usize| {
+				let n = *corner_normal.entry((v, group)).or_insert_with(|| { normals.push(Vector3D::zero()); normals.len() - 1 });
+				normals[n] = normals[n].add(self.face_normals[t]);
+				n
+			};
+			let normal_tri = (corner(t1), corner(t2), corner(t3));
+			normal_tris.push(normal_tri);
+		}
+		self.normals = normals.iter().map(|n| n.normalize()).collect();
+		self.normal_tris = normal_tris;
+	}
+}
+
+// split a polygon (corners in order, either winding) into triangles of corner indices with the same winding
+// ear clipping so concave faces come out right, falls back to a fan if the polygon is too broken to find ears in
+pub fn triangulate(polygon: &[Vector3D]) -> Vec<Triangle> {
+	let n = polygon.len();
+	if n < 3 { return Vec::new(); }
+	let fan = || (2..n).map(|k| (0, k-1, k)).collect();
+	if n == 3 { return fan(); }
+	// newell's method, works for non planar and concave polygons
+	let mut normal = Vector3D::zero();
+	for i in 0..n {
+		let (a, b) = (polygon[i], polygon[(i+1) % n]);
+		normal = normal.add(Vector3D::XYZ((a.Y - b.Y)*(a.Z + b.Z), (a.Z - b.Z)*(a.X + b.X), (a.X - b.X)*(a.Y + b.Y)));
+	}
+	if normal.mag() == 0.0 || !normal.is_finite() { return fan(); }
+	
+	let inside = |p: Vector3D, a: Vector3D, b: Vector3D, c: Vector3D| {
+		let side = |from: Vector3D, to: Vector3D| to.sub(from).cross(p.sub(from)).dot(normal) >= 0.0;
+		side(a, b) && side(b, c) && side(c, a)
+	};
+	let mut remaining: Vec<usize> = (0..n).collect();
+	let mut triangles = Vec::new();
+	while remaining.len() > 3 {
+		let count = remaining.len();
+		let ear = (0..count).find(|&i| {
+			let (prev, cur, next) = (remaining[(i + count - 1) % count], remaining[i], remaining[(i+1) % count]);
+			let (a, b, c) = (polygon[prev], polygon[cur], polygon[next]);
+			let convex = b.sub(a).cross(c.sub(b)).dot(normal) > 0.0;
+			convex && !remaining.iter().any(|&v| v != prev && v != cur && v != next && inside(polygon[v], a, b, c))
+		});
+		let Some(i) = ear else { return fan(); };
+		triangles.push((remaining[(i + count - 1) % count], remaining[i], remaining[(i+1) % count]));
+		remaining.remove(i);
+	}
+	triangles.push((remaining[0], remaining[1], remaining[2]));
+	triangles
 }
@@ -0,0 +1,48 @@
+use std::io::{ Read, Write };
+use std::net::TcpStream;
+use std::time::Duration;
+
+// plain http only, https would need a tls library
+fn split_url(url: &str) -> std::io::Result<(String, u16, String)> {
+	let Some(rest) = url.strip_prefix("http://") else {
+		return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, format!("{url}: only http:// urls can be fetched")));
+	};
+	let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+	let (host, port) = match authority.rsplit_once(':') {
+		Some((host, port)) => (host, port.parse().map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{url}: bad port")))?),
+		None => (authority, 80)
+	};
+	Ok((host.to_string(), port, if path.is_empty() { "/".to_string() }else { path.to_string() }))
+}
+
+// the body of a GET request, None when the server doesn't have the file (404 or 410). redirects are followed a few
+// times. asks for http/1.0 so the reply is never chunked and ends when the connection closes
+pub fn http_get(url: &str) -> std::io::Result<Option<Vec<u8>>> {
+	let mut url = url.to_string();
+	for _ in 0..5 {
+		let (host, port, path) = split_url(&url)?;
+		let mut stream = TcpStream::connect((host.as_str(), port))?;
+		stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+		stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+		write!(stream, "GET {path} HTTP/1.0\r\nHost: {host}\r\nUser-Agent: CLI-rendering-engine\r\nConnection: close\r\n\r\n")?;
+		let mut response = Vec::new();
+		stream.read_to_end(&mut response)?;
+
+		let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{url}: {message}"));
+		let end = response.windows(4).position(|w| w == b"\r\n\r\n").ok_or(invalid("reply has no end of headers"))?;
+		let headers = String::from_utf8_lossy(&response[..end]).to_string();
+		let status: u16 = headers.split_whitespace().nth(1).and_then(|s| s.parse().ok()).ok_or(invalid("reply has no status"))?;
+		match status {
+			200 => return Ok(Some(response[end + 4..].to_vec())),
+			404 | 410 => return Ok(None),
+			301 | 302 | 303 | 307 | 308 => {
+				let location = headers.lines().find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("location")).map(|(_, value)| value.trim().to_string()));
+				let location = location.ok_or(invalid("redirect without a location"))?;
+				// relative redirects stay on the same server
+				url = if location.starts_with('/') { format!("http://{host}:{port}{location}") }else { location };
+			},
+			_ => return Err(invalid(&format!("server replied {}", headers.lines().next().unwrap_or(""))))
+		}
+	}
+	Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{url}: too many redirects")))
+}
@@ -0,0 +1,570 @@
+use crate::{ Vector3D, Color };
+use crate::{ AssetManager, load_concurrently, load_mesh_file, load_object_groups };
+use crate::mesh::{ Mesh, Transform, Aabb };
+use crate::viewport::{ Viewport, Rasterizer, Visibility, Quality, Overrun, ShadingWeights, PlanarShadow };
+use crate::graphicsutils::{ LightSource, AmbientLight, SpecularModel, MaterialBuilder, LightingMode, StudioRig, SineWave, Environment };
+use crate::camera::Camera;
+use crate::displayutils::{ ColorMode, ColorFilter, ToneMapping, PHOSPHOR_GREEN, PHOSPHOR_AMBER, AsciiShader, EdgeShader, detect_terminal };
+
+use std::sync::Arc;
+use std::time::Duration;
+
+// scene files are a small subset of toml, everything the demos otherwise set up in code:
+//
+//   [viewport]       size = [w, h], fov or focal_length, background, quality ("low", "medium", "high"),
+//                    studio ("neutral", "high-key", "low-key"), cells ("ascii", "edges"), colors ("truecolor", "256"),
+//                    rasterizer ("float", "fixed-point"), visibility ("depth-buffer", "painter"), headless,
+//                    cell_size = [w, h] of the terminal font so pixels come out square, filter ("grayscale", or
+//                    monochrome in "green", "amber" or a [r, g, b] tint), tone_mapping ("clamp", "reinhard",
+//                    "aces") and exposure for lights brighter than 1, contrast, white_balance (kelvin, 6500 is
+//                    neutral), near (distance meshes get clipped at), cache_lighting = true lights meshes that don't
+//                    spin once per vertex instead of every frame (smooth meshes then highlight like gouraud),
+//                    weights = [ambient, diffuse, specular] for every material (0.2, 0.4, 0.6 by default),
+//                    budget (milliseconds per frame) and overrun ("wireframe" outlines what's left once a frame goes
+//                    over it, "partial" stops drawing, wireframe by default),
+//                    panorama (textures/name, equirectangular) or cubemap (textures/name_px, _nx, _py, _ny, _pz, _nz)
+//   [camera]         position, look_at. without one the camera looks down -z from wherever frames every mesh
+//   [ambient]        color, intensity, environment = true lights it by the panorama or cubemap instead of evenly
+//   [[light]]        color, position, intensity (1 unless set, scales its diffuse and specular), range (only meshes
+//                    this close to position are lit by it), enabled = false to switch it off. one table per light
+//   [shadow]         every mesh casts a flat shadow onto a plane: point and normal of the plane (the ground, y = 0,
+//                    unless set), light (direction towards it, the first light's position without one) and darkness
+//                    (0.5 halves what it lands on)
+//   [[mesh]]         file (objects/name.obj, or name.stl/.ply/.off/.mesh, or a url), texture (textures/name), then
+//                    translate, scale and rotate = [axis x, y, z, degrees] applied in the order they're written.
+//                    spin = [axis x, y, z, degrees] turns the mesh every frame, wave = [amplitude, wavelength, speed]
+//                    ripples it along world x. group = "name" takes only that o/g group of an obj file
+//   [mesh.material]  mode ("flat", "smooth", "gouraud", "toon", "unlit"), bands (toon shading with that many diffuse
+//                    steps, 3 unless set), ambient, diffuse, specular, emissive (added whatever the lights do),
+//                    highlights, opacity, specular_model ("phong", "blinn-phong"), metallic and roughness (0 to 1,
+//                    either one shades it with the metallic-roughness brdf instead of phong), for the last mesh
+//
+// assets = "dir or url" before the first table adds an asset root, and can be repeated
+//
+// values are numbers, "strings", true/false and [lists, of, numbers]. # starts a comment
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+	Number(f32),
+	Text(String),
+	Bool(bool),
+	List(Vec<f32>)
+}
+
+impl std::fmt::Display for Value {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Value::Number(n) => write!(f, "{n}"),
+			Value::Text(t) => write!(f, "\"{t}\""),
+			Value::Bool(b) => write!(f, "{b}"),
+			Value::List(l) => write!(f, "[{}]", l.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(", "))
+		}
+	}
+}
+
+fn list(values: &[f32]) -> Value { Value::List(values.to_vec()) }
+
+// a [table] or [[table]] with its entries in file order and the line numbers errors point at
+struct Section {
+	name: String,
+	line: usize,
+	entries: Vec<(String, Value, usize)>
+}
+
+fn scene_error(path: &str, line: usize, message: String) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{path}:{line}: {message}"))
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+	if let Some(text) = text.strip_prefix('"') { return text.strip_suffix('"').map(|t| Value::Text(t.to_string())); }
+	if let Some(list) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+		return list.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()).map(|n| n.parse::<f32>().ok()).collect::<Option<Vec<f32>>>().map(Value::List);
+	}
+	match text {
+		"true" => Some(Value::Bool(true)),
+		"false" => Some(Value::Bool(false)),
+		_ => text.parse::<f32>().ok().map(Value::Number)
+	}
+}
+
+// entries before the first table go in one named ""
+fn parse_sections(path: &str, text: &str) -> std::io::Result<Vec<Section>> {
+	let mut sections = vec![Section{ name: String::new(), line: 0, entries: Vec::new() }];
+	for (i, line) in text.lines().enumerate() {
+		// strings can't contain # in this subset, so comments can be cut off anywhere
+		let line = line.split('#').next().unwrap_or("").trim();
+		if line.is_empty() { continue; }
+		if let Some(name) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")).or(line.strip_prefix('[').and_then(|l| l.strip_suffix(']'))) {
+			sections.push(Section{ name: name.trim().to_string(), line: i+1, entries: Vec::new() });
+			continue;
+		}
+		let Some((key, value)) = line.split_once('=') else { return Err(scene_error(path, i+1, format!("expected key = value or a [table], found {line:?}"))); };
+		let value = parse_value(value.trim()).ok_or(scene_error(path, i+1, format!("can't read the value of {}", key.trim())))?;
+		sections.last_mut().unwrap().entries.push((key.trim().to_string(), value, i+1));
+	}
+	Ok(sections)
+}
+
+// frames per second animations are authored at, spin is per frame at this rate
+pub const SCENE_FPS: f32 = 20.0;
+
+// where a scene mesh came from and what was done to it since, what gets written back when saving
+pub struct MeshSource {
+	pub file: String,
+	pub group: Option<String>, // o/g name in an obj file, the mesh is only that part of it
+	pub texture: Option<String>,
+	pub transforms: Vec<Transform>, // in the order they were applied
+	pub wave: Option<SineWave>
+}
+
+pub struct Scene {
+	pub viewport: Viewport,
+	pub meshes: Vec<Mesh>,
+	pub sources: Vec<MeshSource>, // one per mesh
+	pub spin: Vec<Option<(Vector3D, f32)>>, // per mesh, axis and degrees per frame
+	pub near: Option<f32>, // meshes are clipped this far in front of the camera
+	pub shadow: Option<PlanarShadow>, // every mesh casts one onto this plane
+	cache_lighting: bool, // meshes that don't spin are lit per vertex once, see Viewport::cache_lighting
+	settings: Vec<(String, Value)>, // the [viewport] table as read, saved back as is
+	roots: Vec<String>, // assets entries
+	rest: Vec<Mesh>, // meshes as the file set them up, pose starts over from these
+	frame: f32 // of the last pose
+}
+
+impl Scene {
+	pub fn from_file(path: &str, assets: &mut AssetManager) -> std::io::Result<Scene> {
+		let text = std::fs::read_to_string(path)?;
+		Scene::from_str(path, &text, assets)
+	}
+
+	// path only shows up in error messages, which are InvalidData errors pointing at the offending line
+	pub fn from_str(path: &str, text: &str, assets: &mut AssetManager) -> std::io::Result<Scene> {
+		let mut viewport = Viewport::builder();
+		let mut camera: Option<Camera> = None;
+		let (mut near, mut shadow, mut cache_lighting) = (None, None, false);
+		let (mut budget, mut overrun) = (None, Overrun::Wireframe);
+		let (mut tone_mapping, mut exposure) = (None, None);
+		let (mut contrast, mut white_balance) = (None, None);
+		let (mut sources, mut settings, mut roots) = (Vec::new(), Vec::new(), Vec::new());
+		let mut lights: Vec<LightSource> = Vec::new();
+		let mut ambient = AmbientLight::new(Color::RGB(1.0, 1.0, 1.0), 1.0);
+		let mut image_based = false;
+		let (mut meshes, mut spin): (Vec<Mesh>, Vec<Option<(Vector3D, f32)>>) = (Vec::new(), Vec::new());
+		let sections = parse_sections(path, text)?;
+		
+		// every mesh file and texture is loaded up front on the thread pool, the loop below takes them in the order it
+		// comes across them. values of the wrong type are left for the loop to report
+		let (mut mesh_files, mut group_files, mut texture_names) = (Vec::new(), Vec::new(), Vec::new());
+		for section in sections.iter() {
+			if section.name == "mesh" {
+				if let Some((_, Value::Text(name), _)) = section.entries.iter().find(|(key, _, _)| key == "file") {
+					assets.add_root_of(name);
+					// meshes that are one group of an obj come from the file split up instead
+					if section.entries.iter().any(|(key, _, _)| key == "group") { group_files.push(name.strip_suffix(".obj").unwrap_or(name)); }
+					else { mesh_files.push(name.as_str()); }
+				}
+			}
+			for (key, value, _) in section.entries.iter() {
+				let Value::Text(name) = value else { continue; };
+				match (section.name.as_str(), key.as_str()) {
+					("", "assets") => assets.add_root(name),
+					("mesh", "texture") | ("viewport", "panorama") => { assets.add_root_of(name); texture_names.push(name.clone()); },
+					("viewport", "cubemap") => {
+						assets.add_root_of(name);
+						texture_names.extend(["px", "nx", "py", "ny", "pz", "nz"].map(|side| format!("{name}_{side}")));
+					},
+					_ => {}
+				}
+			}
+		}
+		let mut loaded_meshes = load_concurrently(assets, "objects", &mesh_files, load_mesh_file).into_iter();
+		let mut loaded_groups = load_concurrently(assets, "objects", &group_files, load_object_groups).into_iter();
+		let texture_names: Vec<&str> = texture_names.iter().map(|name| name.as_str()).collect();
+		let mut textures = load_concurrently(assets, "textures", &texture_names, AssetManager::load_texture).into_iter();
+
+		for section in sections {
+			// a mesh table takes its file first so the transforms after it have something to move
+			if section.name == "mesh" {
+				let Some((_, file, line)) = section.entries.iter().find(|(key, _, _)| key == "file") else {
+					return Err(scene_error(path, section.line, "[[mesh]] needs a file".to_string()));
+				};
+				let Value::Text(name) = file else { return Err(scene_error(path, *line, "mesh.file: expected a \"string\"".to_string())); };
+				let group = match section.entries.iter().find(|(key, _, _)| key == "group") {
+					Some((_, Value::Text(group), _)) => Some(group.clone()),
+					Some((_, _, line)) => return Err(scene_error(path, *line, "mesh.group: expected a \"string\"".to_string())),
+					None => None
+				};
+				match &group {
+					Some(group) => {
+						let mut groups = loaded_groups.next().unwrap()?;
+						let Some(index) = groups.iter().position(|(name, _)| name == group) else {
+							return Err(scene_error(path, *line, format!("mesh.group: {name} has no group {group:?}")));
+						};
+						meshes.push(groups.swap_remove(index).1);
+					},
+					None => meshes.push(loaded_meshes.next().unwrap()?)
+				}
+				spin.push(None);
+				sources.push(MeshSource{ file: name.clone(), group, texture: None, transforms: Vec::new(), wave: None });
+			}
+			// the shadow plane defaults to the ground (y = 0) with the light left for after the loop
+			if section.name == "shadow" { shadow = Some(PlanarShadow::new(Vector3D::zero(), Vector3D::XYZ(0.0, 1.0, 0.0), Vector3D::zero(), 0.5)); }
+			let mut light = (section.name == "light").then(LightSource::builder);
+			let mut material = meshes.last().filter(|_| section.name == "mesh.material").map(|mesh| MaterialBuilder::from(mesh.material.clone()));
+			if section.name == "mesh.material" && meshes.is_empty() {
+				return Err(scene_error(path, section.line, "[mesh.material] has to follow a [[mesh]]".to_string()));
+			}
+
+			for (key, value, line) in section.entries.iter() {
+				let error = |message: &str| scene_error(path, *line, format!("{}.{key}: {message}", section.name));
+				let number = || match value { Value::Number(n) => Ok(*n), _ => Err(error("expected a number")) };
+				let text = || match value { Value::Text(t) => Ok(t.as_str()), _ => Err(error("expected a \"string\"")) };
+				let list = |count: usize| match value { Value::List(l) if l.len() == count => Ok(l.clone()), _ => Err(error(&format!("expected a list of {count} numbers"))) };
+				let vector = || list(3).map(|v| Vector3D::XYZ(v[0], v[1], v[2]));
+				let color = || list(3).map(|c| Color::RGB(c[0], c[1], c[2]));
+				let axis_angle = || list(4).map(|r| (Vector3D::XYZ(r[0], r[1], r[2]), r[3]));
+				let mesh = meshes.last_mut();
+				let source = sources.last_mut();
+				// studio lights are saved along with the others, so they aren't added again when the scene is loaded back
+				if section.name == "viewport" && key != "studio" { settings.push((key.clone(), value.clone())); }
+
+				match (section.name.as_str(), key.as_str()) {
+					("", "assets") => roots.push(text()?.to_string()),
+					("viewport", "size") => { let size = list(2)?; viewport = viewport.size(size[0] as usize, size[1] as usize); },
+					("viewport", "fov") => viewport = viewport.fov(number()?),
+					("viewport", "focal_length") => viewport = viewport.focal_length(number()?),
+					("viewport", "background") => viewport = viewport.background(color()?),
+					("viewport", "weights") => { let w = list(3)?; viewport = viewport.shading_weights(ShadingWeights::new(w[0], w[1], w[2])); },
+					("viewport", "near") => near = Some(number()?),
+					("viewport", "panorama") => {
+						text()?; // only strings were loaded, anything else is an error here before it takes a texture
+						viewport = viewport.environment(Environment::Panorama(textures.next().unwrap()?));
+					},
+					("viewport", "cubemap") => {
+						text()?;
+						let [px, nx, py, ny, pz, nz] = [(); 6].map(|_| textures.next().unwrap());
+						viewport = viewport.environment(Environment::Cubemap(Box::new([px?, nx?, py?, ny?, pz?, nz?])));
+					},
+					("viewport", "budget") => budget = match number()? {
+						ms if ms > 0.0 && ms.is_finite() => Some(Duration::from_secs_f32(ms/1000.0)),
+						_ => return Err(error("expected a positive number of milliseconds"))
+					},
+					("viewport", "overrun") => overrun = match text()? {
+						"partial" => Overrun::Partial,
+						"wireframe" => Overrun::Wireframe,
+						_ => return Err(error("expected partial or wireframe"))
+					},
+					("viewport", "cell_size") => {
+						let size = list(2)?;
+						if size[0] <= 0.0 || size[1] <= 0.0 { return Err(error("the cell size has to be positive")); }
+						viewport = viewport.cell_size(size[0], size[1]);
+					},
+					("viewport", "filter") => viewport = viewport.filter(match value {
+						Value::Text(filter) if filter == "grayscale" => ColorFilter::Grayscale,
+						Value::Text(filter) if filter == "green" => ColorFilter::Monochrome(PHOSPHOR_GREEN),
+						Value::Text(filter) if filter == "amber" => ColorFilter::Monochrome(PHOSPHOR_AMBER),
+						Value::List(_) => ColorFilter::Monochrome(color()?),
+						_ => return Err(error("expected grayscale, green, amber or a tint color"))
+					}),
+					("viewport", "tone_mapping") => tone_mapping = Some(match text()? {
+						"clamp" => ToneMapping::Clamp,
+						"reinhard" => ToneMapping::Reinhard,
+						"aces" => ToneMapping::Aces,
+						_ => return Err(error("expected clamp, reinhard or aces"))
+					}),
+					("viewport", "exposure") => exposure = Some(number()?),
+					("viewport", "contrast") => contrast = Some(number()?),
+					("viewport", "white_balance") => white_balance = Some(number()?),
+					("viewport", "colors") => viewport = viewport.color_mode(match text()? {
+						"truecolor" => ColorMode::TrueColor,
+						"256" => ColorMode::Ansi256,
+						_ => return Err(error("expected truecolor or 256"))
+					}),
+					("viewport", "rasterizer") => viewport = viewport.rasterizer(match text()? {
+						"float" => Rasterizer::Float,
+						"fixed-point" => Rasterizer::FixedPoint,
+						_ => return Err(error("expected float or fixed-point"))
+					}),
+					("viewport", "visibility") => viewport = viewport.visibility(match text()? {
+						"depth-buffer" => Visibility::DepthBuffer,
+						"painter" => Visibility::Painter,
+						_ => return Err(error("expected depth-buffer or painter"))
+					}),
+					("viewport", "cells") => viewport = viewport.cell_shader(match text()? {
+						"ascii" => Box::new(AsciiShader),
+						"edges" => Box::new(EdgeShader{ line_color: Color::RGB(1.0, 1.0, 1.0) }),
+						_ => return Err(error("expected ascii or edges"))
+					}),
+					("viewport", "cache_lighting") => cache_lighting = match value {
+						Value::Bool(cache) => *cache,
+						_ => return Err(error("expected true or false"))
+					},
+					("viewport", "headless") => if *value == Value::Bool(true) { viewport = viewport.headless(); },
+					("viewport", "quality") => viewport = viewport.quality(match text()? {
+						"low" => Quality::Low,
+						"medium" => Quality::Medium,
+						"high" => Quality::High,
+						_ => return Err(error("expected low, medium or high"))
+					}),
+					("viewport", "studio") => viewport = viewport.studio(match text()? {
+						"neutral" => StudioRig::Neutral,
+						"high-key" => StudioRig::HighKey,
+						"low-key" => StudioRig::LowKey,
+						_ => return Err(error("expected neutral, high-key or low-key"))
+					}),
+					("camera", "position") => camera.get_or_insert(Camera::new()).position = vector()?,
+					("camera", "look_at") => { let target = vector()?; camera.get_or_insert(Camera::new()).look_at(target); },
+					("ambient", "color") => ambient.color = color()?,
+					("ambient", "intensity") => ambient.intensity = number()?,
+					("ambient", "environment") => image_based = match value { Value::Bool(b) => *b, _ => return Err(error("expected true or false")) },
+					("shadow", "point") => shadow.as_mut().unwrap().point = vector()?,
+					("shadow", "normal") => shadow.as_mut().unwrap().normal = vector()?,
+					("shadow", "light") => shadow.as_mut().unwrap().light = vector()?,
+					("shadow", "darkness") => shadow.as_mut().unwrap().darkness = number()?,
+					("light", "color") => light = Some(light.take().unwrap().color(color()?)),
+					("light", "position") => light = Some(light.take().unwrap().position(vector()?)),
+					("light", "intensity") => light = Some(light.take().unwrap().intensity(number()?)),
+					("light", "range") => light = Some(light.take().unwrap().range(number()?)),
+					("light", "enabled") => light = Some(light.take().unwrap().enabled(match value { Value::Bool(b) => *b, _ => return Err(error("expected true or false")) })),
+					("mesh", "file" | "group") => {},
+					("mesh", "texture") => {
+						text()?;
+						mesh.unwrap().texture = textures.next().unwrap()?;
+						source.unwrap().texture = Some(text()?.to_string());
+					},
+					("mesh", "translate" | "scale" | "rotate") => {
+						let transform = match key.as_str() {
+							"translate" => Transform::Translate(vector()?),
+							"scale" => Transform::Scale(vector()?),
+							_ => { let (axis, degrees) = axis_angle()?; Transform::rotation(axis, degrees) }
+						};
+						mesh.unwrap().transform(transform);
+						source.unwrap().transforms.push(transform);
+					},
+					("mesh", "spin") => *spin.last_mut().unwrap() = Some(axis_angle()?),
+					("mesh", "wave") => {
+						let wave = list(3)?;
+						if wave[1] == 0.0 { return Err(error("the wavelength can't be 0")); }
+						let wave = SineWave{ amplitude: wave[0], wavelength: wave[1], speed: wave[2] };
+						mesh.unwrap().vertex_shader = Some(Arc::new(wave));
+						source.unwrap().wave = Some(wave);
+					},
+					("mesh.material", "mode") => material = Some(material.take().unwrap().mode(match text()? {
+						"flat" => LightingMode::Flat,
+						"smooth" => LightingMode::Smooth,
+						"gouraud" => LightingMode::Gouraud,
+						"toon" => LightingMode::Toon(3),
+						"unlit" => LightingMode::None,
+						_ => return Err(error("expected flat, smooth, gouraud, toon or unlit"))
+					})),
+					("mesh.material", "bands") => material = Some(material.take().unwrap().mode(match number()? {
+						bands if bands >= 2.0 => LightingMode::Toon(bands as usize),
+						_ => return Err(error("expected 2 or more bands"))
+					})),
+					("mesh.material", "ambient") => material = Some(material.take().unwrap().ambient(color()?)),
+					("mesh.material", "diffuse") => material = Some(material.take().unwrap().diffuse(color()?)),
+					("mesh.material", "specular") => material = Some(material.take().unwrap().specular(color()?)),
+					("mesh.material", "emissive") => material = Some(material.take().unwrap().emissive(color()?)),
+					("mesh.material", "highlights") => material = Some(material.take().unwrap().highlights(number()?)),
+					("mesh.material", "specular_model") => material = Some(material.take().unwrap().specular_model(match text()? {
+						"phong" => SpecularModel::Phong,
+						"blinn-phong" => SpecularModel::BlinnPhong,
+						_ => return Err(error("expected phong or blinn-phong"))
+					})),
+					("mesh.material", "metallic") => material = Some(material.take().unwrap().metallic(number()?)),
+					("mesh.material", "roughness") => material = Some(material.take().unwrap().roughness(number()?)),
+					("mesh.material", "opacity") => material = Some(material.take().unwrap().opacity(number()?)),
+					_ => return Err(error("unknown setting"))
+				}
+			}
+			if let Some(light) = light { lights.push(light.build()); }
+			if let (Some(material), Some(mesh)) = (material, meshes.last_mut()) { mesh.material = material.build(); }
+		}
+		// shadows without a light of their own are cast from the first one
+		if let Some(shadow) = shadow.as_mut().filter(|shadow| shadow.light.mag() == 0.0) {
+			shadow.light = lights.first().map_or(Vector3D::XYZ(0.0, 1.0, 0.0), |light| light.position);
+		}
+		for light in lights { viewport = viewport.light(light); }
+		viewport = viewport.ambient(ambient.color, ambient.intensity);
+		if image_based { viewport = viewport.image_based_ambient(); }
+		if let Some(camera) = camera { viewport = viewport.camera(camera); }
+		if let Some(budget) = budget { viewport = viewport.frame_budget(budget, overrun); }
+		if tone_mapping.is_some() || exposure.is_some() {
+			viewport = viewport.tone_mapping(tone_mapping.unwrap_or(ToneMapping::Clamp), exposure.unwrap_or(1.0));
+		}
+		if contrast.is_some() || white_balance.is_some() {
+			viewport = viewport.grade(contrast.unwrap_or(1.0), white_balance.unwrap_or(6500.0));
+		}
+		let framed = camera.is_none();
+		let mut scene = Scene{ viewport: viewport.build(), rest: meshes.clone(), meshes, sources, spin, near, shadow, cache_lighting, settings, roots, frame: 0.0 };
+		if framed { scene.frame(0.1); }
+		Ok(scene)
+	}
+
+	// loading a scene never touches the terminal, players that are going to show it call this. the terminal picks the
+	// display mode, and the color mode unless the file set colors. headless scenes are left alone
+	pub fn detect_terminal(&mut self) {
+		let setting = |name: &str| self.settings.iter().find(|(key, _)| key == name).map(|(_, value)| value);
+		if setting("headless") == Some(&Value::Bool(true)) { return; }
+		let (display_mode, color_mode) = detect_terminal();
+		self.viewport.display_mode = display_mode;
+		if setting("colors").is_none() { self.viewport.color_mode = color_mode; }
+		// the pixel aspect depends on the display mode, so a cell size from the file is worked out again
+		if let Some(Value::List(size)) = setting("cell_size") { self.viewport.set_cell_size(size[0], size[1]); }
+	}
+
+	// move the camera back along its view until every mesh as currently posed fits, margin as in Viewport::frame
+	pub fn frame(&mut self, margin: f32) {
+		if let Some(bounds) = Aabb::around(self.meshes.iter().flat_map(|mesh| mesh.vertices.iter().copied())) {
+			self.viewport.frame(bounds, margin);
+		}
+	}
+
+	// one frame of every mesh, antialiased like draw_frame does
+	pub fn draw(&mut self) {
+		let (meshes, near, shadow) = (&self.meshes, self.near, self.shadow);
+		self.viewport.draw_frame(|screen| {
+			for mesh in meshes.iter() {
+				let Some(near) = near else { screen.draw_mesh(mesh); continue; };
+				let mut clipped = mesh.clone();
+				screen.clip_against_plane(&mut clipped, screen.camera.position.add(screen.camera.forward.mul(near)), screen.camera.forward);
+				screen.draw_mesh(&clipped);
+			}
+			// after every mesh so the shadows can land on all of them
+			if let Some(shadow) = shadow {
+				for mesh in meshes.iter() { screen.draw_planar_shadow(mesh, &shadow); }
+			}
+		});
+	}
+
+	// put every mesh where it is frame frames into the animation. poses are rebuilt from the meshes as loaded rather than
+	// stepped from the last one, so frames can be shown in any order (and changes made to meshes are lost). fractional
+	// frames land in between, for exporting at more than SCENE_FPS
+	pub fn pose(&mut self, frame: f32) {
+		self.frame = frame;
+		self.viewport.time = frame/SCENE_FPS;
+		for ((mesh, rest), spin) in self.meshes.iter_mut().zip(self.rest.iter_mut()).zip(self.spin.iter()) {
+			// the cache is kept on the rest copy so every pose clones it instead of lighting again, and the key
+			// throws it away when lights or camera change
+			if self.cache_lighting && spin.is_none() { self.viewport.cache_lighting(rest); }
+			*mesh = rest.clone();
+			if let Some((axis, degrees)) = spin { mesh.transform(Transform::rotation(*axis, *degrees*frame)); }
+		}
+	}
+	
+	// move one of the meshes for good: it's kept with the mesh's other transforms, shows in the current pose right away
+	// and gets saved with the scene
+	pub fn transform(&mut self, mesh: usize, transform: Transform) {
+		self.rest[mesh].transform(transform);
+		self.sources[mesh].transforms.push(transform);
+		self.pose(self.frame);
+	}
+	
+	// the scene in the format from_str reads. frame becomes the starting pose: spin up to it is written as one more
+	// rotate, so a scene saved while paused opens where it was left
+	pub fn to_toml(&self, frame: f32) -> String {
+		let mut out = String::new();
+		let mut table = |header: &str, entries: Vec<(&str, Value)>| {
+			if !header.is_empty() { out += &format!("{header}\n"); }
+			for (key, value) in entries { out += &format!("{key} = {value}\n"); }
+			out += "\n";
+		};
+		let vector = |v: Vector3D| list(&[v.X, v.Y, v.Z]);
+		let color = |c: Color| list(&[c.RGB.0, c.RGB.1, c.RGB.2]);
+		
+		if !self.roots.is_empty() { table("", self.roots.iter().map(|root| ("assets", Value::Text(root.clone()))).collect()); }
+		table("[viewport]", self.settings.iter().map(|(key, value)| (key.as_str(), value.clone())).collect());
+		let camera = self.viewport.camera;
+		// look_at rebuilds up from the world axes, so a rolled camera comes back level
+		table("[camera]", vec![("position", vector(camera.position)), ("look_at", vector(camera.position.add(camera.forward)))]);
+		let ambient = self.viewport.ambient;
+		let mut entries = vec![("color", color(ambient.color)), ("intensity", Value::Number(ambient.intensity))];
+		if ambient.irradiance.is_some() { entries.push(("environment", Value::Bool(true))); }
+		table("[ambient]", entries);
+		if let Some(shadow) = self.shadow {
+			table("[shadow]", vec![("point", vector(shadow.point)), ("normal", vector(shadow.normal)), ("light", vector(shadow.light)), ("darkness", Value::Number(shadow.darkness))]);
+		}
+		for light in self.viewport.lights.iter() {
+			let mut entries = vec![("color", color(light.color)), ("position", vector(light.position)), ("intensity", Value::Number(light.intensity))];
+			if let Some(range) = light.range { entries.push(("range", Value::Number(range))); }
+			if !light.enabled { entries.push(("enabled", Value::Bool(false))); }
+			table("[[light]]", entries);
+		}
+		for ((source, mesh), spin) in self.sources.iter().zip(self.rest.iter()).zip(self.spin.iter()) {
+			let mut entries = vec![("file", Value::Text(source.file.clone()))];
+			if let Some(group) = &source.group { entries.push(("group", Value::Text(group.clone()))); }
+			if let Some(texture) = &source.texture { entries.push(("texture", Value::Text(texture.clone()))); }
+			let started = spin.filter(|_| frame != 0.0).map(|(axis, degrees)| Transform::rotation(axis, degrees*frame));
+			for transform in source.transforms.iter().chain(started.iter()) {
+				entries.push(match transform {
+					Transform::Translate(v) => ("translate", vector(*v)),
+					Transform::Scale(v) => ("scale", vector(*v)),
+					Transform::Rotate(_, _) => {
+						let (axis, degrees) = transform.axis_angle().unwrap_or((Vector3D::XYZ(0.0, 0.0, 1.0), 0.0));
+						("rotate", list(&[axis.X, axis.Y, axis.Z, degrees]))
+					}
+				});
+			}
+			if let Some((axis, degrees)) = spin { entries.push(("spin", list(&[axis.X, axis.Y, axis.Z, *degrees]))); }
+			if let Some(wave) = source.wave { entries.push(("wave", list(&[wave.amplitude, wave.wavelength, wave.speed]))); }
+			table("[[mesh]]", entries);
+			
+			let material = &mesh.material;
+			let mode = match material.mode { LightingMode::Flat => "flat", LightingMode::Smooth => "smooth", LightingMode::Gouraud => "gouraud", LightingMode::Toon(_) => "toon", LightingMode::None => "unlit" };
+			let mut entries = vec![
+				("mode", Value::Text(mode.to_string())), ("ambient", color(material.ambient)), ("diffuse", color(material.diffuse)),
+				("specular", color(material.specular)), ("emissive", color(material.emissive)), ("highlights", Value::Number(material.highlights)), ("opacity", Value::Number(material.opacity)),
+				("specular_model", Value::Text(match material.specular_model { SpecularModel::Phong => "phong", SpecularModel::BlinnPhong => "blinn-phong" }.to_string()))
+			];
+			if let Some(pbr) = &material.pbr { entries.extend([("metallic", Value::Number(pbr.metallic)), ("roughness", Value::Number(pbr.roughness))]); }
+			// after mode, which resets it
+			if let LightingMode::Toon(bands) = material.mode { entries.push(("bands", Value::Number(bands as f32))); }
+			table("[mesh.material]", entries);
+		}
+		out.trim_end().to_string() + "\n"
+	}
+	
+	pub fn save(&self, path: &str, frame: f32) -> std::io::Result<()> {
+		println!("exporting scene: {path}");
+		std::fs::write(path, self.to_toml(frame))?;
+		println!("scene exported successfully!\n");
+		Ok(())
+	}
+}
+
+// playback position over an animation of length frames. players call tick once per frame shown, everything else is
+// for whoever is driving it: keys in the viewer, or code stepping through frames to inspect them
+pub struct Timeline {
+	pub frame: usize,
+	pub length: usize,
+	pub playing: bool,
+	pub looping: bool // otherwise playback pauses on the last frame
+}
+
+impl Timeline {
+	pub fn new(length: usize) -> Timeline { Timeline{ frame: 0, length: length.max(1), playing: true, looping: true } }
+	
+	pub fn play(&mut self) { self.playing = true; }
+	pub fn pause(&mut self) { self.playing = false; }
+	pub fn toggle(&mut self) { self.playing = !self.playing; }
+	
+	// jump to a frame, clamped to the timeline
+	pub fn seek(&mut self, frame: isize) { self.frame = frame.clamp(0, self.length as isize - 1) as usize; }
+	
+	// move by frames (negative goes back) and pause there, the way frame by frame inspection wants it
+	pub fn step(&mut self, frames: isize) {
+		self.pause();
+		self.seek(self.frame as isize + frames);
+	}
+	
+	// jump to a fraction of the way through, 0 is the first frame and 1 the last
+	pub fn scrub(&mut self, fraction: f32) { self.seek((fraction.clamp(0.0, 1.0)*(self.length - 1) as f32).round() as isize); }
+	pub fn progress(&self) -> f32 { if self.length > 1 { self.frame as f32/(self.length - 1) as f32 }else { 0.0 } }
+	
+	pub fn tick(&mut self) {
+		if !self.playing { return; }
+		if self.frame + 1 < self.length { self.frame += 1; }
+		else if self.looping { self.frame = 0; }
+		else { self.pause(); }
+	}
+}
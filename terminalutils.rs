@@ -0,0 +1,116 @@
+use std::io::{ IsTerminal, Write };
+use std::process::{ Command, Stdio };
+
+// everything the viewport needs from a terminal, swap it out to draw through crossterm/termion or to capture frames
+pub trait Backend {
+	fn write_frame(&mut self, frame: &str) -> std::io::Result<()>;
+	fn size(&self) -> Option<(usize, usize)>; // columns and rows, None when there's no terminal to ask
+	fn enter_raw_mode(&mut self) -> std::io::Result<()>;
+	fn leave_raw_mode(&mut self) -> std::io::Result<()>;
+}
+
+// plain escape codes on stdout, raw mode and size go through stty like the terminal detection does
+pub struct AnsiBackend {
+	saved_mode: Option<String>, // stty settings from before enter_raw_mode
+	crlf: bool // line feeds don't return the cursor in raw mode or on windows consoles, so lines end in \r\n there
+}
+
+impl AnsiBackend {
+	pub fn new() -> AnsiBackend {
+		enable_virtual_terminal();
+		AnsiBackend{ saved_mode: None, crlf: cfg!(windows) }
+	}
+}
+
+// windows consoles only understand escape codes once virtual terminal processing is switched on for the output handle,
+// newer ones (windows terminal) already have it on so this does nothing there. only runs once per process
+#[cfg(windows)]
+pub fn enable_virtual_terminal() {
+	type Handle = *mut std::ffi::c_void;
+	const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+	const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+	extern "system" {
+		fn GetStdHandle(std_handle: u32) -> Handle;
+		fn GetConsoleMode(console: Handle, mode: *mut u32) -> i32;
+		fn SetConsoleMode(console: Handle, mode: u32) -> i32;
+	}
+	static ENABLED: std::sync::Once = std::sync::Once::new();
+	ENABLED.call_once(|| unsafe {
+		let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+		let mut mode = 0;
+		if !handle.is_null() && GetConsoleMode(handle, &mut mode) != 0 {
+			SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING);
+		}
+	});
+}
+
+#[cfg(not(windows))]
+pub fn enable_virtual_terminal() {}
+
+fn stty(args: &[&str]) -> std::io::Result<String> {
+	let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?;
+	if !output.status.success() { return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("stty {} failed", args.join(" ")))); }
+	Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl Backend for AnsiBackend {
+	// one locked write per frame so the terminal never shows half of one
+	fn write_frame(&mut self, frame: &str) -> std::io::Result<()> {
+		let mut stdout = std::io::stdout().lock();
+		if self.crlf && frame.contains('\n') {
+			stdout.write_all(frame.replace('\n', "\r\n").as_bytes())?;
+		}else {
+			stdout.write_all(frame.as_bytes())?;
+		}
+		stdout.flush()
+	}
+	
+	fn size(&self) -> Option<(usize, usize)> {
+		if !std::io::stdin().is_terminal() { return None; }
+		let size = stty(&["size"]).ok()?;
+		let (rows, columns) = size.split_once(' ')?;
+		Some((columns.parse().ok()?, rows.parse().ok()?))
+	}
+	
+	fn enter_raw_mode(&mut self) -> std::io::Result<()> {
+		if self.saved_mode.is_some() { return Ok(()); }
+		self.saved_mode = Some(stty(&["-g"])?);
+		stty(&["raw", "-echo"])?;
+		self.crlf = true;
+		print!("\x1b[?25l"); // hide the cursor while frames are being drawn
+		std::io::stdout().flush()
+	}
+	
+	fn leave_raw_mode(&mut self) -> std::io::Result<()> {
+		if let Some(saved) = self.saved_mode.take() { stty(&[&saved])?; }
+		self.crlf = cfg!(windows);
+		print!("\x1b[?25h");
+		std::io::stdout().flush()
+	}
+}
+
+impl Drop for AnsiBackend {
+	// don't leave the user's shell in raw mode if we panic or forget to call leave_raw_mode
+	fn drop(&mut self) { if self.saved_mode.is_some() { let _ = self.leave_raw_mode(); } }
+}
+
+// keeps every frame instead of printing it, for tests and for checking output without a terminal
+pub struct RecordingBackend {
+	pub frames: Vec<String>,
+	pub size: Option<(usize, usize)>,
+	pub raw_mode: bool
+}
+
+impl RecordingBackend {
+	pub fn new(size: Option<(usize, usize)>) -> RecordingBackend { RecordingBackend{ frames: Vec::new(), size, raw_mode: false } }
+}
+
+impl Backend for RecordingBackend {
+	fn write_frame(&mut self, frame: &str) -> std::io::Result<()> {
+		self.frames.push(frame.to_string());
+		Ok(())
+	}
+	fn size(&self) -> Option<(usize, usize)> { self.size }
+	fn enter_raw_mode(&mut self) -> std::io::Result<()> { self.raw_mode = true; Ok(()) }
+	fn leave_raw_mode(&mut self) -> std::io::Result<()> { self.raw_mode = false; Ok(()) }
+}
@@ -0,0 +1,429 @@
+use crate::{ Vector3D, Color, approx_eq };
+use crate::mesh::{ Mesh, Transform, Aabb };
+use crate::viewport::{ Viewport, Rasterizer, Visibility, Quality };
+use crate::graphicsutils::{ Rng, LightSource, LightingMode, Texture, Material, Transparency, SpecularModel, Pbr, Fragment, VertexInput, Environment, Irradiance };
+use crate::displayutils::{ DisplayMode, Dithering, Bloom, encode_ppm };
+use crate::exportutils::{ encode_mesh, decode_mesh };
+
+use std::sync::Arc;
+
+// mostly sensible numbers with the occasional value that breaks naive math
+fn random_float(rng: &mut Rng) -> f32 {
+	match rng.range(0, 20) {
+		0 => f32::NAN,
+		1 => f32::INFINITY,
+		2 => f32::NEG_INFINITY,
+		3 => 0.0,
+		4 => 1e-30,
+		5 => (rng.float() - 0.5) * 1e20,
+		_ => (rng.float() - 0.5) * 20.0
+	}
+}
+
+fn random_vector(rng: &mut Rng) -> Vector3D {
+	Vector3D::XYZ(random_float(rng), random_float(rng), random_float(rng))
+}
+
+// indices are occasionally out of range and the attribute arrays don't always line up, like a half broken import
+fn random_mesh(rng: &mut Rng) -> Mesh {
+	let vertex_count = rng.range(0, 12);
+	let vertices = (0..vertex_count).map(|_| random_vector(rng)).collect();
+	let mut triangles = Vec::new();
+	for _ in 0..rng.range(0, 16) {
+		let mut index = || rng.range(0, vertex_count + 2);
+		triangles.push((index(), index(), index()));
+	}
+	let mut mesh = Mesh::new(vertices, triangles);
+	mesh.tex_coords = (0..rng.range(0, 4)).map(|_| (random_float(rng), random_float(rng))).collect();
+	for _ in 0..(mesh.triangles.len() + rng.range(0, 3)).saturating_sub(1) {
+		mesh.tex_tris.push((rng.range(0, 5), rng.range(0, 5), rng.range(0, 5)));
+	}
+	mesh.face_normals = (0..mesh.triangles.len()).map(|_| random_vector(rng)).collect();
+	mesh.vertex_normals = (0..mesh.vertices.len()).map(|_| random_vector(rng)).collect();
+	mesh.vertex_colors = (0..rng.range(0, mesh.vertices.len() + 2)).map(|_| Color::RGB(random_float(rng), random_float(rng), random_float(rng))).collect();
+	mesh.texture = if rng.range(0, 4) == 0 { Texture::new(rng.range(0, 3), rng.range(0, 3), Vec::new()) }
+		else { Texture::missing(rng.range(0, 8), rng.range(0, 8), rng.range(0, 3)) };
+	mesh.material.mode = match rng.range(0, 5) {
+		0 => LightingMode::Flat,
+		1 => LightingMode::Smooth,
+		2 => LightingMode::Gouraud,
+		3 => LightingMode::Toon(rng.range(0, 5)),
+		_ => LightingMode::None
+	};
+	let random_map = |rng: &mut Rng| if rng.range(0, 3) == 0 { Some(Texture::new(rng.range(0, 3), rng.range(0, 3), Vec::new())) }else { None };
+	(mesh.material.specular_map, mesh.material.highlight_map, mesh.material.opacity_map) = (random_map(rng), random_map(rng), random_map(rng));
+	(mesh.material.emissive, mesh.material.emissive_map) = (Color::RGB(random_float(rng), random_float(rng), random_float(rng)), random_map(rng));
+	mesh.material.normal_map = random_map(rng).or_else(|| if rng.range(0, 3) == 0 { Some(Texture::missing(rng.range(0, 4), rng.range(0, 4), 1)) }else { None });
+	if rng.range(0, 3) == 0 {
+		mesh.material.pbr = Some(Pbr{ metallic: random_float(rng), roughness: random_float(rng), metallic_map: random_map(rng), roughness_map: random_map(rng) });
+	}
+	mesh.material.opacity = random_float(rng);
+	mesh.material.specular_highlights = rng.range(0, 4) != 0;
+	mesh.material.transparency = [Transparency::Blend, Transparency::ScreenDoor, Transparency::Noise][rng.range(0, 3)];
+	if rng.range(0, 3) == 0 {
+		mesh.materials = (0..rng.range(0, 3)).map(|_| (mesh.material.clone(), Texture::missing(rng.range(0, 4), rng.range(0, 4), 1))).collect();
+		mesh.face_materials = (0..mesh.triangles.len() + rng.range(0, 3)).map(|_| rng.range(0, 4)).collect();
+	}
+	if rng.range(0, 4) == 0 {
+		// stripes across world x, or whatever a broken shader might hand back
+		let (width, junk) = (random_float(rng), Color::RGB(random_float(rng), random_float(rng), random_float(rng)));
+		mesh.shader = Some(Arc::new(move |fragment: &Fragment| if (fragment.position.X/width).rem_euclid(2.0) < 1.0 { fragment.material.diffuse }else { junk.mul(fragment.depth) }));
+	}
+	if rng.range(0, 4) == 0 {
+		let (offset, wobble) = (random_vector(rng), random_float(rng));
+		mesh.vertex_shader = Some(Arc::new(move |vertex: &VertexInput| vertex.position.add(offset.mul((vertex.time*wobble + vertex.index as f32).sin()))));
+	}
+	if rng.range(0, 3) == 0 {
+		mesh.normals = (0..rng.range(0, 6)).map(|_| random_vector(rng)).collect();
+		mesh.normal_tris = (0..mesh.triangles.len() + rng.range(0, 3)).map(|_| (rng.range(0, 6), rng.range(0, 6), rng.range(0, 6))).collect();
+	}
+	mesh
+}
+
+// renders randomly generated broken meshes, any panic in here is a renderer bug
+pub fn fuzz_render(iterations: usize, seed: u64) {
+	let mut rng = Rng::new(seed);
+	println!("fuzzing renderer: {iterations} meshes, seed {seed}");
+	for i in 0..iterations {
+		let mut screen = Viewport::headless(rng.range(1, 40), rng.range(1, 40), random_float(&mut rng), Color::black());
+		if rng.range(0, 2) == 0 { screen.rasterizer = Rasterizer::FixedPoint; }
+		if rng.range(0, 3) == 0 { screen.visibility = Visibility::Painter; }
+		if rng.range(0, 2) == 0 { screen.bloom = Some(Bloom::new(random_float(&mut rng), random_float(&mut rng), 1.0, rng.range(0, 4))); }
+		screen.grain = rng.float();
+		if rng.range(0, 4) == 0 { screen.set_quality([Quality::Low, Quality::Medium, Quality::High][rng.range(0, 3)]); }
+		for _ in 0..rng.range(0, 3) { screen.lights.push(LightSource::new(Color::RGB(1.0, 1.0, 1.0), random_vector(&mut rng))); }
+		
+		screen.time = random_float(&mut rng);
+		let random_texture = |rng: &mut Rng| Texture::missing(rng.range(0, 4), rng.range(0, 4), rng.range(0, 3));
+		screen.environment = match rng.range(0, 4) {
+			0 => Some(Environment::Panorama(random_texture(&mut rng))),
+			1 => Some(Environment::Cubemap(Box::new([(); 6].map(|_| random_texture(&mut rng))))),
+			_ => None
+		};
+		if rng.range(0, 3) == 0 { screen.set_image_based_ambient(); }
+		if rng.range(0, 2) == 0 { screen.camera.look_at(random_vector(&mut rng)); }
+		screen.clear_screen();
+		let mut mesh = random_mesh(&mut rng);
+		mesh.transform(Transform::Rotate(random_vector(&mut rng), random_vector(&mut rng)));
+		screen.clip_against_plane(&mut mesh, random_vector(&mut rng), random_vector(&mut rng));
+		mesh_area(&mesh);
+		screen.draw_mesh(&mesh);
+		screen.draw_wireframe(&mesh);
+		screen.draw_flat_texture(&mesh.texture);
+		for mode in [DisplayMode::HalfBlock, DisplayMode::QuarterBlock, DisplayMode::Sixel, DisplayMode::Kitty, DisplayMode::Iterm, DisplayMode::Braille, DisplayMode::Ascii, DisplayMode::Regis] {
+			screen.display_mode = mode;
+			screen.dithering = if rng.range(0, 2) == 0 { Dithering::FloydSteinberg }else { Dithering::Bayer };
+			screen.display();
+		}
+		if (i+1) % 100 == 0 { println!("{} meshes rendered", i+1); }
+	}
+	println!("fuzzing finished without panicking!");
+}
+
+
+// fnv-1a over the final 8 bit image, cheap enough to compare every frame of a run against a list of known hashes
+pub fn frame_hash(screen: &Viewport) -> u64 {
+	hash_bytes(&screen.rgb_bytes())
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+	bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ImageDiff {
+	pub mismatched: usize, // pixels where any channel differs by more than the tolerance
+	pub max_error: u8
+}
+
+pub fn diff_images(a: &[u8], b: &[u8], tolerance: u8) -> ImageDiff {
+	let mut diff = ImageDiff::default();
+	for (p1, p2) in a.chunks(3).zip(b.chunks(3)) {
+		let error = p1.iter().zip(p2.iter()).map(|(c1, c2)| c1.abs_diff(*c2)).max().unwrap_or(0);
+		diff.max_error = diff.max_error.max(error);
+		if error > tolerance { diff.mismatched += 1; }
+	}
+	diff
+}
+
+// only the binary ppm encode_ppm writes, whitespace separated header and 8 bit channels
+fn decode_ppm(data: &[u8]) -> Option<(usize, usize, Vec<u8>)> {
+	let mut fields = Vec::new();
+	let mut pos = 0;
+	while fields.len() < 4 {
+		while data.get(pos)?.is_ascii_whitespace() { pos += 1; }
+		let start = pos;
+		while !data.get(pos)?.is_ascii_whitespace() { pos += 1; }
+		fields.push(std::str::from_utf8(&data[start..pos]).ok()?);
+	}
+	if fields[0] != "P6" || fields[3] != "255" { return None; }
+	let (width, height): (usize, usize) = (fields[1].parse().ok()?, fields[2].parse().ok()?);
+	let pixels = data.get(pos+1..)?;
+	if pixels.len() != width*height*3 { return None; }
+	Some((width, height, pixels.to_vec()))
+}
+
+// compare the current frame against a stored reference ppm, a missing reference fails. bless overwrites the reference
+// with the current frame instead, for after a deliberate rendering change
+// on a mismatch the actual frame and an amplified difference image are saved next to the reference for inspection
+pub fn check_golden(screen: &Viewport, path: &str, tolerance: u8, bless: bool) -> std::io::Result<bool> {
+	let actual = screen.graded_pixels();
+	if bless {
+		std::fs::write(path, encode_ppm(&actual))?;
+		println!("{path}: reference image updated");
+		return Ok(true);
+	}
+	let Ok(reference) = std::fs::read(path) else {
+		println!("{path}: no reference image, run with --bless to create it");
+		return Ok(false);
+	};
+	let (height, width) = (actual.len(), if actual.len() > 0 { actual[0].len() }else { 0 });
+	let actual_bytes = screen.rgb_bytes();
+	// grading clamps a NaN to black, which a dark reference would let through, so the raw buffer is checked on its own
+	let non_finite = screen.pixels().iter().flatten().filter(|c| !(c.RGB.0.is_finite() && c.RGB.1.is_finite() && c.RGB.2.is_finite())).count();
+	if non_finite > 0 { println!("{path}: {non_finite} pixels aren't finite before grading"); }
+	let passed = non_finite == 0 && match decode_ppm(&reference) {
+		// equal hashes are the common case, only a mismatch is worth a pixel by pixel diff
+		Some((w, h, pixels)) if (w, h) == (width, height) && hash_bytes(&pixels) == frame_hash(screen) => {
+			println!("{path}: matches, hash {:016x}", hash_bytes(&pixels));
+			true
+		},
+		Some((w, h, pixels)) if (w, h) == (width, height) => {
+			let diff = diff_images(&pixels, &actual_bytes, tolerance);
+			println!("{path}: {} pixels differ, max channel error {}", diff.mismatched, diff.max_error);
+			if diff.mismatched > 0 {
+				let amplified: Vec<Vec<Color>> = (0..height).map(|h| (0..width).map(|w| {
+					let i = (h*width + w)*3;
+					let error = |c: usize| (pixels[i+c].abs_diff(actual_bytes[i+c]) as f32/255.0*8.0).min(1.0);
+					Color::RGB(error(0), error(1), error(2))
+				}).collect()).collect();
+				std::fs::write(format!("{path}.diff.ppm"), encode_ppm(&amplified))?;
+			}
+			diff.mismatched == 0
+		},
+		Some((w, h, _)) => { println!("{path}: reference is {w}x{h} but the frame is {width}x{height}"); false },
+		None => { println!("{path}: unreadable reference image"); false }
+	};
+	if !passed { std::fs::write(format!("{path}.actual.ppm"), encode_ppm(&actual))?; }
+	Ok(passed)
+}
+
+
+// well formed meshes for checking clipping results, unlike random_mesh every index is valid and every value finite
+// each vertex has its own texture coordinate at the same index so the two arrays stay parallel through clipping
+fn random_triangles(rng: &mut Rng) -> Mesh {
+	let count = rng.range(3, 12);
+	let mut coord = || (rng.float() - 0.5) * 20.0;
+	let vertices: Vec<Vector3D> = (0..count).map(|_| Vector3D::XYZ(coord(), coord(), coord())).collect();
+	let triangles: Vec<(usize, usize, usize)> = (0..rng.range(1, 10)).map(|_| (rng.range(0, count), rng.range(0, count), rng.range(0, count))).collect();
+	let mut mesh = Mesh::new(vertices, triangles.clone());
+	mesh.tex_tris = triangles;
+	mesh.tex_coords = (0..count).map(|_| (rng.float(), rng.float())).collect();
+	mesh.vertex_normals = (0..count).map(|_| Vector3D::XYZ(rng.float() - 0.5, rng.float() - 0.5, rng.float() - 0.5).normalize()).collect();
+	mesh.vertex_colors = (0..count).map(|_| Color::RGB(rng.float(), rng.float(), rng.float())).collect();
+	mesh.face_materials = (0..mesh.triangles.len()).map(|_| rng.range(0, 3)).collect();
+	if rng.range(0, 2) == 0 {
+		// hard edges, every corner gets its own normal
+		mesh.normals = (0..3*mesh.triangles.len()).map(|_| Vector3D::XYZ(rng.float() - 0.5, rng.float() - 0.5, rng.float() - 0.5).normalize()).collect();
+		mesh.normal_tris = (0..mesh.triangles.len()).map(|t| (3*t, 3*t + 1, 3*t + 2)).collect();
+	}
+	mesh.face_normals = mesh.triangles.iter().map(|&(t1, t2, t3)| mesh.vertices[t2].sub(mesh.vertices[t1]).cross(mesh.vertices[t3].sub(mesh.vertices[t1]))).collect();
+	mesh
+}
+
+// a point near the mesh and a direction that isn't too short to normalize
+fn random_plane(rng: &mut Rng) -> (Vector3D, Vector3D) {
+	let mut value = || (rng.float() - 0.5) * 16.0;
+	let position = Vector3D::XYZ(value(), value(), value());
+	let mut normal = Vector3D::XYZ(value(), value(), value());
+	if normal.mag() < 0.1 { normal = Vector3D::XYZ(0.0, 0.0, 1.0); }
+	(position, normal)
+}
+
+fn mesh_area(mesh: &Mesh) -> f32 {
+	mesh.faces().map(|face| { let [p1, p2, p3] = face.positions; (p2 - p1).cross(p3 - p1).mag() * 0.5 }).sum()
+}
+
+// everything that should hold after clip_against_plane, returns what broke
+fn clipping_violation(before: &Mesh, after: &Mesh, plane_pos: Vector3D, plane_normal: Vector3D) -> Option<String> {
+	const EPSILON: f32 = 1e-3;
+	let normal = plane_normal.normalize();
+	if after.triangles.len() != after.tex_tris.len() || after.triangles.len() != after.face_normals.len() { return Some("triangle arrays out of step".to_string()); }
+	if after.vertex_colors.len() != after.vertices.len() { return Some("vertex colors out of step".to_string()); }
+	if after.face_materials.len() != after.triangles.len() { return Some("face materials out of step".to_string()); }
+	if !before.normal_tris.is_empty() && after.normal_tris.len() != after.triangles.len() { return Some("corner normals out of step".to_string()); }
+	if let Some(t) = after.normal_tris.iter().position(|n| n.0 >= after.normals.len() || n.1 >= after.normals.len() || n.2 >= after.normals.len()) { return Some(format!("triangle {t} has a normal index out of range")); }
+	if let Some(t) = (0..after.triangles.len()).find(|t| !after.triangle_in_range(*t)) { return Some(format!("triangle {t} has an index out of range")); }
+	
+	// cut corners render with the normal interpolated for their new vertex, whichever array it's read from
+	for t in 0..after.triangles.len() {
+		let (t1, t2, t3) = after.triangles[t];
+		for (v, n) in [t1, t2, t3].into_iter().zip(after.corner_normals(t)) {
+			if v >= before.vertices.len() && !n.approx_eq(after.vertex_normals[v], 1e-5) { return Some(format!("triangle {t} renders new vertex {v} with normal {n:?} instead of {:?}", after.vertex_normals[v])); }
+	}}
+	
+	// every vertex still in use is on the kept side
+	for &(t1, t2, t3) in after.triangles.iter() {
+		for v in [t1, t2, t3] {
+			let distance = after.vertices[v].sub(plane_pos).dot(normal);
+			if distance < -EPSILON { return Some(format!("vertex {v} is {distance} behind the plane")); }
+	}}
+	
+	// clipping can only remove surface, and leaves it alone when nothing crosses the plane
+	let (area_before, area_after) = (mesh_area(before), mesh_area(after));
+	if area_after > area_before + EPSILON*area_before.max(1.0) { return Some(format!("area grew from {area_before} to {area_after}")); }
+	let all_inside = before.vertices.iter().all(|v| v.sub(plane_pos).dot(normal) >= 0.0);
+	if all_inside && (area_after - area_before).abs() > EPSILON*area_before.max(1.0) { return Some(format!("area changed from {area_before} to {area_after} with nothing clipped")); }
+	
+	// new vertices lie on an original edge, with attributes between that edge's endpoint values. normals are compared
+	// with the corner normals of the face the edge belongs to, which differ from vertex_normals at hard edges
+	let within = |value: f32, a: f32, b: f32| value >= a.min(b) - EPSILON && value <= a.max(b) + EPSILON;
+	for v in before.vertices.len()..after.vertices.len() {
+		let point = after.vertices[v];
+		// a cut close to a shared corner can sit on several edges, any of them with consistent attributes will do
+		let edges: Vec<(usize, usize, Vector3D, Vector3D)> = before.faces().flat_map(|face| {
+			let ((t1, t2, t3), [n1, n2, n3]) = (face.vertices, face.normals);
+			[(t1, t2, n1, n2), (t2, t3, n2, n3), (t3, t1, n3, n1)]
+		}).filter(|&(a, b, _, _)| {
+			let (start, end) = (before.vertices[a], before.vertices[b]);
+			let direction = end.sub(start);
+			let fac = point.sub(start).dot(direction) / direction.dot(direction);
+			within(fac, 0.0, 1.0) && start.lerp(end, fac).sub(point).mag() < EPSILON*direction.mag().max(1.0)
+		}).collect();
+		if edges.is_empty() { return Some(format!("new vertex {v} isn't on any original edge")); }
+		let attribute_problem = |(a, b, n_a, n_b): (usize, usize, Vector3D, Vector3D)| -> Option<String> {
+			let (uv, uv_a, uv_b) = (after.tex_coords[v], before.tex_coords[a], before.tex_coords[b]);
+			if !within(uv.0, uv_a.0, uv_b.0) || !within(uv.1, uv_a.1, uv_b.1) { return Some(format!("new vertex {v} has texture coordinate {uv:?} outside {uv_a:?}..{uv_b:?}")); }
+			let n = after.vertex_normals[v];
+			if !within(n.X, n_a.X, n_b.X) || !within(n.Y, n_a.Y, n_b.Y) || !within(n.Z, n_a.Z, n_b.Z) { return Some(format!("new vertex {v} has normal {n:?} outside {n_a:?}..{n_b:?}")); }
+			let (c, c_a, c_b) = (after.vertex_colors[v].RGB, before.vertex_colors[a].RGB, before.vertex_colors[b].RGB);
+			if !within(c.0, c_a.0, c_b.0) || !within(c.1, c_a.1, c_b.1) || !within(c.2, c_a.2, c_b.2) { return Some(format!("new vertex {v} has color {c:?} outside {c_a:?}..{c_b:?}")); }
+			None
+		};
+		let problems: Vec<Option<String>> = edges.into_iter().map(attribute_problem).collect();
+		if problems.iter().all(|p| p.is_some()) { return problems[0].clone(); }
+	}
+	None
+}
+
+// clips random meshes against random planes and checks the invariants above, failing cases are printed with their
+// iteration number so they can be replayed with the same seed
+pub fn check_clipping(iterations: usize, seed: u64) -> bool {
+	let mut rng = Rng::new(seed);
+	let screen = Viewport::headless(1, 1, 1.0, Color::black());
+	println!("checking clipping: {iterations} meshes, seed {seed}");
+	let mut failures = 0;
+	for i in 0..iterations {
+		let before = random_triangles(&mut rng);
+		let (plane_pos, plane_normal) = random_plane(&mut rng);
+		let mut after = before.clone();
+		screen.clip_against_plane(&mut after, plane_pos, plane_normal);
+		if let Some(problem) = clipping_violation(&before, &after, plane_pos, plane_normal) {
+			failures += 1;
+			if failures <= 10 { println!("case {i}: {problem}"); }
+		}
+	}
+	if failures == 0 { println!("clipping checks passed!"); }else { println!("{failures} of {iterations} clipping cases failed"); }
+	failures == 0
+}
+
+
+// known answers for the vector and color math, checked with approx_eq
+pub fn check_math() -> bool {
+	let rotate = |v: Vector3D, axis: Vector3D, degrees: f32| match Transform::rotation(axis, degrees) {
+		Transform::Rotate(a, b) => v.reflect(a).reflect(b),
+		_ => unreachable!()
+	};
+	let reflect_pair = |v: Vector3D, transform: Transform| match transform {
+		Transform::Rotate(a, b) => v.reflect(a).reflect(b),
+		_ => unreachable!()
+	};
+	let spin = Transform::Rotate(Vector3D::XYZ(1.0, 0.01, -0.01), Vector3D::XYZ(1.0, 0.02, 0.0));
+	let x = Vector3D::XYZ(1.0, 0.0, 0.0);
+	let (y, z) = (Vector3D::XYZ(0.0, 1.0, 0.0), Vector3D::XYZ(0.0, 0.0, 1.0));
+	// far from the origin and with a vertex that didn't load, the old sentinel based center got both wrong
+	let far_mesh = Mesh::new(vec![Vector3D::XYZ(1000.0, -2000.0, 1000.0), Vector3D::XYZ(f32::NAN, 0.0, 0.0), Vector3D::XYZ(1002.0, -1996.0, 1006.0)], Vec::new());
+	let vectors: [(&str, Vector3D, Vector3D); 16] = [
+		("operators", x + y*0.5 - -z, Vector3D::XYZ(1.0, 0.5, 1.0)),
+		("operators match methods", 2.0*(x - y)/4.0, x.sub(y).mul(0.5)),
+		("x cross y", x.cross(y), z),
+		("y cross z", y.cross(z), x),
+		("normalize", Vector3D::XYZ(3.0, 0.0, 4.0).normalize(), Vector3D::XYZ(0.6, 0.0, 0.8)),
+		("normalize zero", Vector3D::zero().normalize(), Vector3D::zero()),
+		("reflect across x", Vector3D::XYZ(1.0, 2.0, 3.0).reflect(x), Vector3D::XYZ(-1.0, 2.0, 3.0)),
+		("lerp", x.lerp(y, 0.25), Vector3D::XYZ(0.75, 0.25, 0.0)),
+		("rotate x 90 around z", rotate(x, z, 90.0), y),
+		("rotate y 90 around x", rotate(y, x, 90.0), z),
+		("rotate x 180 around y", rotate(x, y, 180.0), Vector3D::XYZ(-1.0, 0.0, 0.0)),
+		("half of a 180 turn", reflect_pair(x, Transform::rotation(z, 180.0).scaled(0.5)), y),
+		("two halves of a turn", reflect_pair(reflect_pair(y, spin.scaled(0.5)), spin.scaled(0.5)), reflect_pair(y, spin.scaled(1.0))),
+		("array round trip", Vector3D::from(<[f32; 3]>::from(Vector3D::XYZ(1.5, -2.0, 1e6))), Vector3D::XYZ(1.5, -2.0, 1e6)),
+		("mesh center", far_mesh.center(), Vector3D::XYZ(1001.0, -1998.0, 1003.0)),
+		("bounds extent", far_mesh.bounds().extent(), Vector3D::XYZ(2.0, 4.0, 6.0))
+	];
+	let orange = Color::RGB(1.0, 0.5, 0.0);
+	let hsv = |(h, s, v): (f32, f32, f32)| Color::HSV(h, s, v);
+	let hsl = |(h, s, l): (f32, f32, f32)| Color::HSL(h, s, l);
+	let colors: [(&str, Color, Color); 14] = [
+		("operators", Color::RGB(0.5, 1.0, 0.2)*Color::RGB(0.5, 0.5, 0.5) + Color::RGB(0.1, 0.1, 0.1)*2.0, Color::RGB(0.45, 0.7, 0.3)),
+		("hadamard", Color::RGB(0.5, 1.0, 0.2).hadamard(Color::RGB(0.5, 0.5, 0.5)), Color::RGB(0.25, 0.5, 0.1)),
+		("lerp", Color::black().lerp(orange, 0.5), Color::RGB(0.5, 0.25, 0.0)),
+		("rgb8 round trip", Color::from(<[u8; 3]>::from(Color::from_rgb8([255, 128, 0]))), Color::from_rgb8([255, 128, 0])),
+		("hsv orange", Color::HSV(30.0, 1.0, 1.0), orange),
+		("hsv wraps", Color::HSV(-240.0, 0.5, 0.8), Color::HSV(120.0, 0.5, 0.8)),
+		("hsv magenta", Color::HSV(300.0, 1.0, 0.5), Color::RGB(0.5, 0.0, 0.5)),
+		("hsl blue", Color::HSL(240.0, 1.0, 0.5), Color::RGB(0.0, 0.0, 1.0)),
+		("hsl pastel", Color::HSL(0.0, 1.0, 0.75), Color::RGB(1.0, 0.5, 0.5)),
+		("hsv round trip", hsv(Color::RGB(0.2, 0.6, 0.9).to_hsv()), Color::RGB(0.2, 0.6, 0.9)),
+		("hsl round trip", hsl(Color::RGB(0.7, 0.1, 0.4).to_hsl()), Color::RGB(0.7, 0.1, 0.4)),
+		("gray round trip", hsl(Color::RGB(0.3, 0.3, 0.3).to_hsl()), Color::RGB(0.3, 0.3, 0.3)),
+		("rotate hue", orange.rotate_hue(180.0), Color::RGB(0.0, 0.5, 1.0)),
+		("desaturate", orange.scale_saturation(0.0), Color::RGB(1.0, 1.0, 1.0))
+	];
+	let mut passed = true;
+	for (name, actual, expected) in vectors {
+		if !actual.approx_eq(expected, 1e-5) { println!("{name}: got {actual:?}, expected {expected:?}"); passed = false; }
+	}
+	for (name, actual, expected) in colors {
+		if !actual.approx_eq(expected, 1e-5) { println!("{name}: got {actual:?}, expected {expected:?}"); passed = false; }
+	}
+	let scalars = [("90 degrees", 90f32.to_radians(), std::f32::consts::FRAC_PI_2), ("large values", 1e6 + 1.0, 1e6)];
+	for (name, actual, expected) in scalars {
+		if !approx_eq(actual, expected, 1e-5) { println!("{name}: got {actual}, expected {expected}"); passed = false; }
+	}
+	let plane = Aabb::around([Vector3D::zero(), x, y]).unwrap();
+	if plane.contains(z.mul(1e-4)) || !plane.padded(1e-3).contains(z.mul(1e-4)) { println!("padded bounds don't enclose a flat mesh"); passed = false; }
+	if Aabb::around([Vector3D::XYZ(f32::NAN, 0.0, 0.0)]).is_some() { println!("bounds of only non finite points should be None"); passed = false; }
+	if (x + y*2.0 + z*3.0)[2] != 3.0 || Color::RGB(0.1, 0.2, 0.3)[1] != 0.2 { println!("indexing picks the wrong component"); passed = false; }
+	if Color::RGB(1.5, -0.2, 0.5).to_rgb8() != [255, 0, 127] { println!("to_rgb8 doesn't clamp"); passed = false; }
+	// white sky over a black ground, lit from straight above it's as bright as the sky and from below it gets nothing
+	let white = Color::RGB(1.0, 1.0, 1.0);
+	let sky = Environment::Panorama(Texture::bake(&|uv: (f32, f32)| if uv.1 < 0.5 { white }else { Color::black() }, 4, 65));
+	let irradiance = Irradiance::from_environment(&sky, 32);
+	let (up, down) = (irradiance.at(y), irradiance.at(y.mul(-1.0)));
+	if !up.approx_eq(white, 0.05) || !down.approx_eq(Color::black(), 0.05) { println!("irradiance of a half sky: {up:?} up and {down:?} down"); passed = false; }
+	let uniform = Irradiance::from_environment(&Environment::Panorama(Texture::new(1, 1, vec![vec![white]])), 32).at(x);
+	if !uniform.approx_eq(white, 1e-3) { println!("irradiance of a uniform environment: got {uniform:?}, expected white"); passed = false; }
+	// everything a mesh holds has to survive the binary format, so encoding what came back gives the same bytes
+	let mut stored = Mesh::new(vec![Vector3D::zero(), x, y, z], vec![(0, 1, 2), (0, 2, 3)]);
+	(stored.tex_coords, stored.tex_tris) = (vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)], vec![(0, 1, 2), (0, 2, 1)]);
+	(stored.normals, stored.normal_tris) = (vec![z, x], vec![(0, 0, 0), (1, 1, 1)]);
+	stored.vertex_colors = vec![orange, white, Color::black(), Color::RGB(0.1, 0.2, 0.3)];
+	stored.recalculate_normals();
+	stored.material.emissive = orange;
+	stored.material.opacity_map = Some(Texture::missing(3, 2, 1));
+	let mut metal = Material::new(white, orange, white, 40.0, 0.5, LightingMode::Toon(3));
+	(metal.specular_model, metal.transparency) = (SpecularModel::BlinnPhong, Transparency::Noise);
+	metal.pbr = Some(Pbr{ metallic: 1.0, roughness: 0.3, metallic_map: None, roughness_map: Some(Texture::missing(2, 2, 1)) });
+	(stored.materials, stored.face_materials) = (vec![(metal, Texture::missing(4, 4, 2))], vec![1, 0]);
+	stored.origin = Vector3D::XYZ(0.5, 0.5, 0.5);
+	let bytes = encode_mesh(&stored);
+	match decode_mesh(&bytes) {
+		Ok(loaded) if encode_mesh(&loaded) == bytes => (),
+		Ok(_) => { println!("binary mesh round trip changed the mesh"); passed = false; },
+		Err(error) => { println!("binary mesh round trip failed: {error}"); passed = false; }
+	}
+	if decode_mesh(&bytes[..bytes.len() - 1]).is_ok() { println!("a truncated binary mesh loaded"); passed = false; }
+	if passed { println!("math checks passed!"); }
+	passed
+}
@@ -1,22 +1,174 @@
-use crate::{ Point2D, Vector3D, Color };
+use crate::{ Point2D, Vector3D, Color, Triangle };
 use crate::clamp;
-use crate::graphicsutils::{ LightSource, LightingMode, Texture, Material };
-use crate::mesh::Mesh;
+use crate::graphicsutils::{ LightSource, AmbientLight, Irradiance, cook_torrance, Environment, Fragment, FragmentShader, LightingMode, LightingCache, Texture, Material, StudioRig, Transparency };
+use crate::mesh::{ Mesh, Aabb };
+use crate::camera::Camera;
+use crate::terminalutils::{ Backend, AnsiBackend, RecordingBackend };
+use crate::displayutils::{ BAYER_4X4, DisplayMode, ColorMode, Dithering, ToneMapping, ColorFilter, luminance, CellShader, Bloom, apply_bloom, film_grain, apply_filter, resample, encode_cells, detect_terminal, tone_map, color_grade, dither, output_palette, encode_half_blocks, encode_quarter_blocks, encode_braille, encode_ascii, encode_regis, encode_sixel, encode_kitty, kitty_supported, encode_iterm, encode_png, encode_ppm, to_rgb_bytes };
 
-use std::cmp::min;
-use std::fmt::Write;
+use std::cmp::{ min, max };
+use std::hash::{ Hash, Hasher, DefaultHasher };
+use std::time::{ Duration, Instant };
+use std::sync::Arc;
+
+// fixed point precision for the deterministic rasterizer
+const SUBPIXEL_BITS: i64 = 8;
+const WEIGHT_BITS: i64 = 16;
+const DEPTH_BITS: i64 = 16;
+const FIXED_RANGE: f32 = (1 << 20) as f32; // furthest a vertex can be from the screen origin (in pixels, and in 1/z) before the integer math could overflow
+
+// triangles closer to the camera plane than this, or smaller than this on screen, are skipped
+const DEPTH_EPSILON: f32 = 1e-4;
+const AREA_EPSILON: f32 = 1e-6;
+
+// counts of triangles draw_mesh had to skip or patch up since the last clear_screen
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Diagnostics {
+	pub non_finite: usize, // NaN or infinite positions
+	pub near_zero_depth: usize, // would divide by ~0 in project and perspective correction
+	pub zero_area: usize,
+	pub bad_normals: usize // NaN normals replaced with the face normal, or zero if that's broken too
+}
+
+// what draw_mesh does with the triangles left once a frame runs over its budget
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Overrun {
+	Partial, // stop drawing, the frame shows whatever made it in
+	Wireframe // outline the rest, cheap enough that the whole mesh stays visible
+}
+
+// frames that stay under half the budget this many times in a row bring back antialiasing and post effects
+const CALM_FRAMES: u32 = 10;
+
+// hard time limit per frame, so one heavy mesh coming into view can't stall the terminal for seconds. a frame runs from
+// clear_screen (or the first draw_mesh after a display) to display
+#[derive(Copy, Clone, Debug)]
+pub struct Watchdog {
+	pub budget: Duration,
+	pub overrun: Overrun,
+	pub overruns: u64, // frames that went over budget since the watchdog was set
+	started: Option<Instant>,
+	tripped: bool, // the current frame is over budget
+	calm: u32 // frames in a row well under budget since the last overrun
+}
+
+impl Watchdog {
+	pub fn new(budget: Duration, overrun: Overrun) -> Watchdog {
+		Watchdog{ budget, overrun, overruns: 0, started: None, tripped: false, calm: CALM_FRAMES }
+	}
+	
+	// this frame or one of the last few went over budget, draw_frame and display skip antialiasing, bloom and grain
+	pub fn degraded(&self) -> bool { self.tripped || self.calm < CALM_FRAMES }
+	
+	fn start(&mut self) {
+		(self.started, self.tripped) = (Some(Instant::now()), false);
+	}
+	
+	fn over_budget(&mut self) -> bool {
+		let started = *self.started.get_or_insert_with(Instant::now);
+		self.tripped = self.tripped || started.elapsed() > self.budget;
+		self.tripped
+	}
+	
+	// what to do with triangle tri of a mesh, None while there's time left. reading the clock for every triangle
+	// would cost more than it saves
+	fn overrun_at(&mut self, tri: usize) -> Option<Overrun> {
+		if self.tripped || (tri % 64 == 0 && self.over_budget()) { Some(self.overrun) }else { None }
+	}
+	
+	fn finish(&mut self) {
+		let elapsed = self.started.map_or(Duration::ZERO, |started| started.elapsed());
+		if self.tripped || elapsed > self.budget {
+			self.overruns += 1;
+			self.calm = 0;
+		}else if elapsed < self.budget/2 {
+			self.calm = min(self.calm + 1, CALM_FRAMES);
+		}
+		(self.started, self.tripped) = (None, false);
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rasterizer {
+	Float,
+	FixedPoint // integer coordinates, edge functions and depth so coverage and depth tests are bit exact on every platform
+}
+
+// how draw_mesh decides which surface ends up in front
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Visibility {
+	DepthBuffer,
+	// triangles of each draw_mesh call are sorted back to front by average depth and painted over each other without
+	// a per pixel depth test, for targets where that's too slow. intersecting triangles and long ones overlapping short
+	// ones come out wrong, and meshes have to be drawn back to front by the caller
+	Painter
+}
+
+// what apply_phong_shader writes, everything except Full is for inspecting materials
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShadingView {
+	Full,
+	Diffuse, // diffuse light only, no ambient or highlights
+	Specular,
+	Normals // surface normal mapped from -1..1 to 0..1 as rgb
+}
+
+// exact buffer values under the loupe's center pixel
+#[derive(Copy, Clone, Debug)]
+pub struct LoupeSample {
+	pub position: (usize, usize),
+	pub color: Color, // hdr, before tone mapping
+	pub depth: f32, // raw depth buffer value, 999 where nothing was drawn
+	pub normal: Vector3D
+}
+
+// what trace_frame records, triangle indices count within each mesh so a trace can cover the same triangle of several meshes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TraceTarget {
+	Pixel(usize, usize),
+	Triangle(usize)
+}
+
+// pipeline intermediates collected for one frame, one line per step
+struct FrameTrace {
+	target: TraceTarget,
+	path: String,
+	lines: Vec<String>,
+	mesh: usize, // draw_mesh calls so far and the triangle being drawn, for labelling fragments
+	triangle: usize
+}
+
+impl FrameTrace {
+	fn covers(&self, pos: (usize, usize)) -> bool {
+		match self.target {
+			TraceTarget::Pixel(x, y) => pos == (x, y),
+			TraceTarget::Triangle(t) => t == self.triangle
+		}
+	}
+}
+
+// how composite blends between two rendered views
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transition {
+	Crossfade,
+	Wipe // the second view slides in from the left edge
+}
 
 #[derive(Copy, Clone)]
 struct Vertex {
 	screen_XY: Point2D,
 	texture_UV: Point2D,
 	normal: Vector3D,
-	z_coord: f32
+	position: Vector3D, // world space, for effects that depend on where a fragment is rather than where it lands on screen
+	z_coord: f32,
+	color: Color, // per vertex color, white unless the mesh has vertex colors
+	light: Option<Color>, // precomputed lighting from a mesh's lighting cache, shaded per pixel when missing
+	tangents: (Vector3D, Vector3D) // world space directions of increasing u and v, per face and only set for normal mapped materials
 }
 
 impl Vertex {
 	fn new(screen_XY: Point2D, texture_UV: Point2D, z_coord: f32, normal: Vector3D) -> Vertex {
-		Vertex { screen_XY, texture_UV, z_coord, normal }
+		Vertex { screen_XY, texture_UV, z_coord, normal, position: Vector3D::zero(), color: Color::RGB(1.0, 1.0, 1.0), light: None, tangents: (Vector3D::zero(), Vector3D::zero()) }
 	}
 	
 	// apply barycentric interpolation
@@ -30,72 +182,594 @@ impl Vertex {
 				(a*self.texture_UV.0/self.z_coord + b*p2.texture_UV.0/p2.z_coord + c*p3.texture_UV.0/p3.z_coord) / inv_z,
 				(a*self.texture_UV.1/self.z_coord + b*p2.texture_UV.1/p2.z_coord + c*p3.texture_UV.1/p3.z_coord) / inv_z
 			),
-			normal: self.normal.mul(a/self.z_coord).add(p2.normal.mul(b/p2.z_coord)).add(p3.normal.mul(c/p3.z_coord)).div(inv_z)
+			normal: self.normal.mul(a/self.z_coord).add(p2.normal.mul(b/p2.z_coord)).add(p3.normal.mul(c/p3.z_coord)).div(inv_z),
+			position: self.position.mul(a/self.z_coord).add(p2.position.mul(b/p2.z_coord)).add(p3.position.mul(c/p3.z_coord)).div(inv_z),
+			color: self.color.mul(a/self.z_coord).add(p2.color.mul(b/p2.z_coord)).add(p3.color.mul(c/p3.z_coord)).mul(1.0/inv_z),
+			light: match (self.light, p2.light, p3.light) {
+				(Some(l1), Some(l2), Some(l3)) => Some(l1.mul(a/self.z_coord).add(l2.mul(b/p2.z_coord)).add(l3.mul(c/p3.z_coord)).mul(1.0/inv_z)),
+				_ => None
+			},
+			tangents: self.tangents
 		}
 	}
 }
 
+// how much each phong term adds, ambient*0.2 once (lit by the viewport's ambient light) plus diffuse*0.4 + specular*0.6
+// per light unless changed. a bright light can push a pixel past 1, tone_mapping decides what happens then
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShadingWeights {
+	pub ambient: f32,
+	pub diffuse: f32,
+	pub specular: f32
+}
+
+impl ShadingWeights {
+	pub fn new(ambient: f32, diffuse: f32, specular: f32) -> ShadingWeights { ShadingWeights{ ambient, diffuse, specular } }
+}
+
+// shadows flattened onto a ground plane, a cheap stand in for shadow maps. light points at the light like
+// LightSource positions do, darkness 0.5 halves whatever the shadow lands on
+#[derive(Copy, Clone, Debug)]
+pub struct PlanarShadow {
+	pub point: Vector3D,
+	pub normal: Vector3D,
+	pub light: Vector3D,
+	pub darkness: f32
+}
+
+impl PlanarShadow {
+	pub fn new(point: Vector3D, normal: Vector3D, light: Vector3D, darkness: f32) -> PlanarShadow { PlanarShadow{ point, normal, light, darkness } }
+}
+
+// toon outlines go where the distance to the camera jumps by more than this fraction between neighbouring pixels, or
+// where the normals are further apart than this cosine
+const TOON_DEPTH_STEP: f32 = 0.05;
+const TOON_CREASE: f32 = 0.5;
+
+// shadows are raised this far off their plane so they win the depth test against the ground they lie on
+const SHADOW_LIFT: f32 = 1e-3;
+
+// rows of directions set_image_based_ambient integrates the environment over, twice as many columns
+const IRRADIANCE_SAMPLES: usize = 32;
+
+// everything a quality preset controls, see Quality
+#[derive(Copy, Clone)]
+pub struct QualitySettings {
+	pub render_scale: f32, // internal resolution relative to the size the viewport was created with, frames are scaled back up for display
+	pub smooth_shading: bool, // false draws smooth materials with flat shading
+	pub aa_samples: usize, // jittered passes per frame in draw_frame
+	pub bloom: Option<Bloom>,
+	pub grain: f32,
+	pub dithering: Dithering
+}
+
+#[derive(Copy, Clone)]
+pub enum Quality {
+	Low,
+	Medium, // the defaults a new viewport starts with
+	High,
+	Custom(QualitySettings)
+}
+
+impl Quality {
+	pub fn settings(&self) -> QualitySettings {
+		match self {
+			Quality::Low => QualitySettings{ render_scale: 0.5, smooth_shading: false, aa_samples: 1, bloom: None, grain: 0.0, dithering: Dithering::None },
+			Quality::Medium => QualitySettings{ render_scale: 1.0, smooth_shading: true, aa_samples: 1, bloom: None, grain: 0.0, dithering: Dithering::None },
+			Quality::High => QualitySettings{ render_scale: 1.0, smooth_shading: true, aa_samples: 4, bloom: Some(Bloom::new(1.0, 0.5, 0.5, 2)), grain: 0.0, dithering: Dithering::Bayer },
+			Quality::Custom(settings) => *settings
+		}
+	}
+}
+
+fn halton(index: usize, base: usize) -> f32 {
+	let (mut result, mut fac, mut i) = (0.0, 1.0, index);
+	while i > 0 {
+		fac /= base as f32;
+		result += fac * (i % base) as f32;
+		i /= base;
+	}
+	result
+}
+
 pub struct Viewport {
 	width: usize,
 	height: usize,
 	focal_length: f32,
 	pixel_buffer: Vec<Vec<Color>>,
 	depth_buffer: Vec<Vec<f32>>,
+	normal_buffer: Vec<Vec<Vector3D>>,
 	pub lights: Vec<LightSource>,
-	bg_color: Color
+	pub ambient: AmbientLight,
+	active_lights: Vec<usize>, // the lights that reach the triangle being drawn, see pick_lights
+	toon_pixels: Vec<(usize, usize)>, // written by toon materials in the current draw_mesh, outlined at the end of it
+	mesh_shader: Option<Arc<dyn FragmentShader>>, // Mesh.shader of the mesh being drawn
+	pub camera: Camera,
+	pub display_mode: DisplayMode,
+	pub color_mode: ColorMode,
+	pub dithering: Dithering,
+	pub ink: bool, // the 1 bit display modes mark dark pixels instead of lit ones, for e-ink screens and printing
+	pub rasterizer: Rasterizer,
+	pub visibility: Visibility,
+	pub shading_view: ShadingView,
+	pub shading_weights: ShadingWeights,
+	pub tone_mapping: ToneMapping,
+	pub exposure: f32,
+	pub contrast: f32,
+	pub white_balance: Color, // per channel gain, 1.0 leaves a channel alone
+	pub filter: ColorFilter,
+	pub bloom: Option<Bloom>,
+	pub grain: f32, // film grain strength, 0 turns it off
+	pub seed: u64, // grain is seeded from this and the frame count, so the same seed always gives the same footage
+	pub time: f32, // seconds into the animation, what vertex shaders animate by
+	frame: u64,
+	pub diagnostics: Diagnostics,
+	pub log_degenerate: bool, // print every skipped triangle, not just count them
+	pub watchdog: Option<Watchdog>, // None lets frames take as long as they take
+	trace: Option<FrameTrace>,
+	pub pixel_aspect: f32, // width/height of one output pixel, see set_cell_size
+	pub jitter: Point2D, // sub pixel offset added to every projected point, driven by accumulate
+	pub output_size: Option<(usize, usize)>, // resolution frames are resampled to when displayed, None shows the internal buffer as is
+	pub cell_shader: Option<Box<dyn CellShader>>, // replaces the display mode, one pixel per character picked by the shader
+	pub backend: Box<dyn Backend>, // where display() sends frames, stdout unless replaced
+	frame_buffer: String,
+	vector_lines: Vec<(Point2D, Point2D, Color)>, // draw_line calls since the last clear, only recorded for DisplayMode::Regis
+	pub quality: QualitySettings, // change through set_quality, the resolution part only takes effect there
+	base_size: (usize, usize), // size and focal length from new(), what render_scale is relative to
+	base_focal_length: f32,
+	bg_color: Color,
+	pub environment: Option<Environment> // drawn behind everything in place of bg_color
+}
+
+// named setters for everything Viewport::new takes and the options usually set right after it, unset options keep
+// the constructor's defaults. the terminal is only probed with detect_terminal
+pub struct ViewportBuilder {
+	size: (usize, usize),
+	focal_length: Option<f32>,
+	fov: Option<f32>,
+	bg_color: Color,
+	environment: Option<Environment>,
+	image_based_ambient: bool,
+	headless: bool,
+	detect_terminal: bool,
+	display_mode: Option<DisplayMode>,
+	color_mode: Option<ColorMode>,
+	quality: Option<Quality>,
+	dithering: Option<Dithering>,
+	filter: Option<ColorFilter>,
+	tone_mapping: Option<(ToneMapping, f32)>,
+	grade: Option<(f32, f32)>,
+	ink: bool,
+	seed: Option<u64>,
+	rasterizer: Option<Rasterizer>,
+	visibility: Option<Visibility>,
+	shading_weights: Option<ShadingWeights>,
+	studio: Option<StudioRig>,
+	lights: Vec<LightSource>,
+	ambient: Option<AmbientLight>,
+	camera: Option<Camera>,
+	cell_shader: Option<Box<dyn CellShader>>,
+	cell_size: Option<(f32, f32)>,
+	watchdog: Option<Watchdog>
+}
+
+impl ViewportBuilder {
+	pub fn size(mut self, width: usize, height: usize) -> ViewportBuilder { self.size = (width, height); self }
+	pub fn focal_length(mut self, focal_length: f32) -> ViewportBuilder { self.focal_length = Some(focal_length); self }
+	// full view angle in degrees across the narrower side, replaces focal_length
+	pub fn fov(mut self, degrees: f32) -> ViewportBuilder { self.fov = Some(degrees); self }
+	pub fn background(mut self, color: Color) -> ViewportBuilder { self.bg_color = color; self }
+	pub fn environment(mut self, environment: Environment) -> ViewportBuilder { self.environment = Some(environment); self }
+	pub fn image_based_ambient(mut self) -> ViewportBuilder { self.image_based_ambient = true; self }
+	pub fn headless(mut self) -> ViewportBuilder { self.headless = true; self }
+	// ask the terminal which display and color mode it supports, this writes a query to it and reads the reply
+	pub fn detect_terminal(mut self) -> ViewportBuilder { self.detect_terminal = true; self }
+	pub fn display_mode(mut self, mode: DisplayMode) -> ViewportBuilder { self.display_mode = Some(mode); self }
+	pub fn color_mode(mut self, mode: ColorMode) -> ViewportBuilder { self.color_mode = Some(mode); self }
+	pub fn quality(mut self, quality: Quality) -> ViewportBuilder { self.quality = Some(quality); self }
+	pub fn dithering(mut self, dithering: Dithering) -> ViewportBuilder { self.dithering = Some(dithering); self }
+	pub fn filter(mut self, filter: ColorFilter) -> ViewportBuilder { self.filter = Some(filter); self }
+	// exposure scales the hdr frame before the operator, 1.0 leaves it alone
+	pub fn tone_mapping(mut self, operator: ToneMapping, exposure: f32) -> ViewportBuilder { self.tone_mapping = Some((operator, exposure)); self }
+	// contrast around middle gray and a white balance in kelvin, see set_white_balance
+	pub fn grade(mut self, contrast: f32, kelvin: f32) -> ViewportBuilder { self.grade = Some((contrast, kelvin)); self }
+	pub fn ink(mut self, ink: bool) -> ViewportBuilder { self.ink = ink; self }
+	pub fn deterministic(mut self, seed: u64) -> ViewportBuilder { self.seed = Some(seed); self }
+	pub fn rasterizer(mut self, rasterizer: Rasterizer) -> ViewportBuilder { self.rasterizer = Some(rasterizer); self }
+	pub fn visibility(mut self, visibility: Visibility) -> ViewportBuilder { self.visibility = Some(visibility); self }
+	pub fn shading_weights(mut self, weights: ShadingWeights) -> ViewportBuilder { self.shading_weights = Some(weights); self }
+	pub fn studio(mut self, rig: StudioRig) -> ViewportBuilder { self.studio = Some(rig); self }
+	pub fn light(mut self, light: LightSource) -> ViewportBuilder { self.lights.push(light); self }
+	pub fn ambient(mut self, color: Color, intensity: f32) -> ViewportBuilder { self.ambient = Some(AmbientLight::new(color, intensity)); self }
+	pub fn camera(mut self, camera: Camera) -> ViewportBuilder { self.camera = Some(camera); self }
+	pub fn cell_shader(mut self, shader: Box<dyn CellShader>) -> ViewportBuilder { self.cell_shader = Some(shader); self }
+	// the terminal font's cell width and height, see Viewport::set_cell_size
+	pub fn cell_size(mut self, width: f32, height: f32) -> ViewportBuilder { self.cell_size = Some((width, height)); self }
+	pub fn frame_budget(mut self, budget: Duration, overrun: Overrun) -> ViewportBuilder { self.watchdog = Some(Watchdog::new(budget, overrun)); self }
+	
+	// explicit settings win over what quality, deterministic and studio would set
+	pub fn build(self) -> Viewport {
+		let (width, height) = self.size;
+		let focal_length = match (self.fov, self.focal_length) {
+			(Some(fov), _) => 0.5*min(width, height) as f32/(0.5*fov.to_radians()).tan(),
+			(None, Some(focal_length)) => focal_length,
+			(None, None) => 0.75*width as f32 // the 120 over 160 pixels the demos use
+		};
+		let mut viewport = if self.headless { Viewport::headless(width, height, focal_length, self.bg_color) }
+			else { Viewport::new(width, height, focal_length, self.bg_color) };
+		if self.detect_terminal && !self.headless { (viewport.display_mode, viewport.color_mode) = detect_terminal(); }
+		if let Some(quality) = self.quality { viewport.set_quality(quality); }
+		if let Some(seed) = self.seed { viewport.set_deterministic(seed); }
+		if let Some(rig) = self.studio { viewport.set_studio(rig); }
+		viewport.lights.extend(self.lights);
+		if let Some(ambient) = self.ambient { viewport.ambient = ambient; }
+		if let Some(mode) = self.display_mode { viewport.display_mode = mode; }
+		if let Some(mode) = self.color_mode { viewport.color_mode = mode; }
+		if let Some(dithering) = self.dithering { viewport.dithering = dithering; }
+		if let Some(filter) = self.filter { viewport.filter = filter; }
+		if let Some((operator, exposure)) = self.tone_mapping { (viewport.tone_mapping, viewport.exposure) = (operator, exposure); }
+		if let Some((contrast, kelvin)) = self.grade {
+			viewport.contrast = contrast;
+			viewport.set_white_balance(kelvin);
+		}
+		viewport.ink = self.ink;
+		if let Some(rasterizer) = self.rasterizer { viewport.rasterizer = rasterizer; }
+		if let Some(visibility) = self.visibility { viewport.visibility = visibility; }
+		if let Some(weights) = self.shading_weights { viewport.shading_weights = weights; }
+		if let Some(camera) = self.camera { viewport.camera = camera; }
+		viewport.cell_shader = self.cell_shader;
+		// after the display mode and cell shader, which decide how many pixels share a cell
+		if let Some((width, height)) = self.cell_size { viewport.set_cell_size(width, height); }
+		viewport.watchdog = self.watchdog;
+		viewport.environment = self.environment;
+		if viewport.environment.is_some() { viewport.clear_buffers(); }
+		if self.image_based_ambient { viewport.set_image_based_ambient(); }
+		viewport
+	}
 }
 
 impl Viewport {
+	// 160x120 in half blocks and truecolor, see ViewportBuilder
+	pub fn builder() -> ViewportBuilder {
+		ViewportBuilder {
+			size: (160, 120), focal_length: None, fov: None, bg_color: Color::black(), environment: None, image_based_ambient: false, headless: false, detect_terminal: false,
+			display_mode: None, color_mode: None, quality: None, dithering: None, filter: None, tone_mapping: None, grade: None, ink: false, seed: None, rasterizer: None, visibility: None, shading_weights: None,
+			studio: None, lights: Vec::new(), ambient: None, camera: None, cell_shader: None, cell_size: None, watchdog: None
+		}
+	}
+	
+	// draws to stdout in half blocks and truecolor without asking the terminal, see ViewportBuilder::detect_terminal
 	pub fn new(width: usize, height: usize, focal_length: f32, bg_color: Color) -> Viewport {
+		let mut viewport = Viewport::headless(width, height, focal_length, bg_color);
+		viewport.backend = Box::new(AnsiBackend::new());
+		viewport
+	}
+	
+	// pure software renderer that never probes or writes to the terminal, display() frames end up in a RecordingBackend
+	// and the finished image is available through rgb_bytes, graded_pixels or screenshot
+	pub fn headless(width: usize, height: usize, focal_length: f32, bg_color: Color) -> Viewport {
 		let (mut pixel_buffer, mut depth_buffer) = (Vec::new(), Vec::new());
-		for i in 0..height {
+		let normal_buffer = vec![vec![Vector3D::zero(); width]; height];
+		for _ in 0..height {
 			pixel_buffer.push(vec![bg_color; width]);
 			depth_buffer.push(vec![999.0; width]);
 		}
-		Viewport { width, height, focal_length, pixel_buffer, depth_buffer, bg_color, lights: Vec::new() }
+		Viewport {
+			width, height, focal_length, pixel_buffer, depth_buffer, normal_buffer, bg_color,
+			environment: None,
+			lights: Vec::new(),
+			ambient: AmbientLight::new(Color::RGB(1.0, 1.0, 1.0), 1.0),
+			active_lights: Vec::new(),
+			toon_pixels: Vec::new(),
+			mesh_shader: None,
+			camera: Camera::new(),
+			display_mode: DisplayMode::HalfBlock,
+			color_mode: ColorMode::TrueColor,
+			dithering: Dithering::None,
+			ink: false,
+			rasterizer: Rasterizer::Float,
+			visibility: Visibility::DepthBuffer,
+			shading_view: ShadingView::Full,
+			shading_weights: ShadingWeights::new(0.2, 0.4, 0.6),
+			tone_mapping: ToneMapping::Clamp,
+			exposure: 1.0,
+			contrast: 1.0,
+			white_balance: Color::RGB(1.0, 1.0, 1.0),
+			filter: ColorFilter::None,
+			bloom: None,
+			grain: 0.0,
+			seed: 0,
+			time: 0.0,
+			frame: 0,
+			diagnostics: Diagnostics::default(),
+			log_degenerate: false,
+			watchdog: None,
+			trace: None,
+			pixel_aspect: 1.0,
+			jitter: (0.0, 0.0),
+			output_size: None,
+			cell_shader: None,
+			backend: Box::new(RecordingBackend::new(None)),
+			frame_buffer: String::with_capacity(width*height*20), // roughly what a half block frame of mostly distinct colors takes
+			vector_lines: Vec::new(),
+			quality: Quality::Medium.settings(),
+			base_size: (width, height),
+			base_focal_length: focal_length
+		}
+	}
+	
+	// warm/cool white balance from a color temperature, 6500K is neutral
+	pub fn set_white_balance(&mut self, kelvin: f32) {
+		let shift = clamp(-1.0, 1.0, (6500.0 - kelvin)/4000.0);
+		self.white_balance = Color::RGB(1.0 + 0.3*shift, 1.0, 1.0 - 0.3*shift);
+	}
+	
+	// derive the pixel aspect ratio from the terminal font's cell size (in any unit, only the ratio matters)
+	// half blocks split each cell into 2 pixels stacked vertically, quarter blocks and ascii into 2x2, braille into 2x4,
+	// the bitmap modes draw square pixels
+	pub fn set_cell_size(&mut self, cell_width: f32, cell_height: f32) {
+		if self.cell_shader.is_some() {
+			self.pixel_aspect = cell_width / cell_height;
+			return;
+		}
+		self.pixel_aspect = match self.display_mode {
+			DisplayMode::HalfBlock => cell_width / (cell_height*0.5),
+			DisplayMode::QuarterBlock | DisplayMode::Ascii => cell_width / cell_height,
+			DisplayMode::Braille => (cell_width*0.5) / (cell_height*0.25),
+			_ => 1.0
+		};
+	}
+	
+	// resize to fill the backend's terminal, one row short so the cursor doesn't scroll the frame. the field of view is
+	// kept. bitmap modes aren't drawn in cells and headless viewports have no terminal, both keep their size
+	pub fn fit_terminal(&mut self) {
+		let Some((columns, rows)) = self.backend.size() else { return; };
+		let (cell_width, cell_height) = match self.display_mode {
+			_ if self.cell_shader.is_some() => (1, 1),
+			DisplayMode::HalfBlock => (1, 2),
+			DisplayMode::QuarterBlock | DisplayMode::Ascii => (2, 2),
+			DisplayMode::Braille => (2, 4),
+			_ => return
+		};
+		let (width, height) = (max(columns, 1)*cell_width, max(rows, 2).saturating_sub(1)*cell_height);
+		self.base_focal_length *= width as f32/self.base_size.0 as f32;
+		self.base_size = (width, height);
+		self.apply_render_scale();
+	}
+
+	// bit exact output for regression tests: integer rasterization and grain that only depends on the seed and frame index
+	pub fn set_deterministic(&mut self, seed: u64) {
+		self.rasterizer = Rasterizer::FixedPoint;
+		self.seed = seed;
+		self.frame = 0;
+		self.jitter = (0.0, 0.0);
+	}
+	
+	// light ambient by the environment, brighter on the sides facing its brighter parts and tinted by its colors, instead
+	// of evenly. goes back to the flat ambient color without an environment
+	pub fn set_image_based_ambient(&mut self) {
+		self.ambient.irradiance = self.environment.as_ref().map(|environment| Irradiance::from_environment(environment, IRRADIANCE_SAMPLES));
+	}
+	
+	// swap the lights for a studio rig and clear to its backdrop color
+	pub fn set_studio(&mut self, rig: StudioRig) {
+		self.lights = rig.lights();
+		self.bg_color = rig.backdrop();
+		self.clear_screen();
+	}
+	
+	// switch every quality related setting at once, changing the render scale resizes the buffers and clears them
+	pub fn set_quality(&mut self, quality: Quality) {
+		let settings = quality.settings();
+		self.quality = settings;
+		self.apply_render_scale();
+		self.bloom = settings.bloom;
+		self.grain = settings.grain;
+		self.dithering = settings.dithering;
+	}
+	
+	// buffer size and focal length from base_size and the quality's render scale
+	fn apply_render_scale(&mut self) {
+		let scale = if self.quality.render_scale.is_finite() && self.quality.render_scale > 0.0 { self.quality.render_scale }else { 1.0 };
+		let (width, height) = (max((self.base_size.0 as f32*scale).round() as usize, 1), max((self.base_size.1 as f32*scale).round() as usize, 1));
+		if (width, height) != (self.width, self.height) {
+			(self.width, self.height) = (width, height);
+			self.focal_length = self.base_focal_length*width as f32/self.base_size.0 as f32;
+			self.clear_screen();
+		}
+		self.output_size = if (width, height) == self.base_size { None }else { Some(self.base_size) };
 	}
 	
 	pub fn clear_screen(&mut self) {
+		if let Some(watchdog) = &mut self.watchdog { watchdog.start(); }
+		self.clear_buffers();
+	}
+	
+	fn clear_buffers(&mut self) {
 		let (mut new_pix, mut new_z) = (Vec::new(), Vec::new());
-		for i in 0..self.height {
+		for _ in 0..self.height {
 			new_pix.push(vec![self.bg_color; self.width]);
 			new_z.push(vec![999.0; self.width]);
 		}
 		self.pixel_buffer = new_pix;
-		self.depth_buffer = new_z
+		self.depth_buffer = new_z;
+		self.normal_buffer = vec![vec![Vector3D::zero(); self.width]; self.height];
+		self.vector_lines.clear();
+		self.diagnostics = Diagnostics::default();
+		if self.environment.is_some() { self.draw_environment(); }
 	}
 	
-	pub fn display(&self) {
-		let mut buf = String::new();
-		for h in (0..self.height).step_by(2) {
+	// every pixel gets the environment in the direction it looks, by undoing project for a point one unit ahead
+	fn draw_environment(&mut self) {
+		let Some(environment) = &self.environment else { return; };
+		let camera = self.camera;
+		for h in 0..self.height {
 			for w in 0..self.width {
-				let (R_t, G_t, B_t) = self.pixel_buffer[h][w].to_24bit();
-				let (R_b, G_b, B_b) = self.pixel_buffer[h+1][w].to_24bit();
-				write!(&mut buf, "\x1b[38;2;{R_t};{G_t};{B_t}m\x1b[48;2;{R_b};{G_b};{B_b}m▀\x1b[0m");
+				let x = -(w as f32 - self.width as f32*0.5 - self.jitter.0)*self.pixel_aspect/self.focal_length;
+				let y = -(h as f32 - self.height as f32*0.5 - self.jitter.1)/self.focal_length;
+				self.pixel_buffer[h][w] = environment.sample(camera.right().mul(x).add(camera.up.mul(y)).add(camera.forward));
+		}}
+	}
+	
+	// read access to the raw (hdr, not yet tone mapped) color buffer, indexed [row][column]
+	pub fn pixels(&self) -> &Vec<Vec<Color>> { &self.pixel_buffer }
+	
+	// the interpolated 1/z of whatever was drawn at each pixel (smaller is in front), 999 where nothing was
+	pub fn depth(&self) -> &Vec<Vec<f32>> { &self.depth_buffer }
+	
+	// width and height of the buffers, the internal resolution rather than what display() scales it to
+	pub fn size(&self) -> (usize, usize) { (self.width, self.height) }
+	
+	// the frame string is kept between calls so its allocation is reused, and handed to the backend in one piece
+	pub fn display(&mut self) {
+		let mut buf = std::mem::take(&mut self.frame_buffer);
+		buf.clear();
+		self.write_frame(&mut buf);
+		buf.push('\n');
+		let _ = self.backend.write_frame(&buf);
+		self.frame_buffer = buf;
+		self.frame += 1;
+		if let Some(watchdog) = &mut self.watchdog { watchdog.finish(); }
+		if let Err(error) = self.finish_trace() { println!("error: couldn't write frame trace: {error}"); }
+	}
+	
+	// record every intermediate value for target (post transform vertices, barycentrics, texels, light terms) until the
+	// next display() or finish_trace() writes them to path. pair with set_deterministic to get the same dump every run
+	pub fn trace_frame(&mut self, target: TraceTarget, path: &str) {
+		self.trace = Some(FrameTrace{ target, path: path.to_string(), lines: Vec::new(), mesh: 0, triangle: 0 });
+	}
+	
+	// write out and stop the current trace, does nothing if there isn't one
+	pub fn finish_trace(&mut self) -> std::io::Result<()> {
+		let Some(trace) = self.trace.take() else { return Ok(()); };
+		let header = format!("frame {} trace of {:?}, {} lines\n", self.frame, trace.target, trace.lines.len());
+		std::fs::write(&trace.path, header + &trace.lines.join("\n") + "\n")
+	}
+	
+	// the frame display() would print, for embedding in other tui code or checking output without touching stdout
+	pub fn render_to_string(&self) -> String {
+		let mut buf = String::new();
+		self.write_frame(&mut buf);
+		buf
+	}
+	
+	// save the frame at the internal resolution, with the same post processing as display() but no dithering
+	// paths ending in .ppm get a binary ppm, anything else a png
+	pub fn screenshot(&self, path: &str) -> std::io::Result<()> {
+		let frame = self.graded_pixels();
+		std::fs::write(path, if path.ends_with(".ppm") { encode_ppm(&frame) }else { encode_png(&frame) })
+	}
+	
+	// the buffer after post processing, what display() shows before it gets dithered and encoded
+	pub fn graded_pixels(&self) -> Vec<Vec<Color>> { self.post_process(self.pixel_buffer.clone()) }
+	
+	// final 8 bit rgb image, row by row with no padding, for handing frames to other applications
+	pub fn rgb_bytes(&self) -> Vec<u8> { to_rgb_bytes(&self.graded_pixels()) }
+	
+	// bloom, tone mapping, grading, grain and filters, everything between the hdr buffer and the output encoding
+	// a degraded watchdog drops bloom and grain, the costly ones
+	fn post_process(&self, frame: Vec<Vec<Color>>) -> Vec<Vec<Color>> {
+		let degraded = self.watchdog.is_some_and(|watchdog| watchdog.degraded());
+		let frame = match self.bloom {
+			Some(bloom) if !degraded => apply_bloom(&frame, bloom),
+			_ => frame
+		};
+		let frame = tone_map(&frame, self.tone_mapping, self.exposure);
+		let frame = color_grade(&frame, self.contrast, self.white_balance);
+		let frame = if self.grain > 0.0 && !degraded { film_grain(&frame, self.grain, self.seed.wrapping_add(self.frame)) }else { frame };
+		apply_filter(&frame, self.filter)
+	}
+	
+	// tone map, grade, dither and encode the pixel buffer for the current display mode
+	fn write_frame(&self, buf: &mut String) {
+		let (palette, step) = output_palette(self.display_mode, self.color_mode);
+		// rendering resolution is independent of the terminal, cap it on huge terminals or supersample on small ones
+		let frame = match (self.output_size, &self.cell_shader) {
+			(Some((width, height)), None) => resample(&self.pixel_buffer, width, height),
+			_ => self.pixel_buffer.clone() // cell shaders need the frame to line up with the depth and normal buffers
+		};
+		let frame = dither(&self.post_process(frame), self.dithering, palette, step);
+		if let Some(shader) = &self.cell_shader {
+			return encode_cells(buf, &frame, &self.depth_buffer, &self.normal_buffer, shader.as_ref(), self.color_mode);
+		}
+		match self.display_mode {
+			DisplayMode::HalfBlock => encode_half_blocks(buf, &frame, self.color_mode),
+			DisplayMode::Sixel => encode_sixel(buf, &frame),
+			DisplayMode::Kitty => if kitty_supported() { encode_kitty(buf, &frame) }else { encode_half_blocks(buf, &frame, self.color_mode) },
+			DisplayMode::Iterm => encode_iterm(buf, &frame),
+			DisplayMode::QuarterBlock => encode_quarter_blocks(buf, &frame, self.color_mode),
+			DisplayMode::Braille => encode_braille(buf, &frame, self.ink),
+			DisplayMode::Ascii => encode_ascii(buf, &frame, self.ink),
+			DisplayMode::Regis => encode_regis(buf, &self.vector_lines, self.width, self.height)
+		}
+	}
+	
+	// render the same frame several times with the projection nudged by less than a pixel and average them,
+	// antialiased stills out of the normal rasterizer. draw gets a cleared viewport once per sample, the samples
+	// share one watchdog budget and the ones left when it runs out are dropped
+	pub fn accumulate<F: FnMut(&mut Viewport)>(&mut self, samples: usize, mut draw: F) {
+		let mut accumulated = vec![vec![Color::black(); self.width]; self.height];
+		if let Some(watchdog) = &mut self.watchdog { watchdog.start(); }
+		let mut drawn = 0;
+		for s in 0..samples {
+			// halton sequence spreads the offsets evenly over the pixel for any sample count
+			self.jitter = (halton(s+1, 2) - 0.5, halton(s+1, 3) - 0.5);
+			self.clear_buffers();
+			draw(self);
+			for h in 0..self.height {
+				for w in 0..self.width { accumulated[h][w] = accumulated[h][w].add(self.pixel_buffer[h][w]); }
 			}
-			writeln!(&mut buf, "");
+			drawn += 1;
+			if self.watchdog.as_mut().is_some_and(|watchdog| watchdog.over_budget()) { break; }
 		}
-		println!("{buf}");
+		self.jitter = (0.0, 0.0);
+		let fac = 1.0 / drawn.max(1) as f32;
+		self.pixel_buffer = accumulated.iter().map(|row| row.iter().map(|c| c.mul(fac)).collect()).collect();
 	}
 	
-	fn project(&self, vector: Vector3D) -> Point2D {
+	// clear and draw a frame with the antialiasing the quality settings ask for
+	pub fn draw_frame<F: FnMut(&mut Viewport)>(&mut self, mut draw: F) {
+		if self.quality.aa_samples > 1 && !self.watchdog.is_some_and(|watchdog| watchdog.degraded()) { return self.accumulate(self.quality.aa_samples, draw); }
+		self.clear_screen();
+		draw(self);
+	}
+	
+	// narrower half angle of the view, accounting for the pixel aspect ratio
+	pub fn half_fov(&self) -> f32 {
+		let half_width = 0.5*self.width as f32*self.pixel_aspect;
+		let half_height = 0.5*self.height as f32;
+		(half_width.min(half_height)/self.focal_length.abs().max(1e-3)).atan()
+	}
+	
+	// point the camera so bounds (a mesh or an Aabb) fills the view, margin is extra space around it as a fraction of its size
+	pub fn frame(&mut self, bounds: impl Into<Aabb>, margin: f32) {
+		let half_fov = self.half_fov();
+		self.camera.frame(bounds, margin, half_fov);
+	}
+	
+	// camera space point to screen pixels, see Camera::to_view
+	pub fn project(&self, vector: Vector3D) -> Point2D {
 		(
-			(vector.X*self.focal_length/vector.Z) + (self.width as f32) * 0.5,
-			(vector.Y*self.focal_length/vector.Z) + (self.height as f32) * 0.5
+			(vector.X*self.focal_length/(vector.Z*self.pixel_aspect)) + (self.width as f32) * 0.5 + self.jitter.0, // wide pixels need fewer of them per unit
+			(vector.Y*self.focal_length/vector.Z) + (self.height as f32) * 0.5 + self.jitter.1
 		)
 	}
 	
-	fn draw_line(&mut self, p1: Point2D, p2: Point2D, color: Color) {
+	pub fn draw_line(&mut self, p1: Point2D, p2: Point2D, color: Color) {
+		if !(p1.0.is_finite() && p1.1.is_finite() && p2.0.is_finite() && p2.1.is_finite()) { return; }
+		if self.display_mode == DisplayMode::Regis { self.vector_lines.push((p1, p2, color)); }
 		if (p1.0 - p2.0).abs() > (p1.1 - p2.1).abs() {
 			let (start, end) = if p1.0 > p2.0 { (p2, p1) }else { (p1, p2) };
 			let dx = end.0 - start.0;
 			let dy = end.1 - start.1;
 			let m = dy/dx;
 			
-			for i in 0..(dx as usize) + 1 {
+			// only step through the part of the line that's on screen, projected points can be absurdly far away
+			let (i_min, i_max) = (clamp(0.0, dx, -start.0) as usize, clamp(0.0, dx, self.width as f32 - start.0) as usize);
+			for i in i_min..=i_max {
 				let x = start.0 + (i as f32);
 				let y = start.1 + (i as f32)*m;
-				if (x > self.width as f32) || (x < 0.0) || (y > self.height as f32) || (y < 0.0) { continue; }
+				if (x >= self.width as f32) || (x < 0.0) || (y >= self.height as f32) || (y < 0.0) { continue; }
 				self.pixel_buffer[y as usize][x as usize] = color;
 		}}else {
 			let (start, end) = if p1.1 > p2.1 { (p2, p1) }else { (p1, p2) };
@@ -103,15 +777,17 @@ impl Viewport {
 			let dy = end.1 - start.1;
 			let m = dx/dy;
 			
-			for i in 0..(dy as usize) + 1 {
+			let (i_min, i_max) = (clamp(0.0, dy, -start.1) as usize, clamp(0.0, dy, self.height as f32 - start.1) as usize);
+			for i in i_min..=i_max {
 				let x = start.0 + (i as f32)*m;
 				let y = start.1 + (i as f32);
-				if (x > self.width as f32) || (x < 0.0) || (y > self.height as f32) || (y < 0.0) { continue; }
+				if (x >= self.width as f32) || (x < 0.0) || (y >= self.height as f32) || (y < 0.0) { continue; }
 				self.pixel_buffer[y as usize][x as usize] = color;
 		}}
 	}
 	
 	fn draw_triangle(&mut self, p1: Vertex, p2: Vertex, p3: Vertex, tex: &Texture, mtl: &Material, norm: Vector3D) {
+		if self.rasterizer == Rasterizer::FixedPoint { return self.draw_triangle_fixed(p1, p2, p3, tex, mtl, norm); }
 		// find triangle bounding box
 		let (mut x_min, mut x_max) = (999.0, 0.0);
 		let (mut y_min, mut y_max) = (999.0, 0.0);
@@ -127,7 +803,7 @@ impl Viewport {
 		// find total triangle area
 		let side_1 = (p1.screen_XY.0 - p2.screen_XY.0, p1.screen_XY.1 - p2.screen_XY.1);
 		let side_2 = (p1.screen_XY.0 - p3.screen_XY.0, p1.screen_XY.1 - p3.screen_XY.1);
-		let mut total_area = side_1.0*side_2.1 - side_1.1*side_2.0; // technically 2*area, but only ratios between areas matter :3
+		let total_area = side_1.0*side_2.1 - side_1.1*side_2.0; // technically 2*area, but only ratios between areas matter :3
 
 		// check if each point in the bounding box is in the triangle, apply shader if so, otherwise ignore it
 		for h in (y_min as usize)..(y_max as usize)+1 {
@@ -143,78 +819,550 @@ impl Viewport {
 				let (a, b, c) = (p1_area/total_area, p2_area/total_area, p3_area/total_area);
 				
 				let interp = p1.interpolate(p2, p3, a, b, c);
-				if interp.z_coord > self.depth_buffer[h][w] { continue; }
-				self.depth_buffer[h][w] = interp.z_coord;
+				if self.trace.is_some() { self.trace_fragment((w, h), [p1, p2, p3], (a, b, c), interp.z_coord); }
+				if Viewport::screen_door_discards(mtl, &interp, (w, h)) { continue; }
+				if self.visibility == Visibility::DepthBuffer {
+					if interp.z_coord > self.depth_buffer[h][w] { continue; }
+					self.depth_buffer[h][w] = interp.z_coord;
+				}
 
 				self.apply_phong_shader(interp, (w, h), tex, mtl, norm);
 		}}
 	}
 	
+	// same edge function approach as draw_triangle, but everything deciding coverage and depth is done in integers
+	fn draw_triangle_fixed(&mut self, p1: Vertex, p2: Vertex, p3: Vertex, tex: &Texture, mtl: &Material, norm: Vector3D) {
+		for p in [p1, p2, p3] {
+			let in_range = |v: f32| v.abs() < FIXED_RANGE; // also false for NaN
+			if !in_range(p.screen_XY.0) || !in_range(p.screen_XY.1) || !in_range(1.0/p.z_coord) { return; }
+		}
+		let to_fixed = |v: f32, bits: i64| (v * (1i64 << bits) as f32).round() as i64;
+		let snap = |p: Point2D| (to_fixed(p.0, SUBPIXEL_BITS), to_fixed(p.1, SUBPIXEL_BITS));
+		let (q1, q2, q3) = (snap(p1.screen_XY), snap(p2.screen_XY), snap(p3.screen_XY));
+		
+		let x_min = max(0, min(q1.0, min(q2.0, q3.0)) >> SUBPIXEL_BITS);
+		let x_max = min(self.width as i64 - 1, max(q1.0, max(q2.0, q3.0)) >> SUBPIXEL_BITS);
+		let y_min = max(0, min(q1.1, min(q2.1, q3.1)) >> SUBPIXEL_BITS);
+		let y_max = min(self.height as i64 - 1, max(q1.1, max(q2.1, q3.1)) >> SUBPIXEL_BITS);
+		
+		let side_1 = (q1.0 - q2.0, q1.1 - q2.1);
+		let side_2 = (q1.0 - q3.0, q1.1 - q3.1);
+		let total_area = side_1.0*side_2.1 - side_1.1*side_2.0;
+		if total_area <= 0 { return; } // degenerate or wound the wrong way, the float path can't draw these either
+		
+		let (z1, z2, z3) = (to_fixed(1.0/p1.z_coord, DEPTH_BITS), to_fixed(1.0/p2.z_coord, DEPTH_BITS), to_fixed(1.0/p3.z_coord, DEPTH_BITS));
+		let one = (1i64 << WEIGHT_BITS) as f32;
+		
+		for h in y_min..y_max+1 {
+			for w in x_min..x_max+1 {
+				let dist_p1 = ((w << SUBPIXEL_BITS) - q1.0, (h << SUBPIXEL_BITS) - q1.1);
+				let p3_area = dist_p1.0*side_1.1 - dist_p1.1*side_1.0;
+				let p2_area = dist_p1.1*side_2.0 - dist_p1.0*side_2.1;
+				let p1_area = total_area - (p2_area + p3_area);
+				if (p1_area < 0) || (p2_area < 0) || (p3_area < 0) { continue; }
+				
+				let weight = |area: i64| (((area as i128) << WEIGHT_BITS) / total_area as i128) as i64;
+				let (a, b) = (weight(p1_area), weight(p2_area));
+				let c = (1i64 << WEIGHT_BITS) - a - b;
+				let depth = ((a*z1 + b*z2 + c*z3) >> WEIGHT_BITS) as f32 / (1i64 << DEPTH_BITS) as f32;
+				let (h, w) = (h as usize, w as usize);
+				if self.trace.is_some() { self.trace_fragment((w, h), [p1, p2, p3], (a as f32/one, b as f32/one, c as f32/one), depth); }
+				let mut interp = p1.interpolate(p2, p3, a as f32/one, b as f32/one, c as f32/one);
+				interp.z_coord = depth;
+				if Viewport::screen_door_discards(mtl, &interp, (w, h)) { continue; }
+				if self.visibility == Visibility::DepthBuffer {
+					if depth > self.depth_buffer[h][w] { continue; }
+					self.depth_buffer[h][w] = depth;
+				}
+				
+				self.apply_phong_shader(interp, (w, h), tex, mtl, norm);
+		}}
+	}
+	
 	// (づ ᴗ _ᴗ)づ .𖥔 ݁ ˖ ✦ ‧₊˚ ⋅
 	fn apply_phong_shader(&mut self, fragment: Vertex, pos: (usize, usize), tex: &Texture, mtl: &Material, face_norm: Vector3D) {
-		let base_color = tex.sample(fragment.texture_UV);
-		let camera_direction = Vector3D::XYZ(0.0, 0.0, 1.0).normalize();
+		let base_color = tex.sample(fragment.texture_UV).hadamard(fragment.color);
+		let map_value = |map: &Option<Texture>| map.as_ref().map_or(1.0, |map| luminance(map.sample(fragment.texture_UV)));
+		// screen door materials were already thinned out by screen_door_discards, what's left is drawn solid
+		let opacity = if mtl.transparency == Transparency::Blend { mtl.opacity*map_value(&mtl.opacity_map) }else { 1.0 };
+		let emission = mtl.emissive_map.as_ref().map_or(mtl.emissive, |map| map.sample(fragment.texture_UV).hadamard(mtl.emissive));
+		let camera_direction = self.camera.forward.mul(-1.0).normalize();
+		let traced = self.trace.as_ref().is_some_and(|trace| trace.covers(pos));
+		if traced {
+			self.trace_line(format!("    world {:.4} view {:.4}", fragment.position, self.camera.to_view(fragment.position)));
+			self.trace_line(format!("    uv ({:.4}, {:.4}) texel {:.4} vertex color {:.4}", fragment.texture_UV.0, fragment.texture_UV.1, tex.sample(fragment.texture_UV), fragment.color));
+		}
+		let shaded = self.mesh_shader.as_ref().map(|shader| shader.shade(&Fragment{ UV: fragment.texture_UV, normal: fragment.normal.normalize(), depth: -1.0/fragment.z_coord, position: fragment.position, material: mtl }));
+		if let Some(color) = shaded {
+			if traced { self.trace_line(format!("    custom shader, color {:.4}", color)); }
+			self.normal_buffer[pos.1][pos.0] = fragment.normal.normalize();
+			self.blend_pixel(pos, color, opacity);
+			return;
+		}
 		
-		let surface_normal = match mtl.mode {
+		let interpolated_normal = match mtl.mode {
 			LightingMode::Flat => face_norm.normalize(),
-			LightingMode::Smooth => fragment.normal.normalize(),
+			LightingMode::Smooth if !self.quality.smooth_shading => face_norm.normalize(),
+			LightingMode::Smooth | LightingMode::Gouraud | LightingMode::Toon(_) => fragment.normal.normalize(),
+			LightingMode::None if self.shading_view == ShadingView::Normals => fragment.normal.normalize(),
 			LightingMode::None => {
-				self.pixel_buffer[pos.1][pos.0] = base_color;
+				if traced { self.trace_line(format!("    unlit, color {:.4}", base_color)); }
+				self.normal_buffer[pos.1][pos.0] = fragment.normal.normalize();
+				self.blend_pixel(pos, base_color.add(emission), opacity);
 				return;
 		}};
+		let surface_normal = match &mtl.normal_map {
+			Some(map) => Viewport::perturb_normal(interpolated_normal, fragment.tangents, map.sample(fragment.texture_UV)),
+			None => interpolated_normal
+		};
+		self.normal_buffer[pos.1][pos.0] = surface_normal;
+		if traced { self.trace_line(format!("    {:?} shading, normal {:.4}", mtl.mode, surface_normal)); }
+		if self.shading_view == ShadingView::Normals {
+			self.pixel_buffer[pos.1][pos.0] = Color::RGB(0.5 + 0.5*surface_normal.X, 0.5 + 0.5*surface_normal.Y, 0.5 + 0.5*surface_normal.Z);
+			return;
+		}
 		
-		let ambient = base_color.hadamard(mtl.ambient);
-		let mut new_color = Color::RGB(0.0, 0.0, 0.0);
+		if let (Some(pbr), ShadingView::Full) = (&mtl.pbr, self.shading_view) {
+			let albedo = base_color.hadamard(mtl.diffuse);
+			let (metallic, roughness) = (pbr.metallic*map_value(&pbr.metallic_map), pbr.roughness*map_value(&pbr.roughness_map));
+			let mut color = albedo.hadamard(self.ambient.radiance(surface_normal)).mul(self.shading_weights.ambient).add(emission);
+			for light in self.active_lights.iter().map(|&i| &self.lights[i]) {
+				color = color.add(cook_torrance(albedo, metallic, roughness, surface_normal, light.position.normalize(), camera_direction).hadamard(light.color).mul(light.intensity));
+			}
+			if traced { self.trace_line(format!("    pbr metallic {metallic:.4} roughness {roughness:.4} result {color:.4}")); }
+			self.blend_pixel(pos, color, opacity);
+			return;
+		}
+		let ambient = base_color.hadamard(mtl.ambient).hadamard(self.ambient.radiance(surface_normal)).mul(self.shading_weights.ambient);
+		if let Some(light) = fragment.light {
+			if traced { self.trace_line(format!("    ambient {:.4} cached light {:.4}", ambient, light)); }
+			self.blend_pixel(pos, ambient.add(light).add(emission), opacity);
+			return;
+		}
+		let highlights = mtl.highlights*map_value(&mtl.highlight_map);
+		// Ks scaled by map_Ks, lights are tinted by it like mtl files expect
+		let specular_tint = mtl.specular_map.as_ref().map_or(mtl.specular, |map| map.sample(fragment.texture_UV).hadamard(mtl.specular));
+		let weights = self.shading_weights;
+		let mut new_color = ambient.add(emission);
+		let (mut diffuse_only, mut specular_only) = (Color::black(), Color::black());
 		
-		for light in self.lights.iter() {
+		for light in self.active_lights.iter().map(|&i| &self.lights[i]) {
 			let light_direction = light.position.normalize();
-			let diffuse_strength = clamp(0.0, 1.0, surface_normal.dot(light_direction));
-			let diffuse = mtl.diffuse.mul(diffuse_strength);
+			let mut diffuse_strength = clamp(0.0, 1.0, surface_normal.dot(light_direction));
+			let mut specular_strength = mtl.specular_model.strength(surface_normal, light_direction, camera_direction, highlights);
+			if let LightingMode::Toon(bands) = mtl.mode {
+				let bands = bands.max(2) as f32;
+				diffuse_strength = (diffuse_strength*bands).floor().min(bands - 1.0)/(bands - 1.0);
+				specular_strength = if specular_strength > 0.5 { 1.0 }else { 0.0 };
+			}
+			let diffuse = mtl.diffuse.mul(diffuse_strength*light.intensity);
 		
-			let specular_source = light_direction.mul(-1.0).reflect(surface_normal);
-			let specular_strength = clamp(0.0, 1.0, camera_direction.dot(specular_source)).powf(mtl.highlights);
-			let specular = light.color.mul(specular_strength);
+			let specular = if mtl.specular_highlights { light.color.hadamard(specular_tint).mul(specular_strength*light.intensity) }else { Color::black() };
+			if let (true, Some(trace)) = (traced, &mut self.trace) { trace.lines.push(format!("    light {:.4}: diffuse {:.4} specular {:.4}", light_direction, diffuse_strength, specular_strength)); }
 			
-			new_color = new_color.add(ambient.mul(0.2).add(diffuse.mul(0.4)).add(specular.mul(0.6)));
+			new_color = new_color.add(diffuse.mul(weights.diffuse)).add(specular.mul(weights.specular));
+			diffuse_only = diffuse_only.add(diffuse);
+			specular_only = specular_only.add(specular);
+		}
+		match self.shading_view {
+			ShadingView::Diffuse => self.pixel_buffer[pos.1][pos.0] = diffuse_only,
+			ShadingView::Specular => self.pixel_buffer[pos.1][pos.0] = specular_only,
+			_ => self.blend_pixel(pos, new_color, opacity)
+		};
+		if matches!(mtl.mode, LightingMode::Toon(_)) { self.toon_pixels.push(pos); }
+		if traced { self.trace_line(format!("    ambient {:.4} opacity {:.4} result {:.4}", ambient, opacity, self.pixel_buffer[pos.1][pos.0])); }
+	}
+	
+	// Transparency::ScreenDoor and Noise keep a fragment when its opacity beats the pixel's threshold. runs before the
+	// depth test so dropped fragments don't hide what's behind them
+	fn screen_door_discards(mtl: &Material, fragment: &Vertex, pos: (usize, usize)) -> bool {
+		let threshold = match mtl.transparency {
+			Transparency::Blend => return false,
+			Transparency::ScreenDoor => (BAYER_4X4[pos.1 % 4][pos.0 % 4] + 0.5)/16.0,
+			Transparency::Noise => {
+				let mut hasher = DefaultHasher::new();
+				(pos, fragment.z_coord.to_bits()).hash(&mut hasher);
+				(hasher.finish() >> 40) as f32/(1u64 << 24) as f32
+			}
+		};
+		let opacity = mtl.opacity*mtl.opacity_map.as_ref().map_or(1.0, |map| luminance(map.sample(fragment.texture_UV)));
+		opacity < threshold
+	}
+	
+	// bend the normal by a normal map texel, with the tangents made perpendicular to it first. without usable
+	// tangents (or with a NaN texel) the normal is left alone
+	fn perturb_normal(normal: Vector3D, tangents: (Vector3D, Vector3D), texel: Color) -> Vector3D {
+		let tangent = tangents.0.sub(normal.mul(normal.dot(tangents.0))).normalize();
+		let bitangent = normal.cross(tangent);
+		// mirrored uvs flip the v direction
+		let bitangent = if bitangent.dot(tangents.1) < 0.0 { bitangent.mul(-1.0) }else { bitangent };
+		let (x, y, z) = (2.0*texel.RGB.0 - 1.0, 2.0*texel.RGB.1 - 1.0, 2.0*texel.RGB.2 - 1.0);
+		let perturbed = tangent.mul(x).add(bitangent.mul(y)).add(normal.mul(z)).normalize();
+		if perturbed.is_finite() && tangent.is_finite() { perturbed }else { normal }
+	}
+	
+	// partly transparent fragments mix with whatever was drawn before them, there's no sorting so anything drawn
+	// later behind them is still hidden by the depth test (painter mode draws back to front)
+	fn blend_pixel(&mut self, pos: (usize, usize), color: Color, opacity: f32) {
+		let pixel = &mut self.pixel_buffer[pos.1][pos.0];
+		*pixel = if opacity >= 1.0 { color }else { pixel.lerp(color, clamp(0.0, 1.0, opacity)) };
+	}
+	
+	fn trace_line(&mut self, line: String) {
+		if let Some(trace) = &mut self.trace { trace.lines.push(line); }
+	}
+	
+	// post transform vertices of a traced triangle, view is the camera space position of each corner
+	fn trace_triangle(&mut self, mesh: &Mesh, tri: usize, view: [Vector3D; 3]) {
+		let screen = view.map(|p| self.project(p));
+		let Some(trace) = &mut self.trace else { return; };
+		trace.triangle = tri;
+		if trace.target != TraceTarget::Triangle(tri) { return; }
+		let (t1, t2, t3) = mesh.triangles[tri];
+		trace.lines.push(format!("mesh {} triangle {tri}: vertices {t1} {t2} {t3}, face normal {:.4}", trace.mesh, mesh.face_normals[tri]));
+		for (i, v) in [t1, t2, t3].into_iter().enumerate() {
+			trace.lines.push(format!("  vertex {v}: world {:.4} view {:.4} screen ({:.4}, {:.4})", mesh.vertices[v], view[i], screen[i].0, screen[i].1));
 		}
-		self.pixel_buffer[pos.1][pos.0] = new_color;
+	}
+	
+	// coverage and depth test for one fragment, the shader adds the rest if it passes
+	fn trace_fragment(&mut self, pos: (usize, usize), corners: [Vertex; 3], weights: (f32, f32, f32), depth: f32) {
+		let stored_depth = self.depth_buffer[pos.1][pos.0];
+		let Some(trace) = &mut self.trace else { return; };
+		if !trace.covers(pos) { return; }
+		let screen = corners.map(|p| format!("({:.4}, {:.4})", p.screen_XY.0, p.screen_XY.1)).join(" ");
+		trace.lines.push(format!("mesh {} triangle {} pixel ({}, {}): corners {screen}", trace.mesh, trace.triangle, pos.0, pos.1));
+		let result = if self.visibility == Visibility::Painter { "painted" }else if depth > stored_depth { "hidden" }else { "drawn" };
+		trace.lines.push(format!("    barycentric ({:.4}, {:.4}, {:.4}) depth {depth:.4} vs {stored_depth:.4}, {result}", weights.0, weights.1, weights.2));
 	}
 	
 	pub fn draw_mesh(&mut self, mesh: &Mesh) {
+		// vertex shaders run first, everything below sees the displaced copy with its normals recalculated
+		let shaded = mesh.shaded(self.time);
+		let mesh = shaded.as_ref();
+		// stale caches are ignored rather than recomputed here, that's what cache_lighting is for
+		// the cache is lit with mesh.material and vertex_normals only, so meshes with per face materials, authored
+		// per corner normals or specular, highlight and normal maps don't use it
+		let key = self.lighting_key(mesh);
+		let cached_light = match &mesh.lighting_cache {
+			Some(cache) if cache.key == key && mesh.face_materials.is_empty() && mesh.normal_tris.is_empty() && mesh.material.specular_map.is_none() && mesh.material.highlight_map.is_none() && mesh.material.normal_map.is_none() && mesh.material.pbr.is_none() && cache.vertex_light.len() == mesh.vertices.len() && (matches!(mesh.material.mode, LightingMode::Smooth) && self.quality.smooth_shading || matches!(mesh.material.mode, LightingMode::Gouraud)) && self.shading_view == ShadingView::Full => Some(&cache.vertex_light),
+			_ => None
+		};
+		if let Some(trace) = &mut self.trace { trace.mesh += 1; }
+		self.mesh_shader = mesh.shader.clone();
+		// lights out of range of the whole mesh aren't checked again per triangle
+		let vertices = &mesh.vertices;
+		let bounds = mesh.bounds();
+		let mesh_lights: Vec<usize> = (0..self.lights.len()).filter(|&i| self.lights[i].reaches(bounds.center(), bounds.radius())).collect();
+		let mut sorted = Vec::new(); // painter mode only
 		for tri in 0..mesh.triangles.len() {
+			if !mesh.triangle_in_range(tri) { continue; }
+			let overrun = self.watchdog.as_mut().and_then(|watchdog| watchdog.overrun_at(tri));
+			if overrun == Some(Overrun::Partial) { break; }
+			if overrun == Some(Overrun::Wireframe) {
+				self.outline_triangle(vertices, mesh.triangles[tri], mesh.face_material(tri).0.diffuse);
+				continue;
+			}
 			let (tri1, tri2, tri3) = mesh.triangles[tri];
 			let (tex1, tex2, tex3) = mesh.tex_tris[tri];
-			let (p1, p2, p3) = (mesh.vertices[tri1], mesh.vertices[tri2], mesh.vertices[tri3]);
-
-			self.draw_triangle(
-				Vertex::new(self.project(p1), mesh.tex_coords[tex1], p1.Z, mesh.vertex_normals[tri1]),
-				Vertex::new(self.project(p2), mesh.tex_coords[tex2], p2.Z, mesh.vertex_normals[tri2]),
-				Vertex::new(self.project(p3), mesh.tex_coords[tex3], p3.Z, mesh.vertex_normals[tri3]),
-				&mesh.texture,
-				&mesh.material,
-				mesh.face_normals[tri]
-		);}
+			let (p1, p2, p3) = (self.camera.to_view(vertices[tri1]), self.camera.to_view(vertices[tri2]), self.camera.to_view(vertices[tri3]));
+			if self.trace.is_some() { self.trace_triangle(mesh, tri, [p1, p2, p3]); }
+			
+			let [n1, n2, n3] = mesh.corner_normals(tri);
+			let (v1, v2, v3) = (
+				Vertex::new(self.project(p1), mesh.tex_coords[tex1], p1.Z, n1),
+				Vertex::new(self.project(p2), mesh.tex_coords[tex2], p2.Z, n2),
+				Vertex::new(self.project(p3), mesh.tex_coords[tex3], p3.Z, n3)
+			);
+			let Some((mut v1, mut v2, mut v3, face_normal)) = self.check_triangle(tri, [v1, v2, v3], mesh.face_normals[tri]) else { continue; };
+			(v1.position, v2.position, v3.position) = (vertices[tri1], vertices[tri2], vertices[tri3]);
+			if let (Some(c1), Some(c2), Some(c3)) = (mesh.vertex_colors.get(tri1), mesh.vertex_colors.get(tri2), mesh.vertex_colors.get(tri3)) {
+				(v1.color, v2.color, v3.color) = (*c1, *c2, *c3);
+			}
+			if let Some(cache) = cached_light {
+				(v1.light, v2.light, v3.light) = (Some(cache[tri1]), Some(cache[tri2]), Some(cache[tri3]));
+			}
+			if mesh.face_material(tri).0.normal_map.is_some() {
+				let tangents = Viewport::face_tangents([vertices[tri1], vertices[tri2], vertices[tri3]], [mesh.tex_coords[tex1], mesh.tex_coords[tex2], mesh.tex_coords[tex3]]);
+				(v1.tangents, v2.tangents, v3.tangents) = (tangents, tangents, tangents);
+			}
+			if self.visibility == Visibility::Painter {
+				sorted.push((1.0/v1.z_coord + 1.0/v2.z_coord + 1.0/v3.z_coord, tri, v1, v2, v3, face_normal));
+				continue;
+			}
+			let (material, texture) = mesh.face_material(tri);
+			self.shade_triangle(&mesh_lights, [v1, v2, v3], texture, material, face_normal);
+		}
+		// farthest first, going by the same 1/z the depth test compares (smaller is closer)
+		sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+		for (_, tri, v1, v2, v3, face_normal) in sorted {
+			if self.watchdog.as_mut().is_some_and(|watchdog| watchdog.overrun == Overrun::Partial && watchdog.over_budget()) { break; }
+			if let Some(trace) = &mut self.trace { trace.triangle = tri; }
+			let (material, texture) = mesh.face_material(tri);
+			self.shade_triangle(&mesh_lights, [v1, v2, v3], texture, material, face_normal);
+		}
+		if !self.toon_pixels.is_empty() { self.draw_toon_outlines(); }
+		self.mesh_shader = None;
+	}
+	
+	// darken the toon pixels at silhouettes and sharp creases. goes by the depth and normal buffers, so meshes drawn
+	// later still cover the outline where they're in front
+	fn draw_toon_outlines(&mut self) {
+		let mut pixels = std::mem::take(&mut self.toon_pixels);
+		pixels.sort_unstable();
+		pixels.dedup();
+		let edges: Vec<(usize, usize)> = pixels.iter().copied().filter(|&(x, y)| self.is_toon_edge(x, y)).collect();
+		for (x, y) in edges { self.pixel_buffer[y][x] = Color::black(); }
+		pixels.clear();
+		self.toon_pixels = pixels;
+	}
+	
+	fn is_toon_edge(&self, x: usize, y: usize) -> bool {
+		let (distance, normal) = (1.0/self.depth_buffer[y][x], self.normal_buffer[y][x]);
+		[(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)].into_iter().any(|(nx, ny)| {
+			if nx >= self.width || ny >= self.height { return false; }
+			(1.0/self.depth_buffer[ny][nx] - distance).abs() > TOON_DEPTH_STEP*distance.abs() || self.normal_buffer[ny][nx].dot(normal) < TOON_CREASE
+		})
+	}
+	
+	// pick the lights for a triangle and, for Gouraud materials, light its corners unless the cache already did
+	fn shade_triangle(&mut self, candidates: &[usize], corners: [Vertex; 3], tex: &Texture, mtl: &Material, norm: Vector3D) {
+		self.pick_lights(candidates, corners.map(|v| v.position));
+		let [mut v1, mut v2, mut v3] = corners;
+		if matches!(mtl.mode, LightingMode::Gouraud) && mtl.pbr.is_none() && self.shading_view == ShadingView::Full {
+			for v in [&mut v1, &mut v2, &mut v3] {
+				if v.light.is_none() { v.light = Some(self.vertex_light(mtl, v.normal.normalize(), self.active_lights.iter().map(|&i| &self.lights[i]))); }
+			}
+		}
+		self.draw_triangle(v1, v2, v3, tex, mtl, norm);
+	}
+	
+	// narrow candidates (indices into lights) down to the ones that reach the sphere around a triangle's world corners
+	fn pick_lights(&mut self, candidates: &[usize], corners: [Vector3D; 3]) {
+		self.active_lights.clear();
+		let Some(bounds) = Aabb::around(corners) else { return self.active_lights.extend_from_slice(candidates); };
+		self.active_lights.extend(candidates.iter().filter(|&&i| self.lights[i].reaches(bounds.center(), bounds.radius())));
+	}
+	
+	// how u and v change across a triangle, zero for triangles whose texture coordinates don't span an area
+	fn face_tangents(positions: [Vector3D; 3], uvs: [Point2D; 3]) -> (Vector3D, Vector3D) {
+		let (e1, e2) = (positions[1].sub(positions[0]), positions[2].sub(positions[0]));
+		let (du1, dv1, du2, dv2) = (uvs[1].0 - uvs[0].0, uvs[1].1 - uvs[0].1, uvs[2].0 - uvs[0].0, uvs[2].1 - uvs[0].1);
+		let det = du1*dv2 - du2*dv1;
+		if det.abs() < AREA_EPSILON || !det.is_finite() { return (Vector3D::zero(), Vector3D::zero()); }
+		(e1.mul(dv2).sub(e2.mul(dv1)).div(det), e2.mul(du1).sub(e1.mul(du2)).div(det))
+	}
+	
+	// skip triangles that would smear NaNs and infinities across the screen and patch up broken normals
+	fn check_triangle(&mut self, tri: usize, verts: [Vertex; 3], face_normal: Vector3D) -> Option<(Vertex, Vertex, Vertex, Vector3D)> {
+		let [mut p1, mut p2, mut p3] = verts;
+		let finite = |p: &Vertex| p.screen_XY.0.is_finite() && p.screen_XY.1.is_finite() && p.z_coord.is_finite();
+		let side_1 = (p1.screen_XY.0 - p2.screen_XY.0, p1.screen_XY.1 - p2.screen_XY.1);
+		let side_2 = (p1.screen_XY.0 - p3.screen_XY.0, p1.screen_XY.1 - p3.screen_XY.1);
+		
+		let problem = if !(finite(&p1) && finite(&p2) && finite(&p3)) {
+			self.diagnostics.non_finite += 1;
+			Some("non finite vertex position")
+		}else if [p1, p2, p3].iter().any(|p| p.z_coord.abs() < DEPTH_EPSILON) {
+			self.diagnostics.near_zero_depth += 1;
+			Some("vertex on the camera plane")
+		}else if (side_1.0*side_2.1 - side_1.1*side_2.0).abs() < AREA_EPSILON {
+			self.diagnostics.zero_area += 1;
+			Some("zero area")
+		}else { None };
+		if let Some(problem) = problem {
+			if self.log_degenerate { println!("skipped triangle {tri}: {problem}"); }
+			if self.trace.as_ref().is_some_and(|trace| trace.target == TraceTarget::Triangle(tri)) { self.trace_line(format!("  skipped: {problem}")); }
+			return None;
+		}
+		
+		let mut patched = !face_normal.is_finite();
+		let face_normal = if patched { Vector3D::zero() }else { face_normal };
+		for p in [&mut p1, &mut p2, &mut p3] {
+			if !p.normal.is_finite() { p.normal = face_normal; patched = true; }
+		}
+		if patched {
+			self.diagnostics.bad_normals += 1;
+			if self.log_degenerate { println!("triangle {tri}: replaced NaN normals"); }
+		}
+		Some((p1, p2, p3, face_normal))
+	}
+	
+	// everything per vertex lighting depends on, if any of it changes the cache is stale
+	fn lighting_key(&self, mesh: &Mesh) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		mesh.revision.hash(&mut hasher);
+		mesh.vertices.len().hash(&mut hasher);
+		let mtl = &mesh.material;
+		let mut values = vec![mtl.diffuse.RGB.0, mtl.diffuse.RGB.1, mtl.diffuse.RGB.2, mtl.highlights, mtl.specular_highlights as u8 as f32];
+		mtl.specular_model.hash(&mut hasher);
+		values.extend_from_slice(&[mtl.specular.RGB.0, mtl.specular.RGB.1, mtl.specular.RGB.2]);
+		values.extend_from_slice(&[self.shading_weights.diffuse, self.shading_weights.specular]);
+		values.extend_from_slice(&[self.camera.forward.X, self.camera.forward.Y, self.camera.forward.Z]); // specular depends on the view direction
+		for light in self.lights.iter() {
+			values.extend_from_slice(&[light.color.RGB.0, light.color.RGB.1, light.color.RGB.2, light.position.X, light.position.Y, light.position.Z, light.intensity]);
+			(light.enabled, light.range.map(f32::to_bits)).hash(&mut hasher);
+		}
+		for v in values { v.to_bits().hash(&mut hasher); }
+		hasher.finish()
+	}
+	
+	// for static geometry: light every vertex once and reuse it in draw_mesh until the mesh, its material or the lights change
+	// only smooth and gouraud shaded meshes use the cache, flat shading needs the face normal per pixel anyway. meshes
+	// with a vertex shader move every frame, so they aren't cached at all
+	pub fn cache_lighting(&self, mesh: &mut Mesh) {
+		if mesh.vertex_shader.is_some() { mesh.lighting_cache = None; return; }
+		let key = self.lighting_key(mesh);
+		if let Some(cache) = &mesh.lighting_cache {
+			if cache.key == key { return; }
+		}
+		let vertex_light = (0..mesh.vertices.len()).map(|v| {
+			let surface_normal = mesh.vertex_normals.get(v).map_or(Vector3D::zero(), |n| n.normalize());
+			self.vertex_light(&mesh.material, surface_normal, self.lights.iter().filter(|light| light.reaches(mesh.vertices[v], 0.0)))
+		}).collect();
+		mesh.lighting_cache = Some(LightingCache { key, vertex_light });
+	}
+	
+	// weighted diffuse and specular at one vertex, without ambient or any of the material's maps
+	fn vertex_light<'a>(&self, mtl: &Material, normal: Vector3D, lights: impl Iterator<Item = &'a LightSource>) -> Color {
+		let camera_direction = self.camera.forward.mul(-1.0).normalize();
+		let mut light_sum = Color::black();
+		for light in lights {
+			let light_direction = light.position.normalize();
+			let diffuse = mtl.diffuse.mul(clamp(0.0, 1.0, normal.dot(light_direction))*light.intensity);
+			let specular_strength = mtl.specular_model.strength(normal, light_direction, camera_direction, mtl.highlights);
+			let specular = light.color.hadamard(mtl.specular).mul(specular_strength*light.intensity);
+			light_sum = light_sum.add(diffuse.mul(self.shading_weights.diffuse));
+			if mtl.specular_highlights { light_sum = light_sum.add(specular.mul(self.shading_weights.specular)); }
+		}
+		light_sum
 	}
 	
 	pub fn draw_wireframe(&mut self, mesh: &Mesh) {
-		for tri in 0..mesh.triangles.len() {
-			let (tri1, tri2, tri3) = mesh.triangles[tri];
-			let (p1, p2, p3) = (
-				self.project(mesh.vertices[tri1]),
-				self.project(mesh.vertices[tri2]),
-				self.project(mesh.vertices[tri3])
-			);
-			let color = Color::RGB(0.988, 0.667, 0.118);
-			
-			self.draw_line(p1, p2, color);
-			self.draw_line(p2, p3, color);
-			self.draw_line(p3, p1, color);
+		let vertices = mesh.shaded_vertices(self.time);
+		for &tri in mesh.triangles.iter() { self.outline_triangle(&vertices, tri, Color::RGB(0.988, 0.667, 0.118)); }
+	}
+	
+	fn outline_triangle(&mut self, vertices: &[Vector3D], (tri1, tri2, tri3): Triangle, color: Color) {
+		if tri1 >= vertices.len() || tri2 >= vertices.len() || tri3 >= vertices.len() { return; }
+		let (p1, p2, p3) = (
+			self.project(self.camera.to_view(vertices[tri1])),
+			self.project(self.camera.to_view(vertices[tri2])),
+			self.project(self.camera.to_view(vertices[tri3]))
+		);
+		self.draw_line(p1, p2, color);
+		self.draw_line(p2, p3, color);
+		self.draw_line(p3, p1, color);
+	}
+	
+	// project the part of mesh above the plane onto it along the light and darken what's drawn there. run it after the
+	// ground and the meshes, anything in front of the plane hides the shadow. pixels are darkened once however many
+	// triangles overlap them
+	pub fn draw_planar_shadow(&mut self, mesh: &Mesh, shadow: &PlanarShadow) {
+		let (normal, light) = (shadow.normal.normalize(), shadow.light.normalize());
+		let facing = normal.dot(light);
+		if facing < 1e-4 || !facing.is_finite() { return; } // light from below or along the plane
+		// cast from where the mesh is drawn
+		let mut above = mesh.shaded(self.time).into_owned();
+		self.clip_against_plane(&mut above, shadow.point, normal);
+		
+		let heights: Vec<f32> = above.vertices.iter().map(|p| normal.dot(p.sub(shadow.point))).collect();
+		let flattened: Vec<Option<(Point2D, f32)>> = above.vertices.iter().zip(heights.iter()).map(|(p, height)| {
+			let view = self.camera.to_view(p.sub(light.mul(height/facing)).add(normal.mul(SHADOW_LIFT)));
+			let screen = self.project(view);
+			(view.Z.abs() >= DEPTH_EPSILON && screen.0.is_finite() && screen.1.is_finite()).then_some((screen, view.Z))
+		}).collect();
+		let mut mask = vec![vec![false; self.width]; self.height];
+		for &(t1, t2, t3) in above.triangles.iter() {
+			// already lying on the plane, like the ground itself, so there's nothing to cast
+			if [t1, t2, t3].iter().all(|&t| heights.get(t).is_some_and(|height| *height <= SHADOW_LIFT)) { continue; }
+			let (Some(Some(p1)), Some(Some(p2)), Some(Some(p3))) = (flattened.get(t1), flattened.get(t2), flattened.get(t3)) else { continue; };
+			let side_1 = (p1.0.0 - p2.0.0, p1.0.1 - p2.0.1);
+			let side_2 = (p1.0.0 - p3.0.0, p1.0.1 - p3.0.1);
+			let total_area = side_1.0*side_2.1 - side_1.1*side_2.0;
+			if total_area.abs() < AREA_EPSILON { continue; }
+			// flattening can flip the winding, shadows have no back side
+			let (x_min, x_max) = (p1.0.0.min(p2.0.0).min(p3.0.0).max(0.0), p1.0.0.max(p2.0.0).max(p3.0.0).min(self.width as f32 - 1.0));
+			let (y_min, y_max) = (p1.0.1.min(p2.0.1).min(p3.0.1).max(0.0), p1.0.1.max(p2.0.1).max(p3.0.1).min(self.height as f32 - 1.0));
+			if x_min > x_max || y_min > y_max { continue; }
+			for h in (y_min.ceil() as usize)..=(y_max as usize) {
+				for w in (x_min.ceil() as usize)..=(x_max as usize) {
+					let dist_p1 = (w as f32 - p1.0.0, h as f32 - p1.0.1);
+					let c = (dist_p1.0*side_1.1 - dist_p1.1*side_1.0)/total_area;
+					let b = (dist_p1.1*side_2.0 - dist_p1.0*side_2.1)/total_area;
+					let a = 1.0 - b - c;
+					if a < 0.0 || b < 0.0 || c < 0.0 { continue; }
+					if a/p1.1 + b/p2.1 + c/p3.1 <= self.depth_buffer[h][w] { mask[h][w] = true; }
+			}}
 		}
+		let keep = 1.0 - clamp(0.0, 1.0, shadow.darkness);
+		for h in 0..self.height {
+			for w in 0..self.width {
+				if mask[h][w] { self.pixel_buffer[h][w] = self.pixel_buffer[h][w].mul(keep); }
+		}}
+	}
+	
+	// copy another viewport's buffers in with its top left corner at (x, y), for split screen views
+	pub fn blit(&mut self, other: &Viewport, x: usize, y: usize) {
+		for h in 0..min(other.height, self.height.saturating_sub(y)) {
+			for w in 0..min(other.width, self.width.saturating_sub(x)) {
+				self.pixel_buffer[y+h][x+w] = other.pixel_buffer[h][w];
+				self.depth_buffer[y+h][x+w] = other.depth_buffer[h][w];
+				self.normal_buffer[y+h][x+w] = other.normal_buffer[h][w];
+		}}
+	}
+	
+	// magnify the (2*radius+1)^2 pixels around (x, y) by zoom with nearest neighbor sampling into an overlay in the corner
+	// farthest from them, the center pixel is outlined. call after drawing and before display, returns the center pixel's
+	// values so they can be printed next to the frame, or None if (x, y) is off screen
+	pub fn draw_loupe(&mut self, x: usize, y: usize, radius: usize, zoom: usize) -> Option<LoupeSample> {
+		if x >= self.width || y >= self.height { return None; }
+		let sample = LoupeSample{ position: (x, y), color: self.pixel_buffer[y][x], depth: self.depth_buffer[y][x], normal: self.normal_buffer[y][x] };
+		let zoom = max(zoom, 1);
+		let size = min((2*radius + 1)*zoom, min(self.width, self.height));
+		let left = if x < self.width/2 { self.width - size }else { 0 };
+		let top = if y < self.height/2 { self.height - size }else { 0 };
+		
+		let source = self.pixel_buffer.clone();
+		let border = Color::RGB(1.0, 1.0, 1.0);
+		for h in 0..size {
+			for w in 0..size {
+				let (sx, sy) = ((x + w/zoom).checked_sub(radius), (y + h/zoom).checked_sub(radius));
+				let color = match (sx, sy) {
+					(Some(sx), Some(sy)) if sx < self.width && sy < self.height => source[sy][sx],
+					_ => Color::black()
+				};
+				// outline the magnified center pixel and the loupe itself
+				let center = (w/zoom == radius && (w % zoom == 0 || w % zoom == zoom-1) && h/zoom == radius) ||
+					(h/zoom == radius && (h % zoom == 0 || h % zoom == zoom-1) && w/zoom == radius);
+				let edge = w == 0 || h == 0 || w == size-1 || h == size-1;
+				self.pixel_buffer[top + h][left + w] = if center || edge { border }else { color };
+		}}
+		Some(sample)
+	}
+	
+	// blend two views into this one, t goes from 0 (only from) to 1 (only to). render both views every frame and
+	// step t over time for a transition, or hold it for a before/after comparison
+	pub fn composite(&mut self, from: &Viewport, to: &Viewport, transition: Transition, t: f32) {
+		let t = clamp(0.0, 1.0, t);
+		let (height, width) = (min(self.height, min(from.height, to.height)), min(self.width, min(from.width, to.width)));
+		for h in 0..height {
+			for w in 0..width {
+				let fac = match transition {
+					Transition::Crossfade => t,
+					Transition::Wipe => if (w as f32 + 0.5) < t*self.width as f32 { 1.0 }else { 0.0 }
+				};
+				self.pixel_buffer[h][w] = from.pixel_buffer[h][w].lerp(to.pixel_buffer[h][w], fac);
+				// depth and normals can't be blended, take them from whichever view is showing more
+				let source = if fac < 0.5 { from }else { to };
+				self.depth_buffer[h][w] = source.depth_buffer[h][w];
+				self.normal_buffer[h][w] = source.normal_buffer[h][w];
+		}}
 	}
 	
 	pub fn draw_flat_texture(&mut self, tex: &Texture) {
-		for h in 0..min(tex.height, self.height) {
-			for w in 0..min(tex.width, self.width) { self.pixel_buffer[h][w] = tex.bitmap[h][w]; }
+		for h in 0..min(min(tex.height, tex.bitmap.len()), self.height) {
+			for w in 0..min(min(tex.width, tex.bitmap[h].len()), self.width) { self.pixel_buffer[h][w] = tex.bitmap[h][w]; }
 		}
 	}
 	
@@ -233,15 +1381,30 @@ impl Viewport {
 		)
 	}
 
+	// the plane is in world space, with a moved camera a near plane has to be placed relative to camera.position
 	pub fn clip_against_plane(&self, mesh: &mut Mesh, plane_pos: Vector3D, plane_normal: Vector3D) {
 		let normal = plane_normal.normalize();
 		let mut tris_to_remove = Vec::new();
+		
+		// new triangles are appended to all three arrays, so they have to line up before anything gets pushed
+		let tri_count = min(mesh.triangles.len(), min(mesh.tex_tris.len(), mesh.face_normals.len()));
+		mesh.triangles.truncate(tri_count);
+		mesh.tex_tris.truncate(tri_count);
+		mesh.face_normals.truncate(tri_count);
+		let has_colors = mesh.vertex_colors.len() == mesh.vertices.len(); // cut edges get interpolated colors too
+		let has_materials = mesh.face_materials.len() >= tri_count; // and pieces of a face keep its material
+		mesh.face_materials.truncate(if has_materials { tri_count }else { 0 });
+		let has_normal_tris = mesh.normal_tris.len() >= tri_count; // and authored corner normals
+		mesh.normal_tris.truncate(if has_normal_tris { tri_count }else { 0 });
 
 		for t in 0..mesh.triangles.len() {
+			if !mesh.triangle_in_range(t) { tris_to_remove.push(t); continue; }
 			let mut inside = Vec::new();
 			let mut outside = Vec::new();
 			let tri = [mesh.triangles[t].0, mesh.triangles[t].1, mesh.triangles[t].2];
 			let tex = [mesh.tex_tris[t].0, mesh.tex_tris[t].1, mesh.tex_tris[t].2];
+			let norm = mesh.corner_normals(t);
+			let norm_tri = mesh.normal_tris.get(t).map(|n| [n.0, n.1, n.2]).unwrap_or([0; 3]);
 			
 			// set the reference point to index 0, swap the other 2 whichever way maintains chirality of the original triangle
 			let get_orientation = |pos: usize| { 
@@ -263,7 +1426,7 @@ impl Viewport {
 			if inside.len() == 1 {
 				let (i, o1, o2) = get_orientation(inside[0]);
 				let (vi, vo1, vo2) = (mesh.vertices[tri[i]], mesh.vertices[tri[o1]], mesh.vertices[tri[o2]]);
-				let (ni, no1, no2) = (mesh.vertex_normals[tri[i]], mesh.vertex_normals[tri[o1]], mesh.vertex_normals[tri[o2]]);
+				let (ni, no1, no2) = (norm[i], norm[o1], norm[o2]);
 				let (ti, to1, to2) = (mesh.tex_coords[tex[i]], mesh.tex_coords[tex[o1]], mesh.tex_coords[tex[o2]]);
 				
 				let (fac1, fac2) = (
@@ -272,14 +1435,22 @@ impl Viewport {
 				);
 				mesh.triangles.push((tri[i], mesh.vertices.len(), mesh.vertices.len()+1));
 				mesh.face_normals.push(mesh.face_normals[t]);
+				if has_materials { mesh.face_materials.push(mesh.face_materials[t]); }
 				mesh.tex_tris.push((tex[i], mesh.tex_coords.len(), mesh.tex_coords.len()+1));
+				if has_normal_tris { mesh.normal_tris.push((norm_tri[i], mesh.normals.len(), mesh.normals.len()+1)); }
 				
 				mesh.vertices.push(vo1.lerp(vi, fac1));
 				mesh.vertices.push(vo2.lerp(vi, fac2));
 				mesh.vertex_normals.push(no1.lerp(ni, fac1));
 				mesh.vertex_normals.push(no2.lerp(ni, fac2));
+				if has_normal_tris { mesh.normals.extend_from_slice(&[no1.lerp(ni, fac1), no2.lerp(ni, fac2)]); }
 				mesh.tex_coords.push(Viewport::lerp_UV(to1, ti, fac1));
 				mesh.tex_coords.push(Viewport::lerp_UV(to2, ti, fac2));
+				if has_colors {
+					let (ci, co1, co2) = (mesh.vertex_colors[tri[i]], mesh.vertex_colors[tri[o1]], mesh.vertex_colors[tri[o2]]);
+					mesh.vertex_colors.push(co1.lerp(ci, fac1));
+					mesh.vertex_colors.push(co2.lerp(ci, fac2));
+				}
 				
 				tris_to_remove.push(t);
 			}
@@ -287,7 +1458,7 @@ impl Viewport {
 			if inside.len() == 2 {
 				let (o, i1, i2) = get_orientation(outside[0]);
 				let (vo, vi1, vi2) = (mesh.vertices[tri[o]], mesh.vertices[tri[i1]], mesh.vertices[tri[i2]]);
-				let (no, ni1, ni2) = (mesh.vertex_normals[tri[o]], mesh.vertex_normals[tri[i1]], mesh.vertex_normals[tri[i2]]);
+				let (no, ni1, ni2) = (norm[o], norm[i1], norm[i2]);
 				let (to, ti1, ti2) = (mesh.tex_coords[tex[o]], mesh.tex_coords[tex[i1]], mesh.tex_coords[tex[i2]]);
 				
 				let (fac1, fac2) = (
@@ -298,27 +1469,43 @@ impl Viewport {
 				mesh.triangles.push((mesh.vertices.len(), tri[i2], mesh.vertices.len()+1));
 				mesh.face_normals.push(mesh.face_normals[t]);
 				mesh.face_normals.push(mesh.face_normals[t]);
+				if has_materials { mesh.face_materials.extend_from_slice(&[mesh.face_materials[t], mesh.face_materials[t]]); }
 				mesh.tex_tris.push((tex[i1], tex[i2], mesh.tex_coords.len()));
 				mesh.tex_tris.push((mesh.tex_coords.len(), tex[i2], mesh.tex_coords.len()+1));
+				if has_normal_tris {
+					mesh.normal_tris.push((norm_tri[i1], norm_tri[i2], mesh.normals.len()));
+					mesh.normal_tris.push((mesh.normals.len(), norm_tri[i2], mesh.normals.len()+1));
+				}
 				
 				mesh.vertices.push(vo.lerp(vi1, fac1));
 				mesh.vertices.push(vo.lerp(vi2, fac2));
 				mesh.vertex_normals.push(no.lerp(ni1, fac1));
 				mesh.vertex_normals.push(no.lerp(ni2, fac2));
+				if has_normal_tris { mesh.normals.extend_from_slice(&[no.lerp(ni1, fac1), no.lerp(ni2, fac2)]); }
 				mesh.tex_coords.push(Viewport::lerp_UV(to, ti1, fac1));
 				mesh.tex_coords.push(Viewport::lerp_UV(to, ti2, fac2));
+				if has_colors {
+					let (co, ci1, ci2) = (mesh.vertex_colors[tri[o]], mesh.vertex_colors[tri[i1]], mesh.vertex_colors[tri[i2]]);
+					mesh.vertex_colors.push(co.lerp(ci1, fac1));
+					mesh.vertex_colors.push(co.lerp(ci2, fac2));
+				}
 				
 				tris_to_remove.push(t);
 		}}
-		let (mut new_tris, mut new_face_norms, mut new_tex_tris) = (Vec::new(), Vec::new(), Vec::new());
+		let (mut new_tris, mut new_face_norms, mut new_tex_tris, mut new_face_materials) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+		let mut new_normal_tris = Vec::new();
 		for t in 0..mesh.triangles.len() {
 			if tris_to_remove.contains(&t) { continue; }
 			new_tris.push(mesh.triangles[t]);
 			new_face_norms.push(mesh.face_normals[t]);
 			new_tex_tris.push(mesh.tex_tris[t]);
+			if has_materials { new_face_materials.push(mesh.face_materials[t]); }
+			if has_normal_tris { new_normal_tris.push(mesh.normal_tris[t]); }
 		}
 		mesh.triangles = new_tris;
 		mesh.face_normals = new_face_norms;
 		mesh.tex_tris = new_tex_tris;
+		mesh.face_materials = new_face_materials;
+		mesh.normal_tris = new_normal_tris;
 	}
 }